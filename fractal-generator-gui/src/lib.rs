@@ -6,7 +6,7 @@ use std::fmt::Debug;
 use num_traits::ToPrimitive;
 
 pub trait RgbaData: Sized + Send + Sync + 'static + Into<[u8; 4]> {
-    type T: Into<u8>;
+    type T: Into<u8> + Copy + Send + Sync + 'static;
     fn r(&self) -> Self::T;
     fn g(&self) -> Self::T;
     fn b(&self) -> Self::T;
@@ -34,7 +34,7 @@ pub trait Viewport: Send + Sync + 'static {
 type BoxedComplex<T> = Box<dyn Complex<T=T>>;
 
 pub trait Generator: GeneratorSettings + Send + Sync + 'static {
-    type B;
+    type B: RgbaData;
     type T;
     // type S;
 
@@ -44,6 +44,9 @@ pub trait Generator: GeneratorSettings + Send + Sync + 'static {
 
     fn data(&self) -> Vec<Self::B>;
 
+    /// Channels of every pixel of `data`, row by row, without copying them
+    fn rgba(&self) -> &[<Self::B as RgbaData>::T];
+
     fn zoom(&mut self, center: (u32, u32), radius: u32) -> (
         BoxedComplex<Self::T>,
         BoxedComplex<Self::T>
@@ -83,6 +86,8 @@ pub enum GeneratorValue {
     Bool(bool),
     Range((Value, Start, End, Option<Step>)),
     Viewport(GeneratorViewport),
+    /// Index of the selected option and all options
+    Choice((usize, Vec<String>)),
 }
 
 pub struct GeneratorViewport {
@@ -154,6 +159,14 @@ impl From<&GeneratorSetting> for HashMap<String, String> {
                     ("height".to_string(), height),
                 ])
             }
+            GeneratorValue::Choice((selected, options)) => {
+                let label = value.label.to_string();
+                let value = options[*selected].to_string();
+
+                HashMap::from([
+                    (label, value)
+                ])
+            }
         }
     }
 }
@@ -179,6 +192,7 @@ pub struct GeneratorSettingsOld {
     pub y2: Option<String>,
     pub exponent: Option<u32>,
     pub iterations: Option<String>,
+    pub formula: Option<String>,
 }
 
 impl GeneratorConfigOld for GeneratorSettingsOld {
@@ -189,6 +203,8 @@ pub enum GeneratorCommandMessage
 // where S
 {
     Zoom((u32, u32)),
+    /// Moves the view by this many pixels, positive towards the bottom right
+    Pan((i32, i32)),
     Settings(Vec<GeneratorSetting>),
     Reset,
 }
@@ -199,6 +215,7 @@ pub enum GeneratorOutputMessage<B, V>
           V: Viewport,
 {
     Loading(bool),
-    Image(Vec<Vec<B>>),
+    /// Channels of every pixel, row by row, like `Generator::rgba`
+    Image(Vec<B::T>),
     Viewport(V),
 }
@@ -14,17 +14,20 @@ use image::RgbaImage;
 use num_traits::Num;
 use rayon::prelude::*;
 
-use fractal_generator_gui::{GeneratorCommandMessage, GeneratorOutputMessage, GeneratorSettingsOld, RgbaData};
+use fractal_generator_gui::{GeneratorCommandMessage, GeneratorOutputMessage, GeneratorSettingsOld};
 use gui::{ComputeTask, LoadingEvent};
 use gui::generator::FractalGenerator;
 use gui::message::{GeneratorRxSender, GeneratorUiReceiver};
 use gui::plugin::GeneratorPlugin;
 use gui::resource::{GeneratorWindowSettings, ImageRes, Images, TriangleGenerator};
 use mandelbrot::config::MandelbrotConfig;
-use mandelbrot::config::viewport::Viewport;
-use mandelbrot::flatten_array;
+use mandelbrot::config::viewport::{CenteredViewport, Viewport};
+use mandelbrot::formula;
+use mandelbrot::lyapunov::{Lyapunov, LyapunovConfig};
 use mandelbrot::mandelbrot::Mandelbrot;
+use mandelbrot::newton::{Newton, NewtonConfig};
 use mandelbrot::pixel::Pixel;
+use mandelbrot::tiles::CancellationToken;
 use sierpinski_triangle::SierpinskiTriangle;
 
 const WIDTH: f32 = 1024.;
@@ -45,39 +48,81 @@ const OFFSET_RE: f32 = -(TOTAL_WIDTH / 2.);
 
 const OFFSET_IM: f32 = -(TOTAL_HEIGHT / 2.);
 
+/// Runs the generator named by the first argument, `mandelbrot`, `newton` or
+/// `lyapunov`, which defaults to the Mandelbrot set
 fn main() {
     // run()
-    let viewport = Viewport::default()
-        .with_size(WIDTH as f64, HEIGHT as f64);
-    let mandelbrot =
-        Mandelbrot::<u8, f64>::new(
-            MandelbrotConfig::default()
-                .with_dimensions((WIDTH as u32, HEIGHT as u32))
-                .with_viewport(viewport),
-            ITERATIONS,
-        );
+    let dimensions = (WIDTH as u32, HEIGHT as u32);
 
     let desired_size = WIDTH * 1.;
 
     let scale_factor = (WIDTH / desired_size) as f64;
 
-    FractalGenerator::<
-        Pixel<u8>,
-        Viewport<f64>,
-        MandelbrotConfig<u8, f64>,
-        f64
-    >::new(
-        GeneratorWindowSettings::new(
-            None,
-            WIDTH,
-            HEIGHT,
-            scale_factor,
-            SIDEBAR_WIDTH,
-            BOTTOM_PANEL_HEIGHT,
-        ),
-        mandelbrot,
-    )
-        .run()
+    let window_settings = GeneratorWindowSettings::new(
+        None,
+        WIDTH,
+        HEIGHT,
+        scale_factor,
+        SIDEBAR_WIDTH,
+        BOTTOM_PANEL_HEIGHT,
+    );
+
+    match std::env::args().nth(1).as_deref() {
+        Some("newton") => {
+            let config = NewtonConfig::default();
+            let viewport = CenteredViewport::fit(&config.viewport, dimensions).into();
+            let newton = Newton::<u8>::new(
+                config.with_dimensions(dimensions).with_viewport(viewport),
+                ITERATIONS,
+            );
+
+            FractalGenerator::<Pixel<u8>, Viewport<f64>, NewtonConfig, f64>::new(
+                window_settings,
+                newton,
+            )
+                .run()
+        }
+        Some("lyapunov") => {
+            let config = LyapunovConfig::default();
+            let viewport = CenteredViewport::fit(&config.viewport, dimensions).into();
+            let lyapunov = Lyapunov::<u8>::new(
+                config.with_dimensions(dimensions).with_viewport(viewport),
+                ITERATIONS,
+            );
+
+            FractalGenerator::<Pixel<u8>, Viewport<f64>, LyapunovConfig<u8>, f64>::new(
+                window_settings,
+                lyapunov,
+            )
+                .run()
+        }
+        _ => {
+            let viewport: Viewport<f64> =
+                CenteredViewport::fit(&Viewport::default(), dimensions).into();
+            let mandelbrot =
+                Mandelbrot::<u8, f64>::new(
+                    MandelbrotConfig::default()
+                        .with_dimensions(dimensions)
+                        .with_viewport(viewport)
+                        .with_perturbation(true)
+                        .with_series_approximation(true)
+                        .with_cardioid_check(true)
+                        .with_periodicity_check(true),
+                    ITERATIONS,
+                );
+
+            FractalGenerator::<
+                Pixel<u8>,
+                Viewport<f64>,
+                MandelbrotConfig<u8, f64>,
+                f64
+            >::new(
+                window_settings,
+                mandelbrot,
+            )
+                .run()
+        }
+    }
 }
 
 fn test_system(
@@ -282,14 +327,18 @@ fn setup_mandelbrot(
     mut ui_state: ResMut<UiState>,
     mut commands: Commands,
 ) {
-    let viewport = Viewport::default()
-        .with_size(WIDTH as f64, HEIGHT as f64);
+    let viewport: Viewport<f64> =
+        CenteredViewport::fit(&Viewport::default(), (WIDTH as u32, HEIGHT as u32)).into();
 
     let mandelbrot =
         Mandelbrot::<u8, f64>::new(
             MandelbrotConfig::default()
                 .with_dimensions((WIDTH as u32, HEIGHT as u32))
-                .with_viewport(viewport),
+                .with_viewport(viewport)
+                .with_perturbation(true)
+                .with_series_approximation(true)
+                .with_cardioid_check(true)
+                .with_periodicity_check(true),
             ITERATIONS,
         );
 
@@ -300,17 +349,28 @@ fn setup_mandelbrot(
     thread::spawn(move || {
         let mut mandelbrot = mandelbrot;
         mandelbrot.run(ITERATIONS);
-        let pixels = mandelbrot.get_pixels();
+        let pixels = mandelbrot.rgba().to_vec();
 
         generator_tx.send(GeneratorOutputMessage::Image(pixels)).unwrap();
-        for msg in generator_rx {
+        for msg in generator_rx.iter() {
             match msg {
                 GeneratorCommandMessage::Zoom((x, y)) => {
                     let (top_left, bottom_right) = mandelbrot.zoom((x, y), 200);
                     generator_tx.send(GeneratorOutputMessage::Loading(true)).unwrap();
-                    mandelbrot.recalculate(true);
+                    // A newer command makes this view obsolete, it is dropped
+                    // at the next finished tile
+                    let cancel = CancellationToken::new();
+                    let rendered = mandelbrot.recalculate_with(true, &|_| {
+                        if !generator_rx.is_empty() {
+                            cancel.cancel();
+                        }
+                    }, &cancel);
+                    if rendered.is_err() {
+                        generator_tx.send(GeneratorOutputMessage::Loading(false)).unwrap();
+                        continue;
+                    }
                     mandelbrot.redraw();
-                    let pixels = mandelbrot.get_pixels();
+                    let pixels = mandelbrot.rgba().to_vec();
                     generator_tx.send(GeneratorOutputMessage::Image(pixels)).unwrap();
                     generator_tx.send(GeneratorOutputMessage::Loading(false)).unwrap();
                     generator_tx.send(GeneratorOutputMessage::Viewport(Viewport {
@@ -318,6 +378,18 @@ fn setup_mandelbrot(
                         bottom_right,
                         width: WIDTH as f64,
                         height: HEIGHT as f64,
+                        rotation: mandelbrot.config().viewport.rotation,
+                    })).unwrap();
+                }
+                GeneratorCommandMessage::Pan((dx, dy)) => {
+                    // Only the pixels that moved into the view are computed
+                    mandelbrot.pan(dx, dy);
+                    let pixels = mandelbrot.rgba().to_vec();
+                    generator_tx.send(GeneratorOutputMessage::Image(pixels)).unwrap();
+                    generator_tx.send(GeneratorOutputMessage::Viewport(Viewport {
+                        width: WIDTH as f64,
+                        height: HEIGHT as f64,
+                        ..mandelbrot.config().viewport
                     })).unwrap();
                 }
                 GeneratorCommandMessage::Settings(settings) => {
@@ -344,10 +416,14 @@ fn setup_mandelbrot(
                     mandelbrot.reset();
                     mandelbrot.update_config(MandelbrotConfig::default()
                         .with_dimensions((WIDTH as u32, HEIGHT as u32))
-                        .with_viewport(viewport));
+                        .with_viewport(viewport)
+                        .with_perturbation(true)
+                        .with_series_approximation(true)
+                        .with_cardioid_check(true)
+                        .with_periodicity_check(true));
                     generator_tx.send(GeneratorOutputMessage::Loading(true)).unwrap();
                     mandelbrot.run(ITERATIONS);
-                    let pixels = mandelbrot.get_pixels();
+                    let pixels = mandelbrot.rgba().to_vec();
                     generator_tx.send(GeneratorOutputMessage::Image(pixels)).unwrap();
                     generator_tx.send(GeneratorOutputMessage::Loading(false)).unwrap();
                 }
@@ -372,17 +448,6 @@ fn generator_image_system(
                 ui_state.loading = is_loading;
             }
             GeneratorOutputMessage::Image(image) => {
-                let image: Vec<_> = image
-                    .into_iter()
-                    .flat_map(|row| row
-                        .into_iter()
-                        .flat_map(|pixel| [
-                            pixel.r(),
-                            pixel.g(),
-                            pixel.b(),
-                            pixel.a()
-                        ]))
-                    .collect();
                 let image = RgbaImage::from_raw(WIDTH as u32, HEIGHT as u32, image).unwrap();
                 let image = image::DynamicImage::from(image);
                 let handle = asset.add(Image::from_dynamic(image, false));
@@ -417,8 +482,7 @@ fn handle_tasks(
     for mut task in &mut tasks {
         if let Some(task) = future::block_on(future::poll_once(&mut task.0)) {
             if task {
-                let pixels = mandelbrot.get_pixels();
-                let image = RgbaImage::from_raw(WIDTH as u32, HEIGHT as u32, flatten_array(pixels))
+                let image = RgbaImage::from_raw(WIDTH as u32, HEIGHT as u32, mandelbrot.rgba().to_vec())
                     .expect("image creation");
                 let image = image::DynamicImage::from(image);
                 let handle = assets.add(Image::from_dynamic(image, false));
@@ -484,6 +548,8 @@ fn ui_example_system(
     mut is_initialized: Local<bool>,
     mut updated: Local<bool>,
     mut iters: Local<u32>,
+    // Drag that has not added up to a whole pixel yet
+    mut dragged: Local<egui::Vec2>,
     // If you need to access the ids from multiple systems, you can also initialize the `Images`
     // resource while building the app and use `Res<Images>` instead.
     // images: Res<Images>,
@@ -520,6 +586,7 @@ fn ui_example_system(
 
     let mut hue = ui_state.settings.hue.unwrap_or(200.);
     let mut exponent = ui_state.settings.exponent.unwrap_or(2);
+    let mut formula = ui_state.settings.formula.clone().unwrap_or(formula::NAMES[0].to_string());
 
     // let vertices = koch_snowflake(*iterations);
 
@@ -603,6 +670,21 @@ fn ui_example_system(
                         *updated = true;
                     }
 
+                    ui.label("Formula");
+                    let selected = formula.clone();
+                    egui::ComboBox::from_id_source("formula")
+                        .selected_text(selected.as_str())
+                        .show_ui(ui, |ui| {
+                            for name in formula::NAMES {
+                                ui.selectable_value(&mut formula, name.to_string(), name);
+                            }
+                        });
+                    ui.end_row();
+                    if formula != selected {
+                        let _ = ui_state.settings.formula.insert(formula);
+                        *updated = true;
+                    }
+
                     ui.add_space(25.);
                     ui.end_row();
 
@@ -703,7 +785,19 @@ fn ui_example_system(
             // ui.painter().extend(triangles);
         })
         .response
-        .interact(Sense::click());
+        .interact(Sense::click_and_drag());
+
+    if image_area.dragged_by(PointerButton::Primary) {
+        *dragged += image_area.drag_delta();
+        let (dx, dy) = (dragged.x.trunc(), dragged.y.trunc());
+        if dx != 0. || dy != 0. {
+            *dragged -= egui::vec2(dx, dy);
+            // Dragging the image right moves the view left
+            generator_sender
+                .send(GeneratorCommandMessage::Pan((-dx as i32, -dy as i32)))
+                .unwrap();
+        }
+    }
 
     if image_area.clicked_by(PointerButton::Primary) {
         if let Some(pos) = image_area.interact_pointer_pos() {
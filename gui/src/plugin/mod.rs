@@ -99,9 +99,9 @@ impl<G, B, V, C, T> GeneratorPlugin<G, B, V, C, T>
         generator.as_mut().0.redraw();
         let image = generator
             .0
-            .data()
-            .into_par_iter()
-            .flat_map(|pixel| pixel.into())
+            .rgba()
+            .par_iter()
+            .map(|channel| (*channel).into())
             .collect();
         let image = RgbaImage::from_raw(window.width as u32, window.height as u32, image).unwrap();
         let image = DynamicImage::from(image);
@@ -129,14 +129,7 @@ impl<G, B, V, C, T> GeneratorPlugin<G, B, V, C, T>
                 GeneratorOutputMessage::Image(image) => {
                     let image: Vec<_> = image
                         .iter()
-                        .flat_map(|row| row
-                            .iter()
-                            .flat_map(|pixel| [
-                                pixel.r().into(),
-                                pixel.g().into(),
-                                pixel.b().into(),
-                                pixel.a().into()
-                            ]))
+                        .map(|channel| (*channel).into())
                         .collect();
                     let image = RgbaImage::from_raw(window.width as u32, window.height as u32, image)
                         .unwrap();
@@ -161,6 +154,7 @@ impl<G, B, V, C, T> GeneratorPlugin<G, B, V, C, T>
         for e in rx.iter() {
             match &e.0 {
                 GeneratorCommandMessage::Zoom(_) => {}
+                GeneratorCommandMessage::Pan(_) => {}
                 GeneratorCommandMessage::Settings(settings) => {
                     generator.as_mut().0.update_settings(settings);
                 }
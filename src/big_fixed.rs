@@ -0,0 +1,447 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// Bits held by every limb
+const LIMB_BITS: i32 = 32;
+
+/// Extra bits kept on top of what the pixel size strictly needs
+const GUARD_BITS: i32 = 64;
+
+#[derive(Debug, Clone)]
+/// Signed fixed point number with a single 32 bit integer limb and an
+/// arbitrary number of 32 bit fraction limbs.
+///
+/// Used for the reference orbit and the view centre on deep zooms where
+/// `f64` runs out of mantissa. Values are expected to stay within the escape
+/// radius, so anything overflowing the integer limb is silently dropped.
+pub struct BigFixed {
+    negative: bool,
+    /// Magnitude, least significant limb first. The last limb is the integer part
+    limbs: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseBigFixedError(String);
+
+impl fmt::Display for ParseBigFixedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid fixed point number: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseBigFixedError {}
+
+impl BigFixed {
+    /// Zero with `precision` fraction limbs
+    pub fn zero(precision: usize) -> Self {
+        Self {
+            negative: false,
+            limbs: vec![0; precision + 1],
+        }
+    }
+
+    /// Number of fraction limbs needed to address pixels of `pixel_size`
+    pub fn precision_for(pixel_size: f64) -> usize {
+        let bits = -pixel_size.abs().max(f64::MIN_POSITIVE).log2().floor() as i32 + GUARD_BITS;
+        (bits.max(LIMB_BITS) / LIMB_BITS + 1) as usize
+    }
+
+    /// Exact conversion of `value`, truncated to `precision` fraction limbs
+    pub fn from_f64(value: f64, precision: usize) -> Self {
+        let mut result = Self::zero(precision);
+        if value == 0. || !value.is_finite() {
+            return result;
+        }
+
+        let bits = value.abs().to_bits();
+        let raw_exponent = ((bits >> 52) & 0x7ff) as i32;
+        let (mantissa, exponent) = if raw_exponent == 0 {
+            (bits & 0xf_ffff_ffff_ffff, -1074)
+        } else {
+            ((bits & 0xf_ffff_ffff_ffff) | (1 << 52), raw_exponent - 1075)
+        };
+
+        // value = mantissa * 2^exponent, the lowest limb has weight 2^(-32 * precision)
+        let shift = exponent + LIMB_BITS * precision as i32;
+        if shift < 0 {
+            let mantissa = if -shift >= 64 { 0 } else { mantissa >> -shift };
+            result.limbs[0] = mantissa as u32;
+            if result.limbs.len() > 1 {
+                result.limbs[1] = (mantissa >> 32) as u32;
+            }
+        } else {
+            let word = (shift / LIMB_BITS) as usize;
+            let wide = (mantissa as u128) << (shift % LIMB_BITS);
+            for i in 0..3 {
+                if let Some(limb) = result.limbs.get_mut(word + i) {
+                    *limb = (wide >> (LIMB_BITS as usize * i)) as u32;
+                }
+            }
+        }
+
+        result.negative = value < 0.;
+        result.normalize_sign();
+        result
+    }
+
+    /// Nearest `f64`, only the most significant limbs contribute
+    pub fn to_f64(&self) -> f64 {
+        let precision = self.precision() as i32;
+        let mut value = 0.;
+        let mut used = 0;
+        for (i, limb) in self.limbs.iter().enumerate().rev() {
+            if *limb != 0 || used > 0 {
+                value += *limb as f64 * 2f64.powi(LIMB_BITS * (i as i32 - precision));
+                used += 1;
+            }
+            if used == 3 {
+                break;
+            }
+        }
+        if self.negative {
+            -value
+        } else {
+            value
+        }
+    }
+
+    /// Number of fraction limbs
+    pub fn precision(&self) -> usize {
+        self.limbs.len() - 1
+    }
+
+    /// Extends or truncates the fraction to `precision` limbs
+    pub fn with_precision(mut self, precision: usize) -> Self {
+        let current = self.precision();
+        match precision.cmp(&current) {
+            Ordering::Greater => {
+                let mut limbs = vec![0; precision - current];
+                limbs.extend_from_slice(&self.limbs);
+                self.limbs = limbs;
+            }
+            Ordering::Less => {
+                self.limbs.drain(..current - precision);
+                self.normalize_sign();
+            }
+            Ordering::Equal => {}
+        }
+        self
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.iter().all(|l| *l == 0)
+    }
+
+    fn normalize_sign(&mut self) {
+        if self.is_zero() {
+            self.negative = false;
+        }
+    }
+
+    fn cmp_magnitude(&self, other: &Self) -> Ordering {
+        self.limbs.iter().rev().cmp(other.limbs.iter().rev())
+    }
+
+    fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+        let mut carry = 0u64;
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| {
+                let sum = *a as u64 + *b as u64 + carry;
+                carry = sum >> LIMB_BITS;
+                sum as u32
+            })
+            .collect()
+    }
+
+    /// `a - b` where `a >= b`
+    fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+        let mut borrow = 0i64;
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| {
+                let mut diff = *a as i64 - *b as i64 - borrow;
+                borrow = 0;
+                if diff < 0 {
+                    diff += 1 << LIMB_BITS;
+                    borrow = 1;
+                }
+                diff as u32
+            })
+            .collect()
+    }
+
+    fn aligned(&self, other: &Self) -> (Self, Self) {
+        let precision = self.precision().max(other.precision());
+        (self.clone().with_precision(precision), other.clone().with_precision(precision))
+    }
+
+    /// Multiplies by a small integer
+    pub fn mul_u32(&self, rhs: u32) -> Self {
+        let mut carry = 0u64;
+        let limbs = self.limbs
+            .iter()
+            .map(|l| {
+                let product = *l as u64 * rhs as u64 + carry;
+                carry = product >> LIMB_BITS;
+                product as u32
+            })
+            .collect();
+        let mut result = Self {
+            negative: self.negative,
+            limbs,
+        };
+        result.normalize_sign();
+        result
+    }
+
+    /// Divides by a small integer, truncating towards zero
+    pub fn div_u32(&self, rhs: u32) -> Self {
+        let mut remainder = 0u64;
+        let mut limbs = self.limbs.clone();
+        for limb in limbs.iter_mut().rev() {
+            let current = (remainder << LIMB_BITS) | *limb as u64;
+            *limb = (current / rhs as u64) as u32;
+            remainder = current % rhs as u64;
+        }
+        let mut result = Self {
+            negative: self.negative,
+            limbs,
+        };
+        result.normalize_sign();
+        result
+    }
+
+    /// Parses a decimal string with `precision` fraction limbs,
+    /// e.g. `-0.7436438870371587047521915` or `1.5e-120`
+    pub fn parse(s: &str, precision: usize) -> Result<Self, ParseBigFixedError> {
+        let err = || ParseBigFixedError(s.to_string());
+        let s = s.trim();
+        let (mantissa, exponent) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], s[i + 1..].parse::<i32>().map_err(|_| err())?),
+            None => (s, 0),
+        };
+        let (negative, mantissa) = match mantissa.strip_prefix('-') {
+            Some(m) => (true, m),
+            None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa)),
+        };
+        let (int_digits, frac_digits) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if int_digits.is_empty() && frac_digits.is_empty() {
+            return Err(err());
+        }
+        if !int_digits.chars().chain(frac_digits.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(err());
+        }
+
+        // Shift the decimal point so that only whole digits and a power of ten remain
+        let digits: Vec<u32> = int_digits
+            .chars()
+            .chain(frac_digits.chars())
+            .map(|c| c.to_digit(10).unwrap())
+            .collect();
+        let mut point = int_digits.len() as i32 + exponent;
+
+        // Digits left of the point accumulate by multiplication, the rest by division
+        let mut result = Self::zero(precision);
+        let split = point.clamp(0, digits.len() as i32) as usize;
+        for digit in &digits[..split] {
+            result = result.mul_u32(10) + Self::from_f64(*digit as f64, precision);
+        }
+        for _ in split as i32..point {
+            result = result.mul_u32(10);
+        }
+        let mut fraction = Self::zero(precision);
+        for digit in digits[split..].iter().rev() {
+            fraction = (fraction + Self::from_f64(*digit as f64, precision)).div_u32(10);
+        }
+        while point < 0 {
+            fraction = fraction.div_u32(10);
+            point += 1;
+        }
+        result = result + fraction;
+
+        result.negative = negative;
+        result.normalize_sign();
+        Ok(result)
+    }
+}
+
+impl FromStr for BigFixed {
+    type Err = ParseBigFixedError;
+
+    /// Parses with enough precision for every digit given
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.chars().filter(|c| c.is_ascii_digit()).count() as f64;
+        let exponent = s
+            .find(['e', 'E'])
+            .and_then(|i| s[i + 1..].parse::<f64>().ok())
+            .unwrap_or(0.)
+            .min(0.);
+        Self::parse(s, Self::precision_for(10f64.powf(exponent - digits).max(f64::MIN_POSITIVE)))
+    }
+}
+
+impl fmt::Display for BigFixed {
+    /// Decimal representation with as many digits as the precision holds
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let integer = *self.limbs.last().unwrap();
+        let mut fraction = self.clone();
+        fraction.negative = false;
+        *fraction.limbs.last_mut().unwrap() = 0;
+
+        let digits = f.precision().unwrap_or((self.precision() as f64 * 32. * 2f64.log10()) as usize);
+        let mut out = format!("{}{integer}.", if self.negative { "-" } else { "" });
+        for _ in 0..digits.max(1) {
+            fraction = fraction.mul_u32(10);
+            let digit = fraction.limbs.last_mut().unwrap();
+            out.push(char::from_digit(*digit, 10).unwrap());
+            *digit = 0;
+        }
+        f.write_str(&out)
+    }
+}
+
+impl PartialEq for BigFixed {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = self.aligned(other);
+        a.negative == b.negative && a.limbs == b.limbs
+    }
+}
+
+impl Add for BigFixed {
+    type Output = BigFixed;
+
+    fn add(self, other: BigFixed) -> BigFixed {
+        let (a, b) = self.aligned(&other);
+        let mut result = if a.negative == b.negative {
+            BigFixed {
+                negative: a.negative,
+                limbs: Self::add_magnitude(&a.limbs, &b.limbs),
+            }
+        } else if a.cmp_magnitude(&b) != Ordering::Less {
+            BigFixed {
+                negative: a.negative,
+                limbs: Self::sub_magnitude(&a.limbs, &b.limbs),
+            }
+        } else {
+            BigFixed {
+                negative: b.negative,
+                limbs: Self::sub_magnitude(&b.limbs, &a.limbs),
+            }
+        };
+        result.normalize_sign();
+        result
+    }
+}
+
+impl Neg for BigFixed {
+    type Output = BigFixed;
+
+    fn neg(mut self) -> BigFixed {
+        self.negative = !self.negative;
+        self.normalize_sign();
+        self
+    }
+}
+
+impl Sub for BigFixed {
+    type Output = BigFixed;
+
+    fn sub(self, other: BigFixed) -> BigFixed {
+        self + -other
+    }
+}
+
+impl Mul for &BigFixed {
+    type Output = BigFixed;
+
+    /// Schoolbook multiplication, truncated back to the larger precision
+    fn mul(self, other: &BigFixed) -> BigFixed {
+        let (a, b) = self.aligned(other);
+        let precision = a.precision();
+        let mut wide = vec![0u64; a.limbs.len() + b.limbs.len()];
+        for (i, x) in a.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, y) in b.limbs.iter().enumerate() {
+                let current = wide[i + j] + *x as u64 * *y as u64 + carry;
+                wide[i + j] = current & 0xffff_ffff;
+                carry = current >> LIMB_BITS;
+            }
+            wide[i + b.limbs.len()] += carry;
+        }
+
+        let mut result = BigFixed {
+            negative: a.negative != b.negative,
+            limbs: wide[precision..=2 * precision].iter().map(|l| *l as u32).collect(),
+        };
+        result.normalize_sign();
+        result
+    }
+}
+
+impl Mul for BigFixed {
+    type Output = BigFixed;
+
+    fn mul(self, other: BigFixed) -> BigFixed {
+        &self * &other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BigFixed;
+
+    #[test]
+    fn f64_round_trip() {
+        for v in [0., 1., -1., 0.5, -1.75, 1e-10, -2.5, 1e-200] {
+            assert_eq!(BigFixed::from_f64(v, 40).to_f64(), v);
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = BigFixed::from_f64(1.5, 4);
+        let b = BigFixed::from_f64(-0.25, 4);
+
+        assert_eq!((a.clone() + b.clone()).to_f64(), 1.25);
+        assert_eq!((b.clone() - a.clone()).to_f64(), -1.75);
+        assert_eq!((&a * &b).to_f64(), -0.375);
+        assert_eq!((&b * &b).to_f64(), 0.0625);
+    }
+
+    #[test]
+    fn keeps_digits_beyond_f64() {
+        let tiny = BigFixed::from_f64(1e-120, 16);
+        let sum = BigFixed::from_f64(1., 16) + tiny.clone();
+
+        assert_eq!(sum.to_f64(), 1.);
+        assert_eq!((sum - BigFixed::from_f64(1., 16)).to_f64(), 1e-120);
+    }
+
+    #[test]
+    fn parse() {
+        let v: BigFixed = "-0.75".parse().unwrap();
+        assert_eq!(v.to_f64(), -0.75);
+
+        let v = BigFixed::parse("1.5e-120", 16).unwrap();
+        assert!((v.to_f64() / 1.5e-120 - 1.).abs() < 1e-15);
+
+        let v = BigFixed::parse("12.5e-1", 4).unwrap();
+        assert_eq!(v.to_f64(), 1.25);
+
+        let precise = BigFixed::parse("0.10000000000000000000000000001", 4).unwrap();
+        let rough = BigFixed::parse("0.1", 4).unwrap();
+        assert!(!(precise.clone() - rough).is_zero());
+
+        assert!("1.2.3".parse::<BigFixed>().is_err());
+        assert!("".parse::<BigFixed>().is_err());
+    }
+
+    #[test]
+    fn display() {
+        let v = BigFixed::from_f64(-1.25, 2);
+        assert_eq!(format!("{v:.4}"), "-1.2500");
+    }
+}
@@ -1,10 +1,10 @@
 use std::fs::File;
 use gif::Repeat::Infinite;
 use itertools_num::linspace;
-use mandelbrot::{color_scale::ContinuousColorScale, flatten_array, mandelbrot::Mandelbrot};
+use mandelbrot::{color_scale::ContinuousColorScale, mandelbrot::Mandelbrot};
 use mandelbrot::complex_number::ComplexNumber;
 use mandelbrot::config::MandelbrotConfig;
-use mandelbrot::config::viewport::Viewport;
+use mandelbrot::config::viewport::{CenteredViewport, Viewport};
 
 
 fn main() {
@@ -23,6 +23,9 @@ fn main() {
 
     let z_y = 0.0025;
 
+    // The view turns a quarter while it zooms in
+    let spin = std::f64::consts::FRAC_PI_2;
+
     let a = ComplexNumber::new(a_x, 1.15);
     let b = ComplexNumber::new(0.5, -1.15);
 
@@ -38,26 +41,27 @@ fn main() {
         let top_left = ComplexNumber::new(a_re_range[i], a_im_range[i]);
         let bottom_right = ComplexNumber::new(b_re_range[i], b_im_range[i]);
 
-        let viewport = Viewport::<f64> {
-            top_left,
-            bottom_right,
-            width: w as f64,
-            height: h as f64,
-        };
+        let corners = Viewport::default()
+            .with_top_left(top_left)
+            .with_bottom_right(bottom_right);
+        let viewport: Viewport<f64> = CenteredViewport::fit(&corners, dimensions)
+            .with_rotation(spin * i as f64 / (f - 1) as f64)
+            .into();
 
-        let config = MandelbrotConfig::<u8> {
-            dimensions,
-            viewport,
-            color_fn: ContinuousColorScale::get_color_fn_boxed(140.0, 1.0, 1.0),
-        };
+        let config = MandelbrotConfig::<u8, f64>::default()
+            .with_dimensions(dimensions)
+            .with_viewport(viewport)
+            .with_color_fn(ContinuousColorScale::get_color_fn_boxed(140.0, 1.0, 1.0))
+            .with_perturbation(true)
+            .with_series_approximation(true)
+            .with_cardioid_check(true)
+            .with_periodicity_check(true);
 
-        let mut mandelbrot = Mandelbrot::new(config);
+        let mut mandelbrot = Mandelbrot::new(config, iter);
 
         mandelbrot.run(iter);
 
-        let pixels = mandelbrot.get_pixels();
-
-        let mut frame = gif::Frame::from_rgba(w as u16, h as u16, &mut flatten_array(pixels));
+        let mut frame = gif::Frame::from_rgba(w as u16, h as u16, &mut mandelbrot.rgba().to_vec());
         frame.delay = 50;
 
         encoder.write_frame(&frame).unwrap();
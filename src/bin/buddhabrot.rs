@@ -0,0 +1,40 @@
+use std::fs::File;
+use std::io::BufWriter;
+
+use png::HasParameters;
+
+use mandelbrot::buddhabrot::{Buddhabrot, BuddhabrotConfig, Limits};
+use mandelbrot::complex_number::ComplexNumber;
+use mandelbrot::config::viewport::{CenteredViewport, Viewport};
+use mandelbrot::pixel::PixelMath;
+
+/// Renders `buddhabrot.png`, a Nebulabrot with the red, green and blue
+/// channels limited to 5000, 500 and 50 iterations
+fn main() {
+    let dimensions = (1000, 1000);
+    let (w, h) = dimensions;
+
+    let file = File::create("buddhabrot.png").expect("Failed to create file");
+    let buf = BufWriter::new(file);
+    let mut encoder = png::Encoder::new(buf, w, h);
+    encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+
+    // 3 wide around -0.5
+    let viewport: Viewport<f64> =
+        CenteredViewport::new(ComplexNumber::new(-0.5, 0.), 3. / (w - 1) as f64, dimensions).into();
+    let config = BuddhabrotConfig::default()
+        .with_dimensions(dimensions)
+        .with_viewport(viewport)
+        .with_limits(Limits::Rgb([5000, 500, 50]))
+        .with_samples(20_000_000);
+
+    let mut buddhabrot = Buddhabrot::new(config);
+    buddhabrot.render();
+
+    let data: Vec<u8> = buddhabrot.tone_map::<u8>()
+        .iter()
+        .flat_map(|pixel| pixel.get_slice())
+        .collect();
+    writer.write_image_data(&data).unwrap();
+}
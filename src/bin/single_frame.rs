@@ -1,3 +1,4 @@
+use std::env;
 use std::fs::File;
 use std::io::BufWriter;
 
@@ -5,11 +6,14 @@ use png::HasParameters;
 
 use mandelbrot::color_scale::ContinuousColorScale;
 use mandelbrot::complex_number::ComplexNumber;
+use mandelbrot::config::antialiasing::Antialiasing;
 use mandelbrot::config::MandelbrotConfig;
-use mandelbrot::config::viewport::Viewport;
-use mandelbrot::flatten_array;
+use mandelbrot::config::viewport::{CenteredViewport, Viewport};
 use mandelbrot::mandelbrot::Mandelbrot;
+use mandelbrot::perturbation::DeepViewport;
 
+/// Renders `mandelbrot.png`, optionally centred on `<re> <im> <width>` given as
+/// decimal strings, which may go far past `f64` precision
 fn main() {
     let dimensions = (1000, 1000);
     let (w, h) = dimensions;
@@ -21,33 +25,39 @@ fn main() {
     encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
     let mut writer = encoder.write_header().unwrap();
 
-    let width = (w) as f64;
-    let height = (h) as f64;
-
     let a_x = -2.;
     let a = ComplexNumber::new(a_x, 1.15);
     let b = ComplexNumber::new(0.5, -1.15);
 
-    let viewport = Viewport::<f64> {
-        top_left: a,
-        bottom_right: b,
-        width,
-        height,
-    };
-
-    let config = MandelbrotConfig::<u8> {
+    // Square pixels that show all of `a`..`b`
+    let viewport: Viewport<f64> = CenteredViewport::fit(
+        &Viewport::default().with_top_left(a).with_bottom_right(b),
         dimensions,
-        viewport,
-        color_fn: ContinuousColorScale::get_color_fn_boxed(200.0, 1.0, 1.0),
-    };
-
-    let mut mandelbrot = Mandelbrot::new(config);
+    ).into();
+
+    let config = MandelbrotConfig::<u8, f64>::default()
+        .with_dimensions(dimensions)
+        .with_viewport(viewport)
+        .with_color_fn(ContinuousColorScale::get_color_fn_boxed(200.0, 1.0, 1.0))
+        .with_perturbation(true)
+        .with_series_approximation(true)
+        .with_cardioid_check(true)
+        .with_periodicity_check(true)
+        .with_antialiasing(Antialiasing::Adaptive { samples: 3, threshold: 1 });
+
+    let mut mandelbrot = Mandelbrot::new(config, frames);
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let [re, im, width] = args.as_slice() {
+        let width = width.parse().expect("Invalid width");
+        let deep_viewport = DeepViewport::parse(re, im, width, dimensions)
+            .expect("Invalid center");
+        mandelbrot.set_deep_viewport(deep_viewport);
+    }
 
     mandelbrot.run(frames);
 
-    let data = mandelbrot.get_pixels();
-
     writer
-        .write_image_data(flatten_array(data).as_slice())
+        .write_image_data(mandelbrot.rgba())
         .unwrap();
 }
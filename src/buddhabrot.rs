@@ -0,0 +1,341 @@
+use std::fmt::UpperHex;
+
+use num_traits::{AsPrimitive, Bounded, Unsigned};
+use rayon::prelude::*;
+
+use crate::buffer::Buffer;
+use crate::complex_number::ComplexNumber;
+use crate::config::viewport::Viewport;
+use crate::pixel::{Pixel, PixelMath};
+
+/// `c` values drawn by every rayon job from its own generator, so the output
+/// only depends on the seed and not on how the jobs are scheduled
+const SAMPLES_PER_JOB: u64 = 1 << 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Iteration limits of the density histograms
+pub enum Limits {
+    /// One histogram, drawn in grey
+    Grey(u32),
+    /// One histogram per red, green and blue channel, the Nebulabrot
+    Rgb([u32; 3]),
+}
+
+impl Limits {
+    pub fn as_slice(&self) -> &[u32] {
+        match self {
+            Limits::Grey(limit) => std::slice::from_ref(limit),
+            Limits::Rgb(limits) => limits,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BuddhabrotConfig {
+    /// The pixel dimensions of the histograms
+    pub dimensions: (u32, u32),
+    /// The `Viewport` the histograms cover
+    pub viewport: Viewport<f64>,
+    pub exponent: u32,
+    pub limits: Limits,
+    /// Record the orbits of points that do not escape instead of the ones
+    /// that do, the anti-Buddhabrot
+    pub anti: bool,
+    /// Number of random `c` values drawn by `Buddhabrot::render`
+    pub samples: u64,
+    /// The same seed always draws the same `c` values
+    pub seed: u64,
+    /// Top left and bottom right corner of the area `c` values are drawn
+    /// from, orbits starting outside the view land in it as well so this
+    /// should hold the whole set
+    pub sample_area: (ComplexNumber<f64>, ComplexNumber<f64>),
+    /// Densities are raised to `1 / gamma` when tone mapped, values above 1
+    /// bring out the rarely visited parts
+    pub gamma: f64,
+}
+
+impl BuddhabrotConfig {
+    pub fn with_dimensions(mut self, dimensions: (u32, u32)) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+
+    pub fn with_viewport(mut self, viewport: Viewport<f64>) -> Self {
+        self.viewport = viewport;
+        self
+    }
+
+    pub fn with_exponent(mut self, exponent: u32) -> Self {
+        self.exponent = exponent;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_anti(mut self, anti: bool) -> Self {
+        self.anti = anti;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: u64) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_sample_area(mut self, sample_area: (ComplexNumber<f64>, ComplexNumber<f64>))
+        -> Self {
+        self.sample_area = sample_area;
+        self
+    }
+
+    pub fn with_gamma(mut self, gamma: f64) -> Self {
+        self.gamma = gamma;
+        self
+    }
+}
+
+impl Default for BuddhabrotConfig {
+    fn default() -> Self {
+        Self {
+            dimensions: (500, 500),
+            viewport: Viewport::default().with_size(500., 500.),
+            exponent: 2,
+            limits: Limits::Grey(1000),
+            anti: false,
+            samples: 1_000_000,
+            seed: 0,
+            sample_area: (ComplexNumber::new(-2., 2.), ComplexNumber::new(2., -2.)),
+            gamma: 2.,
+        }
+    }
+}
+
+/// Density renderer: instead of colouring every pixel by its own orbit, the
+/// orbits of random `c` values are traced and every pixel counts how often
+/// they pass through it
+pub struct Buddhabrot {
+    pub config: BuddhabrotConfig,
+    /// Orbit hits of every pixel, one histogram per limit
+    densities: Vec<Buffer<u32>>,
+}
+
+impl Buddhabrot {
+    pub fn new(config: BuddhabrotConfig) -> Self {
+        Self {
+            densities: Self::empty(&config),
+            config,
+        }
+    }
+
+    fn empty(config: &BuddhabrotConfig) -> Vec<Buffer<u32>> {
+        let (w, h) = config.dimensions;
+        vec![Buffer::new(w as usize, h as usize, 0); config.limits.as_slice().len()]
+    }
+
+    /// Histograms of orbit hits, one per limit of `config.limits`
+    pub fn densities(&self) -> &[Buffer<u32>] {
+        &self.densities
+    }
+
+    /// Clears the histograms
+    pub fn reset(&mut self) {
+        self.densities = Self::empty(&self.config);
+    }
+
+    /// Draws `config.samples` random `c` values and adds their orbits to the
+    /// histograms, calling it again with another seed adds more detail
+    pub fn render(&mut self) {
+        let config = self.config;
+        let jobs = config.samples.div_ceil(SAMPLES_PER_JOB);
+        let densities = (0..jobs).into_par_iter().map(|job| {
+            let mut random = SplitMix64::new(SplitMix64::new(config.seed).next() ^ job);
+            let samples = SAMPLES_PER_JOB.min(config.samples - job * SAMPLES_PER_JOB);
+            let mut densities = Self::empty(&config);
+            let mut orbit = Vec::new();
+            for _ in 0..samples {
+                let (top_left, bottom_right) = config.sample_area;
+                let c = ComplexNumber::new(
+                    top_left.r + (bottom_right.r - top_left.r) * random.next_f64(),
+                    top_left.i + (bottom_right.i - top_left.i) * random.next_f64(),
+                );
+                self.record(c, &mut orbit, &mut densities);
+            }
+            densities
+        }).reduce(|| Self::empty(&config), add);
+
+        self.densities = add(std::mem::take(&mut self.densities), densities);
+    }
+
+    /// Traces the orbit of `c` up to the largest limit and adds it to the
+    /// histogram of every limit it belongs to
+    fn record(&self, c: ComplexNumber<f64>, orbit: &mut Vec<ComplexNumber<f64>>,
+              densities: &mut [Buffer<u32>]) {
+        let limits = self.config.limits.as_slice();
+        // Points in the main cardioid and period-2 bulb never escape
+        if !self.config.anti && self.config.exponent == 2 && in_cardioid_or_bulb(c) {
+            return;
+        }
+
+        orbit.clear();
+        let mut z = ComplexNumber::new(0., 0.);
+        let longest = limits.iter().copied().max().unwrap_or(0);
+        let mut escaped = None;
+        for n in 1..=longest {
+            z = c + z.pow(self.config.exponent);
+            if z.norm_sqr() > 4. {
+                escaped = Some(n);
+                break;
+            }
+            orbit.push(z);
+        }
+
+        for (limit, density) in limits.iter().zip(densities) {
+            let recorded = match (self.config.anti, escaped) {
+                (false, Some(n)) if n <= *limit => &orbit[..],
+                (true, Some(n)) if n <= *limit => continue,
+                (true, _) => &orbit[..*limit as usize],
+                (false, _) => continue,
+            };
+            for z in recorded {
+                if let Some((x, y)) = self.pixel(*z) {
+                    density[y][x] += 1;
+                }
+            }
+        }
+    }
+
+    /// Pixel `z` falls in, the corners of the viewport are pixel centres
+    fn pixel(&self, z: ComplexNumber<f64>) -> Option<(usize, usize)> {
+        let (w, h) = self.config.dimensions;
+        let (x, y) = self.config.viewport.complex_to_pixel(z, self.config.dimensions);
+        let (x, y) = (x.round(), y.round());
+        ((0. ..w as f64).contains(&x) && (0. ..h as f64).contains(&y))
+            .then_some((x as usize, y as usize))
+    }
+
+    /// Tone maps the histograms to pixels, scaled by the densest pixel of each
+    /// and raised to `1 / config.gamma`. A single histogram is drawn in grey,
+    /// three go to the red, green and blue channels.
+    pub fn tone_map<P>(&self) -> Buffer<Pixel<P>>
+        where
+            P: 'static + Unsigned + Bounded + UpperHex + Copy + Send + Sync + Into<f64>,
+            f64: AsPrimitive<P>,
+    {
+        let (w, h) = self.config.dimensions;
+        let maxima: Vec<f64> = self.densities.iter()
+            .map(|density| density.iter().copied().max().unwrap_or(0).max(1) as f64)
+            .collect();
+        let level = |channel: usize, x: usize, y: usize| -> P {
+            let density = self.densities[channel][y][x] as f64 / maxima[channel];
+            (P::max_value().into() * density.powf(1. / self.config.gamma)).as_()
+        };
+
+        Buffer::from_fn(w as usize, h as usize, |x, y| match self.config.limits {
+            Limits::Grey(_) => {
+                let v = level(0, x, y);
+                Pixel::new(v, v, v)
+            }
+            Limits::Rgb(_) => Pixel::new(level(0, x, y), level(1, x, y), level(2, x, y)),
+        })
+    }
+}
+
+fn add(mut a: Vec<Buffer<u32>>, b: Vec<Buffer<u32>>) -> Vec<Buffer<u32>> {
+    for (a, b) in a.iter_mut().zip(b) {
+        a.iter_mut().zip(&b).for_each(|(a, b)| *a += b);
+    }
+    a
+}
+
+fn in_cardioid_or_bulb(c: ComplexNumber<f64>) -> bool {
+    let y2 = c.i * c.i;
+    let q = (c.r - 0.25) * (c.r - 0.25) + y2;
+    q * (q + c.r - 0.25) < 0.25 * y2 || (c.r + 1.) * (c.r + 1.) + y2 < 0.0625
+}
+
+/// SplitMix64, small and fast enough for drawing sample points
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..1`
+    fn next_f64(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::complex_number::ComplexNumber;
+    use crate::config::viewport::Viewport;
+
+    use super::{Buddhabrot, BuddhabrotConfig, Limits};
+
+    fn config() -> BuddhabrotConfig {
+        BuddhabrotConfig::default()
+            .with_dimensions((48, 48))
+            .with_viewport(Viewport::default()
+                .with_top_left(ComplexNumber::new(-2., 1.5))
+                .with_bottom_right(ComplexNumber::new(1., -1.5)))
+            .with_samples(40_000)
+    }
+
+    fn render(config: BuddhabrotConfig) -> Buddhabrot {
+        let mut buddhabrot = Buddhabrot::new(config);
+        buddhabrot.render();
+        buddhabrot
+    }
+
+    fn hits(buddhabrot: &Buddhabrot, channel: usize) -> u64 {
+        buddhabrot.densities()[channel].iter().map(|hits| *hits as u64).sum()
+    }
+
+    #[test]
+    fn seeded_and_nested() {
+        let config = config().with_limits(Limits::Rgb([20, 100, 500]));
+        let buddhabrot = render(config.with_seed(7));
+        assert_eq!(buddhabrot.densities(), render(config.with_seed(7)).densities());
+        assert_ne!(buddhabrot.densities(), render(config.with_seed(8)).densities());
+
+        // Every orbit escaping within a limit escapes within the larger ones
+        assert!(hits(&buddhabrot, 0) > 0);
+        assert!(hits(&buddhabrot, 0) <= hits(&buddhabrot, 1));
+        assert!(hits(&buddhabrot, 1) <= hits(&buddhabrot, 2));
+
+        let pixels = buddhabrot.tone_map::<u8>();
+        assert!(pixels.iter().any(|pixel| pixel.r() == 255));
+        assert!(pixels.iter().any(|pixel| pixel.b() == 255));
+    }
+
+    #[test]
+    fn anti_buddhabrot_stays_inside() {
+        let anti = render(config().with_limits(Limits::Grey(200)).with_anti(true));
+
+        // The main cardioid around -0.25 is only visited by bounded orbits
+        let centre = anti.pixel(ComplexNumber::new(-0.25, 0.)).unwrap();
+        let plain = render(config().with_limits(Limits::Grey(200)));
+        assert!(anti.densities()[0][centre.1][centre.0] > 0);
+        assert!(hits(&anti, 0) > hits(&plain, 0));
+        let corner = anti.pixel(ComplexNumber::new(-1.9, 1.4)).unwrap();
+        assert_eq!(anti.densities()[0][corner.1][corner.0], 0);
+    }
+}
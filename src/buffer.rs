@@ -0,0 +1,135 @@
+use std::ops::{Index, IndexMut};
+
+use rayon::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Grid of `width`×`height` cells stored row by row in one contiguous `Vec`.
+///
+/// Indexing with a row number gives the slice of that row, so cells are read
+/// as `buffer[y][x]`.
+pub struct Buffer<T> {
+    width: usize,
+    height: usize,
+    data: Vec<T>,
+}
+
+impl<T: Clone> Buffer<T> {
+    pub fn new(width: usize, height: usize, value: T) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![value; width * height],
+        }
+    }
+
+    /// Sets every cell to `value`
+    pub fn fill(&mut self, value: T) {
+        self.data.fill(value);
+    }
+}
+
+impl<T> Buffer<T> {
+    /// Buffer with the value of `f(x, y)` in every cell
+    pub fn from_fn(width: usize, height: usize, mut f: impl FnMut(usize, usize) -> T) -> Self {
+        Self {
+            width,
+            height,
+            data: (0..height).flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| f(x, y))
+                .collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Row `y`, if it exists
+    pub fn get(&self, y: usize) -> Option<&[T]> {
+        (y < self.height).then(|| &self[y])
+    }
+
+    /// All cells, row by row
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    /// Iterates over all cells, row by row
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.data.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.data.iter_mut()
+    }
+
+    pub fn rows(&self) -> std::slice::ChunksExact<'_, T> {
+        self.data.chunks_exact(self.width.max(1))
+    }
+
+    pub fn rows_mut(&mut self) -> std::slice::ChunksExactMut<'_, T> {
+        self.data.chunks_exact_mut(self.width.max(1))
+    }
+}
+
+impl<T: Sync> Buffer<T> {
+    pub fn par_rows(&self) -> rayon::slice::ChunksExact<'_, T> {
+        self.data.par_chunks_exact(self.width.max(1))
+    }
+}
+
+impl<T> Index<usize> for Buffer<T> {
+    type Output = [T];
+
+    fn index(&self, y: usize) -> &[T] {
+        &self.data[y * self.width..(y + 1) * self.width]
+    }
+}
+
+impl<T> IndexMut<usize> for Buffer<T> {
+    fn index_mut(&mut self, y: usize) -> &mut [T] {
+        &mut self.data[y * self.width..(y + 1) * self.width]
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Buffer<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Buffer;
+
+    #[test]
+    fn row_major() {
+        let mut buffer = Buffer::from_fn(3, 2, |x, y| x + 10 * y);
+
+        assert_eq!(buffer.as_slice(), &[0, 1, 2, 10, 11, 12]);
+        assert_eq!(buffer[1][2], 12);
+        assert_eq!(buffer.get(1), Some(&[10, 11, 12][..]));
+        assert!(buffer.get(2).is_none());
+        assert_eq!(buffer.rows().count(), 2);
+
+        buffer[0][1] = 5;
+        assert_eq!(buffer.rows().next().unwrap(), &[0, 5, 2]);
+        buffer.fill(7);
+        assert!(buffer.iter().all(|v| *v == 7));
+    }
+}
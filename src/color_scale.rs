@@ -1,5 +1,10 @@
 use num_traits::{AsPrimitive, Bounded, Float, Unsigned};
+use crate::buffer::Buffer;
 use crate::complex_number::ComplexNumber;
+use crate::config::bailout::Bailout;
+use crate::config::ColorFn;
+use crate::histogram::PaletteFn;
+use crate::interior::Interior;
 use crate::pixel::{Pixel, PixelMath};
 use std::fmt::UpperHex;
 
@@ -16,6 +21,19 @@ pub trait ColorScale {
             T: From<f64>;
 }
 
+/// Colouring that looks at the whole frame before colouring any pixel, like
+/// `HistogramColoring`
+pub trait ColorScheme<P: Unsigned + Bounded + Copy + Send + Sync, F: Float + Send + Sync>:
+Send + Sync {
+    /// Called by `Mandelbrot::redraw` with the values of every pixel before
+    /// any of them is coloured, and the bailout and real exponent they were
+    /// iterated with
+    fn prepare(&mut self, values: &Buffer<(u32, ComplexNumber<F>)>, max_iterations: u32,
+               bailout: Bailout, exponent: f64);
+
+    fn color(&self, iterations: u32, z: ComplexNumber<F>, max_iterations: u32) -> Pixel<P>;
+}
+
 pub struct ContinuousColorScale {}
 
 impl ColorScale for ContinuousColorScale {
@@ -38,11 +56,14 @@ impl ColorScale for ContinuousColorScale {
             0.8,
             1.0,
             10.0,
+            Bailout::default(),
+            2.,
         )
     }
 }
 
 impl ContinuousColorScale {
+    #[allow(clippy::too_many_arguments)]
     pub fn pixel_color_gen<P: 'static + Unsigned + Bounded + Copy + UpperHex + Into<f64>, T:
     Float + Send + Sync + Into<f64>>(
         iters_to_escape: u32,
@@ -52,6 +73,8 @@ impl ContinuousColorScale {
         sat: f64,
         val: f64,
         scale: f64,
+        bailout: Bailout,
+        exponent: f64,
     ) -> Pixel<P>
         where
             f64: From<P> + AsPrimitive<P> + Into<T>,
@@ -61,9 +84,8 @@ impl ContinuousColorScale {
             return Pixel::new(P::zero(), P::zero(), P::zero());
         }
 
-        let smooth: f64 = iters_to_escape.into();
-        let smooth: f64 = (smooth.into() + (1.0).into() - ending_point.abs().log((10.0).into()).log(
-            (2.0).into())).into();
+        let ending_point = ComplexNumber::new(ending_point.r.into(), ending_point.i.into());
+        let smooth = bailout.smooth(iters_to_escape, ending_point, exponent);
 
         Pixel::from_hsb(hue + scale * smooth, sat, val).unwrap()
     }
@@ -72,14 +94,16 @@ impl ContinuousColorScale {
         hue: f64,
         sat: f64,
         val: f64,
-    ) -> impl Fn(u32, ComplexNumber<f64>, u32) -> Pixel<P>
+    ) -> impl Fn(u32, ComplexNumber<f64>, u32, Bailout, f64) -> Pixel<P>
         where
             f64: From<P> + AsPrimitive<P>,
             P: Send + Sync,
     {
         move |iters_to_escape: u32,
               ending_point: ComplexNumber<f64>,
-              num_iterations: u32|
+              num_iterations: u32,
+              bailout: Bailout,
+              exponent: f64|
               -> Pixel<P> {
             ContinuousColorScale::pixel_color_gen(
                 iters_to_escape,
@@ -89,16 +113,32 @@ impl ContinuousColorScale {
                 sat,
                 val,
                 10.0,
+                bailout,
+                exponent,
             )
         }
     }
 
+    /// Palette for `HistogramColoring` running through `range` degrees of hue
+    /// from `hue`
+    pub fn palette<P: 'static + Unsigned + Bounded + Copy + UpperHex + Send + Sync + Into<f64>>(
+        hue: f64,
+        range: f64,
+        sat: f64,
+        val: f64,
+    ) -> PaletteFn<P>
+        where
+            f64: From<P> + AsPrimitive<P>,
+    {
+        Box::new(move |position| Pixel::from_hsb(hue + range * position, sat, val).unwrap())
+    }
+
     pub fn get_color_fn_boxed<P: 'static + Unsigned + Bounded + Copy + UpperHex + Send + Sync +
     Into<f64>, T: Float + Send + Sync>(
         hue: f64,
         sat: f64,
         val: f64,
-    ) -> Box<dyn Fn(u32, ComplexNumber<T>, u32) -> Pixel<P> + Send + Sync>
+    ) -> ColorFn<P, T>
         where
             f64: From<P> + AsPrimitive<P> + From<T>,
             T: Send + Sync + From<f64>,
@@ -106,7 +146,9 @@ impl ContinuousColorScale {
         Box::new(
             move |iters_to_escape: u32,
                   ending_point: ComplexNumber<T>,
-                  num_iterations: u32|
+                  num_iterations: u32,
+                  bailout: Bailout,
+                  exponent: f64|
                   -> Pixel<P> {
                 ContinuousColorScale::pixel_color_gen(
                     iters_to_escape,
@@ -116,6 +158,8 @@ impl ContinuousColorScale {
                     sat,
                     val,
                     10.0,
+                    bailout,
+                    exponent,
                 )
             },
         )
@@ -160,3 +204,64 @@ impl ColorScale for SimpleColorScale {
         }
     }
 }
+
+pub struct DistanceColorScale {}
+
+impl DistanceColorScale {
+    /// Darkens `pixel` towards black for points closer than `thickness` pixels
+    /// to the set, which draws its filaments as sharp lines
+    pub fn boundary<P: 'static + Unsigned + Bounded + Copy + UpperHex + Send + Sync + Into<f64>>(
+        pixel: Pixel<P>,
+        distance: f64,
+        thickness: f64,
+    ) -> Pixel<P>
+        where
+            f64: AsPrimitive<P>,
+    {
+        let shade = (distance / thickness).clamp(0., 1.);
+        let (r, g, b, a) = pixel.get_tuple();
+        let scale = |v: P| (v.into() * shade).as_();
+        Pixel::new_rgba(scale(r), scale(g), scale(b), a)
+    }
+}
+
+pub struct InteriorColorScale {}
+
+impl InteriorColorScale {
+    /// Shades the inside of every hyperbolic component, with the hue following
+    /// the multiplier angle and the brightness its magnitude, so bulbs get
+    /// lighter towards their boundary
+    pub fn bulbs<P: 'static + Unsigned + Bounded + Copy + UpperHex + Send + Sync + Into<f64>>(
+        pixel: Pixel<P>,
+        interior: Interior,
+    ) -> Pixel<P>
+        where
+            f64: From<P> + AsPrimitive<P>,
+    {
+        let hue = (interior.angle().to_degrees() + 360.) % 360.;
+        let brightness = 0.2 + 0.8 * interior.multiplier.abs();
+        Pixel::from_hsb(hue, 0.6, brightness).unwrap_or(pixel)
+    }
+}
+
+pub struct LyapunovColorScale {}
+
+impl LyapunovColorScale {
+    /// Yellow where the logistic map settles on a cycle and blue where it is
+    /// chaotic, both getting darker towards an exponent of 0 where the two
+    /// meet
+    pub fn exponent<P: 'static + Unsigned + Bounded + Copy + UpperHex + Send + Sync + Into<f64>>(
+        exponent: f64,
+    ) -> Pixel<P>
+        where
+            f64: From<P> + AsPrimitive<P>,
+    {
+        let (hue, brightness) = if exponent < 0. {
+            (50., 1. - exponent.exp())
+        } else {
+            (220., 1. - (-exponent).exp())
+        };
+        Pixel::from_hsb(hue, 0.9, brightness.clamp(0., 1.))
+            .unwrap_or_else(|_| PixelMath::default())
+    }
+}
@@ -29,7 +29,11 @@ impl<T: Float + Send + Sync> ComplexNumber<T> {
         (self.r * self.r) + (self.i * self.i)
     }
 
+    /// `self^e` by repeated multiplication, 1 for `e = 0`
     pub fn pow(&self, e: u32) -> Self {
+        if e == 0 {
+            return ComplexNumber::new(T::one(), T::zero());
+        }
         let mut r = *self;
         for _ in 1..e {
             r *= *self;
@@ -37,6 +41,25 @@ impl<T: Float + Send + Sync> ComplexNumber<T> {
 
         r
     }
+
+    /// `self^e` for any complex `e` through the polar form
+    /// `exp(e ln |z| + i e arg z)`, on the principal branch
+    pub fn powc(&self, e: ComplexNumber<T>) -> Self {
+        if self.r.is_zero() && self.i.is_zero() {
+            // `0^e` is 0 for a positive real part and undefined otherwise,
+            // infinity makes orbits escape right away
+            return if e.r > T::zero() {
+                *self
+            } else {
+                ComplexNumber::new(T::infinity(), T::zero())
+            };
+        }
+
+        let (ln_abs, arg) = (self.abs().ln(), self.i.atan2(self.r));
+        let abs = (e.r * ln_abs - e.i * arg).exp();
+        let angle = e.i * ln_abs + e.r * arg;
+        ComplexNumber::new(abs * angle.cos(), abs * angle.sin())
+    }
 }
 
 impl<T: Add<Output=T> + Float + Send + Sync> Add<ComplexNumber<T>> for ComplexNumber<T> {
@@ -79,6 +102,19 @@ impl<T: Div<Output=T> + Float, R: Num + Into<T> + Copy> Div<R> for ComplexNumber
     }
 }
 
+impl<T: Div<Output=T> + Float + Send + Sync> Div<ComplexNumber<T>> for ComplexNumber<T> {
+    type Output = ComplexNumber<T>;
+
+    /// Divides our `ComplexNumber` by another `ComplexNumber`
+    fn div(self, other: ComplexNumber<T>) -> ComplexNumber<T> {
+        let norm_sqr = other.norm_sqr();
+        ComplexNumber {
+            r: (self.r * other.r + self.i * other.i) / norm_sqr,
+            i: (self.i * other.r - self.r * other.i) / norm_sqr,
+        }
+    }
+}
+
 impl<T: Mul<Output=T> + Sub<Output=T> + Add<Output=T> + Float> Mul<ComplexNumber<T>>
 for ComplexNumber<T>
     where T: Send + Sync,
@@ -186,6 +222,23 @@ mod tests {
         assert_eq!(c.i, 6.0);
     }
 
+    #[test]
+    fn complex_power() {
+        let a = ComplexNumber::new(1.0, 3.0);
+        let close = |a: ComplexNumber<f64>, b: ComplexNumber<f64>| (a.r - b.r).abs() < 1e-12
+            && (a.i - b.i).abs() < 1e-12;
+
+        assert!(close(a.powc(ComplexNumber::new(3.0, 0.0)), a.pow(3)));
+        assert_eq!(a.pow(3), ComplexNumber::new(-26.0, -18.0));
+        assert_eq!(a.pow(0), ComplexNumber::new(1.0, 0.0));
+        assert!(close(a.powc(ComplexNumber::new(-1.0, 0.0)), ComplexNumber::new(0.1, -0.3)));
+        // i^i = e^(-pi/2)
+        let i = ComplexNumber::new(0.0, 1.0);
+        assert!(close(i.powc(i), ComplexNumber::new((-std::f64::consts::FRAC_PI_2).exp(), 0.0)));
+        let zero = ComplexNumber::new(0.0, 0.0);
+        assert_eq!(zero.powc(ComplexNumber::new(2.5, 1.0)), zero);
+    }
+
     #[test]
     fn complex_multiplication_assign() {
         let mut a = ComplexNumber::new(1.0, 1.0);
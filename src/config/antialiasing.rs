@@ -0,0 +1,114 @@
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// How many samples are averaged into every pixel
+pub enum Antialiasing {
+    /// One sample in the centre of every pixel
+    #[default]
+    Off,
+    /// `n`×`n` samples spread evenly over every pixel
+    Uniform(u32),
+    /// `samples`×`samples` samples, but only for pixels with a 4-neighbour
+    /// whose iteration count differs by more than `threshold`
+    Adaptive { samples: u32, threshold: u32 },
+}
+
+impl Antialiasing {
+    /// Offsets of the samples from the pixel centre in pixels, empty when
+    /// the centre alone is used
+    pub fn offsets(&self) -> Vec<(f64, f64)> {
+        let n = match *self {
+            Antialiasing::Off => return Vec::new(),
+            Antialiasing::Uniform(n) | Antialiasing::Adaptive { samples: n, .. } => n,
+        };
+        if n < 2 {
+            return Vec::new();
+        }
+
+        let offset = |i: u32| (i as f64 + 0.5) / n as f64 - 0.5;
+        (0..n).flat_map(|y| (0..n).map(move |x| (offset(x), offset(y)))).collect()
+    }
+
+    /// Whether a pixel with `iterations` next to pixels with `neighbours` is
+    /// supersampled
+    pub fn resamples(&self, iterations: u32, neighbours: impl IntoIterator<Item = u32>) -> bool {
+        match *self {
+            Antialiasing::Off => false,
+            Antialiasing::Uniform(_) => true,
+            Antialiasing::Adaptive { threshold, .. } => neighbours
+                .into_iter()
+                .any(|neighbour| neighbour.abs_diff(iterations) > threshold),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flatten_array;
+    use crate::mandelbrot::Mandelbrot;
+    use crate::mandelbrot::tests::{config, grid, grid_iterations};
+
+    use super::Antialiasing;
+
+    #[test]
+    fn offsets() {
+        assert!(Antialiasing::Off.offsets().is_empty());
+        assert!(Antialiasing::Uniform(1).offsets().is_empty());
+        assert_eq!(
+            Antialiasing::Uniform(2).offsets(),
+            vec![(-0.25, -0.25), (0.25, -0.25), (-0.25, 0.25), (0.25, 0.25)],
+        );
+        assert_eq!(Antialiasing::Adaptive { samples: 3, threshold: 1 }.offsets()[4], (0., 0.));
+    }
+
+    #[test]
+    fn resamples() {
+        let adaptive = Antialiasing::Adaptive { samples: 3, threshold: 5 };
+        assert!(!adaptive.resamples(10, [12, 15, 5]));
+        assert!(adaptive.resamples(10, [12, 16]));
+        assert!(Antialiasing::Uniform(2).resamples(10, []));
+        assert!(!Antialiasing::Off.resamples(10, [100]));
+    }
+
+    #[test]
+    fn antialiases_mandelbrot_pixels() {
+        let mut plain = Mandelbrot::new(config((30, 20)), 100);
+        let mut uniform = Mandelbrot::new(config((30, 20))
+                                              .with_antialiasing(Antialiasing::Uniform(3)), 100);
+        let adaptive = Antialiasing::Adaptive { samples: 3, threshold: 2 };
+        let mut adaptive_direct = Mandelbrot::new(config((30, 20)).with_antialiasing(adaptive),
+                                                  100);
+        let mut adaptive_perturbed = Mandelbrot::new(config((30, 20))
+                                                         .with_perturbation(true)
+                                                         .with_antialiasing(adaptive), 100);
+        for mandelbrot in [&mut plain, &mut uniform, &mut adaptive_direct,
+            &mut adaptive_perturbed] {
+            mandelbrot.run(50);
+            mandelbrot.run(50);
+        }
+
+        let sampled = |mandelbrot: &Mandelbrot<u8, f64>| {
+            mandelbrot.samples.iter().filter(|samples| !samples.is_empty()).count()
+        };
+        assert_eq!(sampled(&plain), 0);
+        assert_eq!(sampled(&uniform), 600);
+        let adaptive = sampled(&adaptive_direct);
+        assert!(adaptive > 0 && adaptive < 300, "{adaptive}");
+        assert_eq!(sampled(&adaptive_perturbed), adaptive);
+
+        // The centre sample of an odd grid is the pixel itself
+        for (samples, value) in uniform.samples.iter().zip(uniform.values().iter()) {
+            assert_eq!(samples.len(), 9);
+            assert_eq!(samples[4].0, value.0);
+        }
+        let mut grid = Mandelbrot::new(grid().with_antialiasing(Antialiasing::Uniform(3)), 10);
+        grid.run(10);
+        let centres: Vec<_> = grid.samples.iter().map(|samples| samples[4].0).collect();
+        assert_eq!(centres, grid_iterations(10));
+        // -1 + 4/3 i, a third of a pixel above -1 + i, escapes to -16/9 - 4/3 i
+        assert_eq!(grid.samples[0][1][1].0, 1);
+
+        let pixels = flatten_array(plain.get_pixels());
+        let smoothed = flatten_array(adaptive_direct.get_pixels());
+        let differing = pixels.iter().zip(&smoothed).filter(|(a, b)| a != b).count();
+        assert!(differing > 0 && differing <= adaptive * 4);
+    }
+}
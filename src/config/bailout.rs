@@ -0,0 +1,177 @@
+use num_traits::Float;
+
+use crate::complex_number::ComplexNumber;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// Which size of `z` is compared against the bailout radius
+pub enum BailoutTest {
+    /// `|z|`, the only test the smooth colouring is exact for
+    #[default]
+    Euclidean,
+    /// `|re z|`
+    Real,
+    /// `|im z|`
+    Imaginary,
+    /// `|re z| + |im z|`
+    Manhattan,
+    /// `max(|re z|, |im z|)`
+    MaxNorm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// When an orbit counts as escaped: once `test` of `z` exceeds `radius`
+pub struct Bailout {
+    /// Larger radii make the smooth colouring more accurate at the cost of a
+    /// few more iterations per point. Below 2 some bounded orbits would count
+    /// as escaped, and at 1 or less the smooth colouring is undefined.
+    radius: f64,
+    pub test: BailoutTest,
+}
+
+impl Default for Bailout {
+    /// `|z| > 2`
+    fn default() -> Self {
+        Self {
+            radius: 2.,
+            test: BailoutTest::Euclidean,
+        }
+    }
+}
+
+impl Bailout {
+    /// Radii below 2 are raised to 2
+    pub fn with_radius(mut self, radius: f64) -> Self {
+        self.radius = radius.max(2.);
+        self
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn with_test(mut self, test: BailoutTest) -> Self {
+        self.test = test;
+        self
+    }
+
+    /// Whether `z` is still within the radius, which is false for NaN
+    pub fn bounded<F: Float + Send + Sync>(&self, z: &ComplexNumber<F>) -> bool {
+        let radius = F::from(self.radius).unwrap();
+        let (re, im) = (z.r.abs(), z.i.abs());
+        match self.test {
+            BailoutTest::Euclidean => z.norm_sqr() <= radius * radius,
+            BailoutTest::Real => re <= radius,
+            BailoutTest::Imaginary => im <= radius,
+            BailoutTest::Manhattan => re + im <= radius,
+            BailoutTest::MaxNorm => re <= radius && im <= radius,
+        }
+    }
+
+    pub fn escaped<F: Float + Send + Sync>(&self, z: &ComplexNumber<F>) -> bool {
+        !self.bounded(z)
+    }
+
+    /// Continuous iteration count of a point that escaped `z^exponent + c`
+    /// with `z` after `iterations`: `n + 1` right at the radius and `n` where
+    /// one more iteration would have reached it
+    pub fn smooth(&self, iterations: u32, z: ComplexNumber<f64>, exponent: f64) -> f64 {
+        // The escape of exponents up to 1 is not dominated by `z^exponent`,
+        // these are smoothed like squares
+        let exponent = if exponent.abs() > 1. { exponent.abs() } else { 2. };
+        iterations as f64 + 1. - (z.abs().ln() / self.radius.ln()).ln() / exponent.ln()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::complex_number::ComplexNumber;
+    use crate::mandelbrot::Mandelbrot;
+    use crate::mandelbrot::tests::{config, grid, grid_iterations, iterations};
+    use crate::pixel::{Pixel, PixelMath};
+
+    use super::{Bailout, BailoutTest};
+
+    #[test]
+    fn tests() {
+        let z = ComplexNumber::new(1.5, -1.5);
+        let bailout = Bailout::default();
+        assert!(bailout.escaped(&z));
+        assert!(bailout.with_test(BailoutTest::Real).bounded(&z));
+        assert!(bailout.with_test(BailoutTest::Imaginary).bounded(&z));
+        assert!(bailout.with_test(BailoutTest::Manhattan).escaped(&z));
+        assert!(bailout.with_test(BailoutTest::MaxNorm).bounded(&z));
+        assert!(bailout.with_radius(3.).bounded(&z));
+        assert_eq!(bailout.with_radius(3.).radius(), 3.);
+        assert!(bailout.escaped(&ComplexNumber::new(f64::NAN, 0.)));
+    }
+
+    #[test]
+    fn smooth_is_continuous() {
+        // Landing right at the radius or at its square after one iteration
+        // less is the same point of the colour scale
+        for (radius, exponent) in [(2., 2.), (100., 2.), (100., 3.)] {
+            let bailout = Bailout::default().with_radius(radius);
+            let at_radius = bailout.smooth(10, ComplexNumber::new(radius, 0.), exponent);
+            let past = ComplexNumber::new(radius.powf(exponent), 0.);
+            assert!((at_radius - 11.).abs() < 1e-9);
+            assert!((bailout.smooth(11, past, exponent) - at_radius).abs() < 1e-9);
+        }
+
+        for radius in [1., 0.5, -3., f64::NAN] {
+            let bailout = Bailout::default().with_radius(radius);
+            assert_eq!(bailout, Bailout::default());
+            assert!(bailout.smooth(10, ComplexNumber::new(3., 0.), 2.).is_finite());
+        }
+    }
+
+    #[test]
+    fn bailout_radius_and_tests() {
+        let run = |bailout, simd| {
+            let config = config((37, 23)).with_bailout(bailout).with_simd(simd);
+            let mut mandelbrot = Mandelbrot::new(config, 200);
+            mandelbrot.run(200);
+            mandelbrot.values().iter().copied().collect::<Vec<_>>()
+        };
+        let default = run(Bailout::default(), true);
+        let large = Bailout::default().with_radius(1000.);
+        let manhattan = Bailout::default().with_test(BailoutTest::Manhattan);
+        assert_eq!(run(large, true), run(large, false));
+
+        for ((i, z), ((large_i, large_z), (manhattan_i, manhattan_z))) in default.into_iter()
+            .zip(run(large, true).into_iter().zip(run(manhattan, true))) {
+            let escaped = z.norm_sqr() > 4.;
+            // Escaping further out takes a few more iterations
+            assert!(large_i >= i);
+            if large_z.norm_sqr() > 1e6 {
+                assert!(escaped && large_i <= i + 6, "{i} {large_i}");
+            }
+            // `|re| + |im|` is never below `|z|`, so it escapes no later
+            assert!(manhattan_i <= i);
+            assert_eq!(manhattan_z.r.abs() + manhattan_z.i.abs() > 2., manhattan_i < 200);
+        }
+
+        // 1 goes on through 26 and 677 to 458330, -2 + i through 1 - 3i and
+        // -10 - 5i to 73 + 101i. By the real part alone -1 + i only escapes at
+        // -9 - 5i after -1 + 3i, and -2 + i at -10 - 5i.
+        let grid = |bailout| {
+            let mut mandelbrot = Mandelbrot::new(grid().with_bailout(bailout), 10);
+            mandelbrot.run(10);
+            iterations(&mandelbrot)
+        };
+        assert_eq!(grid(large), [4, 5, 10, 4, 10, 10, 10, 5, 4, 5, 10, 4]);
+        assert_eq!(grid(manhattan), grid_iterations(10));
+        assert_eq!(grid(Bailout::default().with_test(BailoutTest::Real)),
+                   [2, 3, 10, 2, 10, 10, 10, 2, 2, 3, 10, 2]);
+
+        // Colour functions smooth with the bailout and exponent of the config
+        let config = config((4, 4))
+            .with_bailout(large)
+            .with_exponent(3)
+            .with_color_fn(Box::new(|_, _, _, bailout, exponent| {
+                Pixel::new((bailout.radius() / 10.) as u8, exponent as u8, 0)
+            }));
+        let mut mandelbrot = Mandelbrot::new(config, 20);
+        mandelbrot.run(20);
+        assert!(mandelbrot.rgba().chunks(4).all(|pixel| pixel == [100, 3, 0, 255]));
+    }
+}
@@ -1,10 +1,19 @@
 use num_traits::{AsPrimitive, Bounded, Float, Unsigned, Zero};
 use std::fmt::UpperHex;
-use crate::color_scale::ContinuousColorScale;
+use crate::color_scale::{ColorScheme, ContinuousColorScale};
 use crate::complex_number::ComplexNumber;
+use crate::config::antialiasing::Antialiasing;
+use crate::config::bailout::Bailout;
+use crate::config::render_strategy::RenderStrategy;
 use crate::config::viewport::Viewport;
+use crate::formula::{EscapeTimeFormula, Exponent, Multibrot};
+use crate::interior::Interior;
+use crate::orbit_trap::{OrbitTrap, TrapHit};
 use crate::pixel::Pixel;
 
+pub mod antialiasing;
+pub mod bailout;
+pub mod render_strategy;
 pub mod viewport;
 
 pub struct MandelbrotConfig<P: Unsigned + Bounded + UpperHex + Copy + Zero + Send + Sync, F:
@@ -16,18 +25,88 @@ Float + Send + Sync + 'static>
     pub viewport: Viewport<F>,
     /// The (Boxed) coloring function to be used
     pub color_fn: ColorFn<P, F>,
-    pub exponent: u32,
+    /// Colours every pixel instead of `color_fn` after seeing the whole frame,
+    /// see `HistogramColoring`
+    pub color_scheme: Option<Box<dyn ColorScheme<P, F>>>,
+    /// Non-integer exponents are iterated directly, without perturbation,
+    /// series approximation, SIMD or interior analysis
+    pub exponent: Exponent,
+    /// When an orbit counts as escaped, `color_fn` and `color_scheme` get it
+    /// to smooth the iteration count
+    pub bailout: Bailout,
+    /// The recurrence iterated for every point
+    pub formula: Box<dyn EscapeTimeFormula<F>>,
+    /// Renders the Julia set for this constant instead of the Mandelbrot set
+    pub julia: Option<ComplexNumber<F>>,
+    /// Iterate per pixel deltas against a high precision reference orbit,
+    /// needed for zooms deeper than ~1e-14
+    pub perturbation: bool,
+    /// Start every pixel at the iteration a series approximation around the
+    /// view centre is still accurate for, speeds up high iteration counts
+    pub series_approximation: bool,
+    /// Track the derivative of every orbit to estimate the distance of
+    /// escaped points to the set, see `Mandelbrot::distance_estimate`
+    pub distance_estimation: bool,
+    /// Recolours the pixels of escaped points by their estimated distance to
+    /// the set in pixels, used with `distance_estimation`
+    pub distance_color_fn: Option<DistanceColorFn<P>>,
+    /// Find the attracting cycle of points that do not escape, see
+    /// `Mandelbrot::interior`
+    pub interior_analysis: bool,
+    /// Recolours the pixels of bounded points by their attracting cycle, used
+    /// with `interior_analysis`
+    pub interior_color_fn: Option<InteriorColorFn<P>>,
+    /// Shape to record the closest approach of every orbit to, see
+    /// `Mandelbrot::trap`. Skipping iterations with the cardioid check or the
+    /// series approximation is turned off while a trap is set.
+    pub orbit_trap: Option<OrbitTrap>,
+    /// Recolours every pixel by the closest approach of its orbit to
+    /// `orbit_trap`
+    pub trap_color_fn: Option<TrapColorFn<P>>,
+    /// Extra samples averaged into every pixel by `color_fn`, the other
+    /// colour functions only see the pixel centre
+    pub antialiasing: Antialiasing,
+    /// Width and height of the tiles a frame is rendered in
+    pub tile_size: u32,
+    /// Which pixels of a tile are iterated
+    pub render_strategy: RenderStrategy,
+    /// Iterate `simd::LANES` pixels at a time for `f32`/`f64` views that only
+    /// need iteration counts, the results are the same as one at a time
+    pub simd: bool,
+    /// Stop right away for points inside the main cardioid or the period-2
+    /// bulb, only used with an exponent of 2
+    pub cardioid_check: bool,
+    /// Stop once the orbit of a point is found to repeat exactly
+    pub periodicity_check: bool,
 }
 
 impl<P: Unsigned + Bounded + UpperHex + Copy + Zero + Send + Sync, F:
 Float + Send + Sync> MandelbrotConfig<P, F> {
-    pub fn new(dimensions: (u32, u32), viewport: Viewport<F>, exponent: u32, color_fn: ColorFn<P,
-        F>) -> Self {
+    pub fn new(dimensions: (u32, u32), viewport: Viewport<F>, exponent: impl Into<Exponent>,
+               color_fn: ColorFn<P, F>) -> Self {
         Self {
             dimensions,
             viewport,
             color_fn,
-            exponent,
+            color_scheme: None,
+            exponent: exponent.into(),
+            bailout: Bailout::default(),
+            formula: Box::new(Multibrot),
+            julia: None,
+            perturbation: false,
+            series_approximation: false,
+            distance_estimation: false,
+            distance_color_fn: None,
+            interior_analysis: false,
+            interior_color_fn: None,
+            orbit_trap: None,
+            trap_color_fn: None,
+            antialiasing: Antialiasing::Off,
+            tile_size: 64,
+            render_strategy: RenderStrategy::BruteForce,
+            simd: true,
+            cardioid_check: false,
+            periodicity_check: false,
         }
     }
 
@@ -46,8 +125,100 @@ Float + Send + Sync> MandelbrotConfig<P, F> {
         self
     }
 
-    pub fn with_exponent(mut self, exponent: u32) -> Self {
-        self.exponent = exponent;
+    pub fn with_color_scheme(mut self, color_scheme: Option<Box<dyn ColorScheme<P, F>>>) -> Self {
+        self.color_scheme = color_scheme;
+        self
+    }
+
+    pub fn with_exponent(mut self, exponent: impl Into<Exponent>) -> Self {
+        self.exponent = exponent.into();
+        self
+    }
+
+    pub fn with_bailout(mut self, bailout: Bailout) -> Self {
+        self.bailout = bailout;
+        self
+    }
+
+    pub fn with_formula(mut self, formula: Box<dyn EscapeTimeFormula<F>>) -> Self {
+        self.formula = formula;
+        self
+    }
+
+    pub fn with_julia(mut self, julia: Option<ComplexNumber<F>>) -> Self {
+        self.julia = julia;
+        self
+    }
+
+    pub fn with_perturbation(mut self, perturbation: bool) -> Self {
+        self.perturbation = perturbation;
+        self
+    }
+
+    pub fn with_series_approximation(mut self, series_approximation: bool) -> Self {
+        self.series_approximation = series_approximation;
+        self
+    }
+
+    pub fn with_distance_estimation(mut self, distance_estimation: bool) -> Self {
+        self.distance_estimation = distance_estimation;
+        self
+    }
+
+    pub fn with_distance_color_fn(mut self, distance_color_fn: Option<DistanceColorFn<P>>)
+        -> Self {
+        self.distance_color_fn = distance_color_fn;
+        self
+    }
+
+    pub fn with_interior_analysis(mut self, interior_analysis: bool) -> Self {
+        self.interior_analysis = interior_analysis;
+        self
+    }
+
+    pub fn with_interior_color_fn(mut self, interior_color_fn: Option<InteriorColorFn<P>>)
+        -> Self {
+        self.interior_color_fn = interior_color_fn;
+        self
+    }
+
+    pub fn with_orbit_trap(mut self, orbit_trap: Option<OrbitTrap>) -> Self {
+        self.orbit_trap = orbit_trap;
+        self
+    }
+
+    pub fn with_trap_color_fn(mut self, trap_color_fn: Option<TrapColorFn<P>>) -> Self {
+        self.trap_color_fn = trap_color_fn;
+        self
+    }
+
+    pub fn with_antialiasing(mut self, antialiasing: Antialiasing) -> Self {
+        self.antialiasing = antialiasing;
+        self
+    }
+
+    pub fn with_tile_size(mut self, tile_size: u32) -> Self {
+        self.tile_size = tile_size;
+        self
+    }
+
+    pub fn with_render_strategy(mut self, render_strategy: RenderStrategy) -> Self {
+        self.render_strategy = render_strategy;
+        self
+    }
+
+    pub fn with_simd(mut self, simd: bool) -> Self {
+        self.simd = simd;
+        self
+    }
+
+    pub fn with_cardioid_check(mut self, cardioid_check: bool) -> Self {
+        self.cardioid_check = cardioid_check;
+        self
+    }
+
+    pub fn with_periodicity_check(mut self, periodicity_check: bool) -> Self {
+        self.periodicity_check = periodicity_check;
         self
     }
 }
@@ -70,4 +241,15 @@ MandelbrotConfig<P, T>
     }
 }
 
-pub type ColorFn<P, T> = Box<dyn Fn(u32, ComplexNumber<T>, u32) -> Pixel<P> + Send + Sync>;
+/// Colours a pixel from its iteration count, the point its orbit ended on and
+/// the iteration limit, with the bailout and real exponent it was iterated
+/// with
+pub type ColorFn<P, T> = Box<dyn Fn(u32, ComplexNumber<T>, u32, Bailout, f64) -> Pixel<P> +
+Send + Sync>;
+
+pub type DistanceColorFn<P> = Box<dyn Fn(Pixel<P>, f64) -> Pixel<P> + Send + Sync>;
+
+/// Gets the interior distance in pixels rather than in the complex plane
+pub type InteriorColorFn<P> = Box<dyn Fn(Pixel<P>, Interior) -> Pixel<P> + Send + Sync>;
+
+pub type TrapColorFn<P> = Box<dyn Fn(Pixel<P>, TrapHit) -> Pixel<P> + Send + Sync>;
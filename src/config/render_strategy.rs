@@ -0,0 +1,13 @@
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// How `Mandelbrot::recalculate` decides which pixels to iterate
+pub enum RenderStrategy {
+    /// Every pixel
+    #[default]
+    BruteForce,
+    /// Only the borders of rectangles, see `tiles::mariani_silver`. The
+    /// inside of a rectangle whose border has one iteration count gets the
+    /// value of a border pixel, which relies on the set being connected.
+    /// Not used with distance estimation, orbit traps or perturbation, whose
+    /// per pixel data would be wrong when copied.
+    MarianiSilver,
+}
@@ -13,19 +13,26 @@ pub struct Viewport<F>
     pub width: F,
     /// Height of the grid
     pub height: F,
+    /// Counter-clockwise rotation of the grid around its centre in radians,
+    /// `top_left` and `bottom_right` are the corners before it is rotated
+    pub rotation: F,
 }
 
 impl<T: Float + Send + Sync> Default for Viewport<T> {
+    /// The whole set from `-2 + 1.15i` to `0.5 - 1.15i` with square pixels,
+    /// 1000 of them along each axis
     fn default() -> Self {
         let a = ComplexNumber::new(T::from(-2.).unwrap(), T::from(1.15).unwrap());
         let b = ComplexNumber::new(T::from(0.5).unwrap(), T::from(-1.15).unwrap());
 
-        Self {
+        let corners = Self {
             top_left: a,
             bottom_right: b,
             width: T::from(1000.).unwrap(),
             height: T::from(1000.).unwrap(),
-        }
+            rotation: T::zero(),
+        };
+        CenteredViewport::fit(&corners, (1000, 1000)).into()
     }
 }
 
@@ -45,4 +52,285 @@ impl<T: Float + Send + Sync> Viewport<T> {
         self.bottom_right = bottom_right;
         self
     }
+
+    pub fn with_rotation(mut self, rotation: T) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// The rectangle from `top_left` to `bottom_right`, given like the
+    /// corners of this view before it is rotated, with the same rotation. The
+    /// rectangle is moved along with the rotation of this view, so it covers
+    /// the same part of the plane.
+    pub fn with_corners(self, top_left: ComplexNumber<T>, bottom_right: ComplexNumber<T>)
+        -> Self {
+        let two = T::one() + T::one();
+        let middle = ComplexNumber::new((top_left.r + bottom_right.r) / two,
+                                        (top_left.i + bottom_right.i) / two);
+        let center = self.rotate(middle);
+        let offset = ComplexNumber::new(center.r - middle.r, center.i - middle.i);
+        self.with_top_left(top_left + offset).with_bottom_right(bottom_right + offset)
+    }
+
+    /// Centre of the grid, which it is rotated around
+    pub fn center(&self) -> ComplexNumber<T> {
+        let two = T::one() + T::one();
+        ComplexNumber::new((self.top_left.r + self.bottom_right.r) / two,
+                           (self.top_left.i + self.bottom_right.i) / two)
+    }
+
+    /// Turns `z`, a point of the grid before it is rotated, by `rotation`
+    /// around the centre. Without a rotation `z` is returned as it is.
+    pub fn rotate(&self, z: ComplexNumber<T>) -> ComplexNumber<T> {
+        self.turn(z, self.rotation)
+    }
+
+    /// Inverse of `rotate`
+    pub fn unrotate(&self, z: ComplexNumber<T>) -> ComplexNumber<T> {
+        self.turn(z, -self.rotation)
+    }
+
+    fn turn(&self, z: ComplexNumber<T>, angle: T) -> ComplexNumber<T> {
+        if angle.is_zero() {
+            return z;
+        }
+        let center = self.center();
+        let (sin, cos) = angle.sin_cos();
+        let (re, im) = (z.r - center.r, z.i - center.i);
+        ComplexNumber::new(center.r + re * cos - im * sin, center.i + re * sin + im * cos)
+    }
+
+    /// Point under pixel `(x, y)` of an image of `dimensions` pixels, with
+    /// the corner pixels on the corners. Pixels outside the image continue
+    /// the grid.
+    pub fn pixel_to_complex(&self, (x, y): (T, T), dimensions: (u32, u32)) -> ComplexNumber<T> {
+        let (w, h) = Self::spans(dimensions);
+        let Viewport { top_left, bottom_right, .. } = *self;
+        self.rotate(ComplexNumber::new(
+            top_left.r + (bottom_right.r - top_left.r) * x / w,
+            top_left.i + (bottom_right.i - top_left.i) * y / h,
+        ))
+    }
+
+    /// Inverse of `pixel_to_complex`, the position of `z` in pixels, which
+    /// may lie outside the image and is not rounded
+    pub fn complex_to_pixel(&self, z: ComplexNumber<T>, dimensions: (u32, u32)) -> (T, T) {
+        let (w, h) = Self::spans(dimensions);
+        let Viewport { top_left, bottom_right, .. } = *self;
+        let z = self.unrotate(z);
+        ((z.r - top_left.r) / (bottom_right.r - top_left.r) * w,
+         (z.i - top_left.i) / (bottom_right.i - top_left.i) * h)
+    }
+
+    /// Moves the centre to pixel `center` and multiplies the pixel size by
+    /// `scale`, making the pixels of an image of `dimensions` pixels square if
+    /// they are not yet
+    pub fn zoomed(&self, center: (T, T), scale: T, dimensions: (u32, u32)) -> Self {
+        let point = self.pixel_to_complex(center, dimensions);
+        let view = CenteredViewport::fit(self, dimensions);
+        view.with_center(point).with_scale(view.scale * scale).into()
+    }
+
+    /// Pixels between the first and the last one along each axis
+    fn spans((w, h): (u32, u32)) -> (T, T) {
+        (T::from(w.max(2) - 1).unwrap(), T::from(h.max(2) - 1).unwrap())
+    }
+}
+
+/// The factor by which the pixels of an image of `dimensions` pixels shrink
+/// when the square of `radius` pixels around a point zooms to fill the shorter
+/// side of the image
+pub fn square_zoom_scale(radius: u32, (w, h): (u32, u32)) -> f64 {
+    (2 * radius) as f64 / (w.min(h).max(2) - 1) as f64
+}
+
+/// A view described by its centre and the size of its pixels, which unlike
+/// the corners of `Viewport` keeps the shape of the pixels when the view is
+/// zoomed or the image resized. Converts to and from a `Viewport` whose
+/// `width` and `height` are the pixel dimensions.
+#[derive(Debug, Clone, Copy)]
+pub struct CenteredViewport<F>
+    where F: Float + Send + Sync + 'static,
+{
+    pub center: ComplexNumber<F>,
+    /// Distance between neighbouring pixels along the real axis
+    pub scale: F,
+    /// Distance between neighbouring rows over `scale`, 1 for square pixels
+    pub aspect: F,
+    /// Counter-clockwise rotation around `center` in radians
+    pub rotation: F,
+    /// The pixel dimensions of the image
+    pub dimensions: (u32, u32),
+}
+
+impl<T: Float + Send + Sync> CenteredViewport<T> {
+    /// A view with square pixels of `scale` around `center`
+    pub fn new(center: ComplexNumber<T>, scale: T, dimensions: (u32, u32)) -> Self {
+        Self {
+            center,
+            scale,
+            aspect: T::one(),
+            rotation: T::zero(),
+            dimensions,
+        }
+    }
+
+    /// The smallest view with square pixels that shows all of `viewport` in
+    /// an image of `dimensions` pixels, with the same centre and rotation.
+    /// Any extra room is split evenly between both sides of the shorter axis.
+    pub fn fit(viewport: &Viewport<T>, dimensions: (u32, u32)) -> Self {
+        let (w, h) = Viewport::<T>::spans(dimensions);
+        let Viewport { top_left, bottom_right, rotation, .. } = *viewport;
+        let scale = ((bottom_right.r - top_left.r).abs() / w)
+            .max((top_left.i - bottom_right.i).abs() / h);
+        Self::new(viewport.center(), scale, dimensions).with_rotation(rotation)
+    }
+
+    pub fn with_center(mut self, center: ComplexNumber<T>) -> Self {
+        self.center = center;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: T) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: T) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Resizes the image around the same centre and pixel size, so a larger
+    /// image shows more of the plane instead of stretching it
+    pub fn with_dimensions(mut self, dimensions: (u32, u32)) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+
+    /// Point under pixel `(x, y)`, pixels outside the image continue the grid
+    pub fn pixel_to_complex(&self, pixel: (T, T)) -> ComplexNumber<T> {
+        let viewport: Viewport<T> = (*self).into();
+        viewport.pixel_to_complex(pixel, self.dimensions)
+    }
+
+    /// Position of `z` in pixels, which may lie outside the image
+    pub fn complex_to_pixel(&self, z: ComplexNumber<T>) -> (T, T) {
+        let viewport: Viewport<T> = (*self).into();
+        viewport.complex_to_pixel(z, self.dimensions)
+    }
+}
+
+impl<T: Float + Send + Sync> From<Viewport<T>> for CenteredViewport<T> {
+    fn from(viewport: Viewport<T>) -> Self {
+        let dimensions = (viewport.width.round().to_u32().unwrap_or(0),
+                          viewport.height.round().to_u32().unwrap_or(0));
+        let (w, h) = Viewport::<T>::spans(dimensions);
+        let Viewport { top_left, bottom_right, rotation, .. } = viewport;
+        let scale = (bottom_right.r - top_left.r) / w;
+        Self {
+            center: viewport.center(),
+            scale,
+            aspect: (top_left.i - bottom_right.i) / h / scale,
+            rotation,
+            dimensions,
+        }
+    }
+}
+
+impl<T: Float + Send + Sync> From<CenteredViewport<T>> for Viewport<T> {
+    fn from(viewport: CenteredViewport<T>) -> Self {
+        let CenteredViewport { center, scale, aspect, rotation, dimensions } = viewport;
+        let (w, h) = Self::spans(dimensions);
+        let two = T::one() + T::one();
+        let (re, im) = (w * scale / two, h * scale * aspect / two);
+        Self {
+            top_left: ComplexNumber::new(center.r - re, center.i + im),
+            bottom_right: ComplexNumber::new(center.r + re, center.i - im),
+            width: T::from(dimensions.0).unwrap(),
+            height: T::from(dimensions.1).unwrap(),
+            rotation,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use crate::complex_number::ComplexNumber;
+
+    use super::{square_zoom_scale, CenteredViewport, Viewport};
+
+    #[test]
+    fn rotates_around_centre() {
+        let viewport = Viewport::default()
+            .with_top_left(ComplexNumber::new(-1., 1.))
+            .with_bottom_right(ComplexNumber::new(3., -1.))
+            .with_rotation(FRAC_PI_2);
+        let close = |a: ComplexNumber<f64>, b: ComplexNumber<f64>| {
+            (a.r - b.r).abs() < 1e-12 && (a.i - b.i).abs() < 1e-12
+        };
+
+        // A quarter turn around 1 + 0i takes the top left corner below the centre
+        let corner = viewport.pixel_to_complex((0., 0.), (101, 51));
+        assert!(close(corner, ComplexNumber::new(0., -2.)));
+        let (x, y) = viewport.complex_to_pixel(corner, (101, 51));
+        assert!(x.abs() < 1e-9 && y.abs() < 1e-9);
+        let (x, y) = viewport.complex_to_pixel(ComplexNumber::new(1.2, 0.7), (101, 51));
+        assert!(close(viewport.pixel_to_complex((x, y), (101, 51)),
+                      ComplexNumber::new(1.2, 0.7)));
+
+        // The right half of the view, which lies above the centre after the turn
+        let half = viewport.with_corners(ComplexNumber::new(1., 1.), ComplexNumber::new(3., -1.));
+        assert_eq!(half.rotation, FRAC_PI_2);
+        assert!(close(half.center(), ComplexNumber::new(1., 1.)));
+        assert!(close(half.pixel_to_complex((0., 0.), (51, 51)),
+                      viewport.pixel_to_complex((50., 0.), (101, 51))));
+    }
+
+    #[test]
+    fn centre_and_scale() {
+        let corners = Viewport::<f64>::default()
+            .with_top_left(ComplexNumber::new(-2., 1.15))
+            .with_bottom_right(ComplexNumber::new(0.5, -1.15))
+            .with_size(101., 51.)
+            .with_rotation(0.3);
+        let centered = CenteredViewport::from(corners);
+        assert_eq!(centered.dimensions, (101, 51));
+        assert!((centered.scale - 0.025).abs() < 1e-15);
+        assert!((centered.aspect - 1.84).abs() < 1e-12);
+        let back: Viewport<f64> = centered.into();
+        for (a, b) in [(back.top_left, corners.top_left), (back.bottom_right, corners.bottom_right)] {
+            assert!((a.r - b.r).abs() < 1e-15 && (a.i - b.i).abs() < 1e-15);
+        }
+        assert_eq!((back.width, back.height, back.rotation), (101., 51., 0.3));
+
+        // Fitting keeps the whole box and the pixels square
+        let fitted = CenteredViewport::fit(&corners, (101, 51));
+        assert!((fitted.scale - 0.046).abs() < 1e-15 && fitted.aspect == 1.);
+        let fitted: Viewport<f64> = fitted.into();
+        assert!((fitted.top_left.i - 1.15).abs() < 1e-12);
+        assert!((fitted.bottom_right.r - fitted.top_left.r - 4.6).abs() < 1e-12);
+
+        // The default view is square too
+        let default = CenteredViewport::from(Viewport::<f64>::default());
+        assert!((default.aspect - 1.).abs() < 1e-12);
+        assert!((default.pixel_to_complex((0., 0.)).r + 2.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn zooms_to_a_square() {
+        let viewport = Viewport::<f64>::default()
+            .with_top_left(ComplexNumber::new(-2., 1.))
+            .with_bottom_right(ComplexNumber::new(2., -1.));
+
+        // Ten pixels across the 50 between the top and the bottom row
+        let scale = square_zoom_scale(5, (101, 51));
+        assert!((scale - 0.2).abs() < 1e-15);
+        let zoomed = viewport.zoomed((25., 25.), scale, (101, 51));
+        assert!((zoomed.center().r + 1.).abs() < 1e-12 && zoomed.center().i.abs() < 1e-12);
+        assert!((zoomed.top_left.r + 1.4).abs() < 1e-12 && (zoomed.top_left.i - 0.2).abs() < 1e-12);
+        assert!((zoomed.bottom_right.r + 0.6).abs() < 1e-12);
+    }
 }
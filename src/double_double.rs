@@ -0,0 +1,746 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::num::FpCategory;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, Sub, SubAssign};
+use std::str::FromStr;
+
+use num_traits::{Float, Num, NumCast, One, ToPrimitive, Zero};
+
+/// ln(2) split into a leading and a trailing `f64`
+const LN_2: DoubleDouble = DoubleDouble::new(std::f64::consts::LN_2, 2.3190468138462996e-17);
+/// ln(10) split into a leading and a trailing `f64`
+const LN_10: DoubleDouble = DoubleDouble::new(std::f64::consts::LN_10, -2.1707562233822494e-16);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+/// Unevaluated sum of two `f64`s with `|lo| <= ulp(hi) / 2`, giving a ~106 bit
+/// mantissa (about 32 decimal digits) with plain hardware floats.
+///
+/// Meets every bound `Mandelbrot<P, F>` puts on `F`, so it can be used instead
+/// of `f64` to zoom roughly 15 orders of magnitude deeper without perturbation.
+/// Arithmetic, `sqrt`, `exp` and the logarithms are computed to full precision,
+/// the trigonometric and hyperbolic functions only to `f64` precision.
+pub struct DoubleDouble {
+    hi: f64,
+    lo: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDoubleDoubleError(String);
+
+impl fmt::Display for ParseDoubleDoubleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid double-double: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseDoubleDoubleError {}
+
+/// `a + b` and its rounding error
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let bb = s - a;
+    (s, (a - (s - bb)) + (b - bb))
+}
+
+/// `a + b` and its rounding error, requires `|a| >= |b|`
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    (s, b - (s - a))
+}
+
+/// `a * b` and its rounding error
+fn two_prod(a: f64, b: f64) -> (f64, f64) {
+    let p = a * b;
+    (p, a.mul_add(b, -p))
+}
+
+impl DoubleDouble {
+    pub const fn new(hi: f64, lo: f64) -> Self {
+        Self { hi, lo }
+    }
+
+    /// Leading `f64`
+    pub fn hi(&self) -> f64 {
+        self.hi
+    }
+
+    /// Trailing `f64`, the rounding error of `hi`
+    pub fn lo(&self) -> f64 {
+        self.lo
+    }
+
+    /// Nearest `f64`
+    pub fn approx(self) -> f64 {
+        self.hi + self.lo
+    }
+
+    fn from_f64(value: f64) -> Self {
+        Self::new(value, 0.)
+    }
+
+    fn normalized(hi: f64, lo: f64) -> Self {
+        let (hi, lo) = quick_two_sum(hi, lo);
+        Self::new(hi, lo)
+    }
+
+    fn mul_f64(self, rhs: f64) -> Self {
+        let (p, e) = two_prod(self.hi, rhs);
+        Self::normalized(p, e + self.lo * rhs)
+    }
+
+    /// `2^n` scaling, exact as long as it does not over- or underflow
+    fn ldexp(self, n: i32) -> Self {
+        let scale = 2f64.powi(n);
+        Self::new(self.hi * scale, self.lo * scale)
+    }
+}
+
+impl From<f64> for DoubleDouble {
+    fn from(value: f64) -> Self {
+        Self::from_f64(value)
+    }
+}
+
+impl From<u32> for DoubleDouble {
+    fn from(value: u32) -> Self {
+        Self::from_f64(value as f64)
+    }
+}
+
+impl From<i32> for DoubleDouble {
+    fn from(value: i32) -> Self {
+        Self::from_f64(value as f64)
+    }
+}
+
+impl From<DoubleDouble> for f64 {
+    fn from(value: DoubleDouble) -> Self {
+        value.approx()
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        let (s, e) = two_sum(self.hi, rhs.hi);
+        let (t, f) = two_sum(self.lo, rhs.lo);
+        let (s, e) = quick_two_sum(s, e + t);
+        Self::normalized(s, e + f)
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self + -rhs
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let (p, e) = two_prod(self.hi, rhs.hi);
+        Self::normalized(p, e + (self.hi * rhs.lo + self.lo * rhs.hi))
+    }
+}
+
+impl Div for DoubleDouble {
+    type Output = Self;
+
+    /// Long division, each step recovers another ~53 bits of the quotient
+    fn div(self, rhs: Self) -> Self {
+        let q1 = self.hi / rhs.hi;
+        let r = self - rhs.mul_f64(q1);
+        let q2 = r.hi / rhs.hi;
+        let r = r - rhs.mul_f64(q2);
+        let q3 = r.hi / rhs.hi;
+        Self::normalized(q1, q2) + Self::from_f64(q3)
+    }
+}
+
+impl Rem for DoubleDouble {
+    type Output = Self;
+
+    fn rem(self, rhs: Self) -> Self {
+        self - (self / rhs).trunc() * rhs
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.hi, -self.lo)
+    }
+}
+
+impl AddAssign for DoubleDouble {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for DoubleDouble {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl MulAssign for DoubleDouble {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl DivAssign for DoubleDouble {
+    fn div_assign(&mut self, rhs: Self) {
+        *self = *self / rhs;
+    }
+}
+
+impl Zero for DoubleDouble {
+    fn zero() -> Self {
+        Self::from_f64(0.)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.hi == 0.
+    }
+}
+
+impl One for DoubleDouble {
+    fn one() -> Self {
+        Self::from_f64(1.)
+    }
+}
+
+impl Num for DoubleDouble {
+    type FromStrRadixErr = ParseDoubleDoubleError;
+
+    fn from_str_radix(str: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+        if radix == 10 {
+            str.parse()
+        } else {
+            Err(ParseDoubleDoubleError(format!("unsupported radix {radix}")))
+        }
+    }
+}
+
+impl ToPrimitive for DoubleDouble {
+    fn to_i64(&self) -> Option<i64> {
+        self.to_i128().and_then(|v| v.to_i64())
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        self.to_i128().and_then(|v| v.to_u64())
+    }
+
+    fn to_i128(&self) -> Option<i128> {
+        let t = self.trunc();
+        Some(t.hi.to_i128()? + t.lo.to_i128()?)
+    }
+
+    fn to_f64(&self) -> Option<f64> {
+        Some(self.approx())
+    }
+}
+
+impl NumCast for DoubleDouble {
+    fn from<T: ToPrimitive>(n: T) -> Option<Self> {
+        n.to_f64().map(Self::from_f64)
+    }
+}
+
+impl FromStr for DoubleDouble {
+    type Err = ParseDoubleDoubleError;
+
+    /// Parses decimal notation such as `-0.74364388703715870475` or `1.5e-20`,
+    /// keeping all digits the double-double can hold
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseDoubleDoubleError(s.to_string());
+        let s = s.trim();
+        match s {
+            "inf" | "+inf" | "infinity" => return Ok(Self::infinity()),
+            "-inf" | "-infinity" => return Ok(Self::neg_infinity()),
+            "NaN" | "nan" => return Ok(Self::nan()),
+            _ => {}
+        }
+
+        let (mantissa, mut exponent) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], s[i + 1..].parse::<i32>().map_err(|_| err())?),
+            None => (s, 0),
+        };
+        let (negative, mantissa) = match mantissa.strip_prefix('-') {
+            Some(m) => (true, m),
+            None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa)),
+        };
+        let (int_digits, frac_digits) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if int_digits.is_empty() && frac_digits.is_empty() {
+            return Err(err());
+        }
+
+        let mut value = Self::zero();
+        for c in int_digits.chars().chain(frac_digits.chars()) {
+            let digit = c.to_digit(10).ok_or_else(err)?;
+            value = value.mul_f64(10.) + Self::from_f64(digit as f64);
+        }
+        exponent -= frac_digits.len() as i32;
+
+        let scale = Self::from_f64(10.).powi(exponent.abs());
+        value = if exponent < 0 { value / scale } else { value * scale };
+
+        Ok(if negative { -value } else { value })
+    }
+}
+
+impl fmt::Display for DoubleDouble {
+    /// Up to 32 significant digits, or `precision` decimals when given
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.hi.is_finite() || self.hi == 0. {
+            return match f.precision() {
+                Some(p) => write!(f, "{:.*}", p, self.hi),
+                None => write!(f, "{}", self.hi),
+            };
+        }
+
+        let value = self.abs();
+        let mut exponent = value.hi.log10().floor() as i32;
+        let mut scaled = value / Self::from_f64(10.).powi(exponent);
+        if scaled.hi >= 10. {
+            scaled /= Self::from_f64(10.);
+            exponent += 1;
+        } else if scaled.hi < 1. {
+            scaled = scaled.mul_f64(10.);
+            exponent -= 1;
+        }
+
+        let digit_count = match f.precision() {
+            Some(p) => (exponent + 1 + p as i32).clamp(0, 34) as usize,
+            None => 32,
+        };
+        let mut digits = Vec::with_capacity(digit_count + 1);
+        for _ in 0..=digit_count {
+            let digit = scaled.hi.floor().clamp(0., 9.);
+            digits.push(digit as u8);
+            scaled = (scaled - Self::from_f64(digit)).mul_f64(10.);
+        }
+        // Round on the extra digit
+        if digits.pop().unwrap_or(0) >= 5 {
+            let mut i = digits.len();
+            loop {
+                if i == 0 {
+                    digits.insert(0, 1);
+                    exponent += 1;
+                    break;
+                }
+                i -= 1;
+                if digits[i] == 9 {
+                    digits[i] = 0;
+                } else {
+                    digits[i] += 1;
+                    break;
+                }
+            }
+        }
+
+        let mut out = String::new();
+        if self.is_sign_negative() {
+            out.push('-');
+        }
+        let digit = |i: i32| {
+            digits.get(i as usize).map_or('0', |d| char::from(b'0' + d))
+        };
+        if exponent >= 0 {
+            (0..=exponent).for_each(|i| out.push(digit(i)));
+        } else {
+            out.push('0');
+        }
+        let decimals = match f.precision() {
+            Some(p) => p as i32,
+            None => (digits.len() as i32 - exponent - 1).max(0),
+        };
+        if decimals > 0 {
+            out.push('.');
+            (1..=decimals).for_each(|i| out.push(digit(exponent + i)));
+        }
+        if f.precision().is_none() && out.contains('.') {
+            out = out.trim_end_matches('0').trim_end_matches('.').to_string();
+        }
+
+        f.write_str(&out)
+    }
+}
+
+impl Float for DoubleDouble {
+    fn nan() -> Self {
+        Self::from_f64(f64::NAN)
+    }
+
+    fn infinity() -> Self {
+        Self::from_f64(f64::INFINITY)
+    }
+
+    fn neg_infinity() -> Self {
+        Self::from_f64(f64::NEG_INFINITY)
+    }
+
+    fn neg_zero() -> Self {
+        Self::from_f64(-0.)
+    }
+
+    fn min_value() -> Self {
+        Self::from_f64(f64::MIN)
+    }
+
+    fn min_positive_value() -> Self {
+        Self::from_f64(f64::MIN_POSITIVE)
+    }
+
+    fn epsilon() -> Self {
+        Self::from_f64(2f64.powi(-104))
+    }
+
+    fn max_value() -> Self {
+        Self::from_f64(f64::MAX)
+    }
+
+    fn is_nan(self) -> bool {
+        self.hi.is_nan()
+    }
+
+    fn is_infinite(self) -> bool {
+        self.hi.is_infinite()
+    }
+
+    fn is_finite(self) -> bool {
+        self.hi.is_finite()
+    }
+
+    fn is_normal(self) -> bool {
+        self.hi.is_normal()
+    }
+
+    fn classify(self) -> FpCategory {
+        self.hi.classify()
+    }
+
+    fn floor(self) -> Self {
+        let hi = self.hi.floor();
+        if hi == self.hi {
+            Self::normalized(hi, self.lo.floor())
+        } else {
+            Self::from_f64(hi)
+        }
+    }
+
+    fn ceil(self) -> Self {
+        let hi = self.hi.ceil();
+        if hi == self.hi {
+            Self::normalized(hi, self.lo.ceil())
+        } else {
+            Self::from_f64(hi)
+        }
+    }
+
+    fn round(self) -> Self {
+        if self.is_sign_negative() {
+            -(-self).round()
+        } else {
+            (self + Self::from_f64(0.5)).floor()
+        }
+    }
+
+    fn trunc(self) -> Self {
+        if self.is_sign_negative() {
+            self.ceil()
+        } else {
+            self.floor()
+        }
+    }
+
+    fn fract(self) -> Self {
+        self - self.trunc()
+    }
+
+    fn abs(self) -> Self {
+        if self.is_sign_negative() {
+            -self
+        } else {
+            self
+        }
+    }
+
+    fn signum(self) -> Self {
+        Self::from_f64(self.hi.signum())
+    }
+
+    fn is_sign_positive(self) -> bool {
+        self.hi.is_sign_positive()
+    }
+
+    fn is_sign_negative(self) -> bool {
+        self.hi.is_sign_negative()
+    }
+
+    fn mul_add(self, a: Self, b: Self) -> Self {
+        self * a + b
+    }
+
+    fn recip(self) -> Self {
+        Self::one() / self
+    }
+
+    fn powi(self, n: i32) -> Self {
+        let mut base = self;
+        let mut exponent = n.unsigned_abs();
+        let mut result = Self::one();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result *= base;
+            }
+            base = base * base;
+            exponent >>= 1;
+        }
+        if n < 0 {
+            result.recip()
+        } else {
+            result
+        }
+    }
+
+    fn powf(self, n: Self) -> Self {
+        (self.ln() * n).exp()
+    }
+
+    /// One Newton step on top of the `f64` root doubles its precision
+    fn sqrt(self) -> Self {
+        if self.hi <= 0. {
+            return Self::from_f64(self.hi.sqrt());
+        }
+        let root = Self::from_f64(self.hi.sqrt());
+        root + (self - root * root) / root.mul_f64(2.)
+    }
+
+    /// Reduces by ln(2) and 2^-10, then sums the Taylor series
+    fn exp(self) -> Self {
+        if self.hi > 709.8 {
+            return Self::infinity();
+        }
+        if self.hi < -745.2 {
+            return Self::zero();
+        }
+
+        let k = (self.hi / LN_2.hi).round();
+        let r = (self - LN_2.mul_f64(k)).ldexp(-10);
+
+        let mut sum = Self::zero();
+        let mut term = Self::one();
+        for i in 1..=24 {
+            term = term * r / Self::from_f64(i as f64);
+            sum += term;
+            if term.hi.abs() < 1e-36 {
+                break;
+            }
+        }
+        // exp(r * 2^10) - 1 by repeated (s + 1)^2 - 1 = s^2 + 2s
+        for _ in 0..10 {
+            sum = sum * sum + sum.mul_f64(2.);
+        }
+
+        (sum + Self::one()).ldexp(k as i32)
+    }
+
+    /// Newton iteration on `exp(y) = x` starting from the `f64` logarithm
+    fn ln(self) -> Self {
+        if self.hi <= 0. || !self.hi.is_finite() {
+            return Self::from_f64(self.hi.ln());
+        }
+        let mut y = Self::from_f64(self.hi.ln());
+        for _ in 0..2 {
+            y = y + self * (-y).exp() - Self::one();
+        }
+        y
+    }
+
+    fn log(self, base: Self) -> Self {
+        self.ln() / base.ln()
+    }
+
+    fn exp2(self) -> Self {
+        (self * LN_2).exp()
+    }
+
+    fn log2(self) -> Self {
+        self.ln() / LN_2
+    }
+
+    fn log10(self) -> Self {
+        self.ln() / LN_10
+    }
+
+    fn max(self, other: Self) -> Self {
+        match self.partial_cmp(&other) {
+            Some(Ordering::Less) => other,
+            None if self.is_nan() => other,
+            _ => self,
+        }
+    }
+
+    fn min(self, other: Self) -> Self {
+        match self.partial_cmp(&other) {
+            Some(Ordering::Greater) => other,
+            None if self.is_nan() => other,
+            _ => self,
+        }
+    }
+
+    fn abs_sub(self, other: Self) -> Self {
+        if self <= other {
+            Self::zero()
+        } else {
+            self - other
+        }
+    }
+
+    fn cbrt(self) -> Self {
+        let root = Self::from_f64(self.hi.cbrt());
+        if root.hi == 0. || !root.hi.is_finite() {
+            return root;
+        }
+        root - (root * root * root - self) / (root * root).mul_f64(3.)
+    }
+
+    fn hypot(self, other: Self) -> Self {
+        (self * self + other * other).sqrt()
+    }
+
+    fn sin(self) -> Self {
+        Self::from_f64(self.approx().sin())
+    }
+
+    fn cos(self) -> Self {
+        Self::from_f64(self.approx().cos())
+    }
+
+    fn tan(self) -> Self {
+        Self::from_f64(self.approx().tan())
+    }
+
+    fn asin(self) -> Self {
+        Self::from_f64(self.approx().asin())
+    }
+
+    fn acos(self) -> Self {
+        Self::from_f64(self.approx().acos())
+    }
+
+    fn atan(self) -> Self {
+        Self::from_f64(self.approx().atan())
+    }
+
+    fn atan2(self, other: Self) -> Self {
+        Self::from_f64(self.approx().atan2(other.approx()))
+    }
+
+    fn sin_cos(self) -> (Self, Self) {
+        (self.sin(), self.cos())
+    }
+
+    fn exp_m1(self) -> Self {
+        self.exp() - Self::one()
+    }
+
+    fn ln_1p(self) -> Self {
+        (self + Self::one()).ln()
+    }
+
+    fn sinh(self) -> Self {
+        Self::from_f64(self.approx().sinh())
+    }
+
+    fn cosh(self) -> Self {
+        Self::from_f64(self.approx().cosh())
+    }
+
+    fn tanh(self) -> Self {
+        Self::from_f64(self.approx().tanh())
+    }
+
+    fn asinh(self) -> Self {
+        Self::from_f64(self.approx().asinh())
+    }
+
+    fn acosh(self) -> Self {
+        Self::from_f64(self.approx().acosh())
+    }
+
+    fn atanh(self) -> Self {
+        Self::from_f64(self.approx().atanh())
+    }
+
+    fn integer_decode(self) -> (u64, i16, i8) {
+        self.hi.integer_decode()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_traits::Float;
+
+    use super::DoubleDouble;
+
+    fn dd(s: &str) -> DoubleDouble {
+        s.parse().unwrap()
+    }
+
+    fn assert_close(a: DoubleDouble, b: DoubleDouble) {
+        assert!((a - b).abs() < dd("1e-30") * b.abs().max(dd("1")), "{a} != {b}");
+    }
+
+    #[test]
+    fn keeps_digits_beyond_f64() {
+        let one: DoubleDouble = 1.0.into();
+        let tiny = dd("1e-25");
+
+        assert_eq!(f64::from((one + tiny) - one), 1e-25);
+        assert_eq!(1.0 + 1e-25 - 1.0, 0.0);
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_close(dd("0.1") * dd("3"), dd("0.3"));
+        assert_close(dd("1") / dd("3") * dd("3"), dd("1"));
+        assert_close(dd("2").sqrt() * dd("2").sqrt(), dd("2"));
+        assert_close(dd("7.5") % dd("2"), dd("1.5"));
+        assert_close(dd("1.5").powi(-3), dd("8") / dd("27"));
+    }
+
+    #[test]
+    fn transcendental() {
+        assert_close(dd("1").exp(), dd("2.718281828459045235360287471352662"));
+        assert_close(dd("10").ln(), dd("2.302585092994045684017991454684364"));
+        assert_close(dd("12.25").ln().exp(), dd("12.25"));
+        assert_close(dd("1000").log10(), dd("3"));
+    }
+
+    #[test]
+    fn rounding() {
+        assert_eq!(dd("-1.5").floor(), dd("-2"));
+        assert_eq!(dd("-1.5").trunc(), dd("-1"));
+        assert_eq!(dd("2.5").round(), dd("3"));
+        assert_eq!((dd("1") + dd("1e-20")).ceil(), dd("2"));
+    }
+
+    #[test]
+    fn parse_and_display() {
+        let s = "-0.7436438870371587047521915061147";
+        assert_eq!(dd(s).to_string(), s);
+        assert_eq!(format!("{:.3}", dd("1.23456")), "1.235");
+        assert_eq!(dd("12.5e-1").to_string(), "1.25");
+        assert!("1.2.3".parse::<DoubleDouble>().is_err());
+        assert!("".parse::<DoubleDouble>().is_err());
+    }
+}
@@ -0,0 +1,338 @@
+use num_traits::Float;
+
+use crate::complex_number::ComplexNumber;
+
+/// Names of the formulas shipped with the crate, in the order the GUI lists them
+pub const NAMES: [&str; 5] = ["Mandelbrot", "Burning Ship", "Tricorn", "Celtic", "Buffalo"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Power `e` of the `z^e` the formulas are built on
+pub enum Exponent {
+    /// Powers by repeated multiplication, exact and fast
+    Integer(u32),
+    /// Powers through the polar form, for real exponents that are not whole
+    /// numbers, negative ones and complex ones
+    Complex(ComplexNumber<f64>),
+}
+
+impl Default for Exponent {
+    fn default() -> Self {
+        Exponent::Integer(2)
+    }
+}
+
+impl From<u32> for Exponent {
+    fn from(exponent: u32) -> Self {
+        Exponent::Integer(exponent)
+    }
+}
+
+impl From<f64> for Exponent {
+    /// Whole numbers from 1 up become `Integer`, so a continuously animated
+    /// exponent lands on the exact path whenever it passes one
+    fn from(exponent: f64) -> Self {
+        ComplexNumber::new(exponent, 0.).into()
+    }
+}
+
+impl From<ComplexNumber<f64>> for Exponent {
+    fn from(exponent: ComplexNumber<f64>) -> Self {
+        let whole = exponent.i == 0. && exponent.r.fract() == 0.
+            && exponent.r >= 1. && exponent.r <= u32::MAX as f64;
+        if whole {
+            Exponent::Integer(exponent.r as u32)
+        } else {
+            Exponent::Complex(exponent)
+        }
+    }
+}
+
+impl Exponent {
+    /// The exponent if it is one for `Integer`, which perturbation, series
+    /// approximation and the other integer only paths need
+    pub fn as_integer(&self) -> Option<u32> {
+        match *self {
+            Exponent::Integer(exponent) => Some(exponent),
+            Exponent::Complex(_) => None,
+        }
+    }
+
+    pub fn real(&self) -> f64 {
+        match *self {
+            Exponent::Integer(exponent) => exponent as f64,
+            Exponent::Complex(exponent) => exponent.r,
+        }
+    }
+
+    pub fn imaginary(&self) -> f64 {
+        match *self {
+            Exponent::Integer(_) => 0.,
+            Exponent::Complex(exponent) => exponent.i,
+        }
+    }
+
+    /// `z^e`
+    pub fn power<F: Float + Send + Sync>(&self, z: ComplexNumber<F>) -> ComplexNumber<F> {
+        match *self {
+            Exponent::Integer(exponent) => z.pow(exponent),
+            Exponent::Complex(exponent) => z.powc(Self::cast(exponent)),
+        }
+    }
+
+    /// `e z^(e - 1)`, the derivative of `z^e`
+    pub fn derivative<F: Float + Send + Sync>(&self, z: ComplexNumber<F>) -> ComplexNumber<F> {
+        match *self {
+            Exponent::Integer(0) => ComplexNumber::new(F::zero(), F::zero()),
+            Exponent::Integer(1) => ComplexNumber::new(F::one(), F::zero()),
+            Exponent::Integer(exponent) => z.pow(exponent - 1) * F::from(exponent).unwrap(),
+            Exponent::Complex(exponent) => {
+                let e: ComplexNumber<F> = Self::cast(exponent);
+                z.powc(ComplexNumber::new(e.r - F::one(), e.i)) * e
+            }
+        }
+    }
+
+    fn cast<F: Float + Send + Sync>(exponent: ComplexNumber<f64>) -> ComplexNumber<F> {
+        ComplexNumber::new(F::from(exponent.r).unwrap(), F::from(exponent.i).unwrap())
+    }
+}
+
+/// Recurrence iterated for every point of an escape-time fractal, starting from
+/// `z = c` until it escapes past `MandelbrotConfig::bailout` or the iteration
+/// limit is reached
+pub trait EscapeTimeFormula<F: Float + Send + Sync>: Send + Sync {
+    /// Name shown in the GUI and accepted by `by_name`
+    fn name(&self) -> &'static str;
+
+    /// Value following `z` in the orbit of `c`
+    fn iterate(&self, z: ComplexNumber<F>, c: ComplexNumber<F>, exponent: Exponent)
+        -> ComplexNumber<F>;
+
+    /// Whether this is plain `z^exponent + c`, which perturbation, series
+    /// approximation and the cardioid check are derived for with integer
+    /// exponents
+    fn is_multibrot(&self) -> bool {
+        false
+    }
+}
+
+/// Looks up one of the formulas in `NAMES`
+pub fn by_name<F: Float + Send + Sync>(name: &str) -> Option<Box<dyn EscapeTimeFormula<F>>> {
+    match name {
+        "Mandelbrot" => Some(Box::new(Multibrot)),
+        "Burning Ship" => Some(Box::new(BurningShip)),
+        "Tricorn" => Some(Box::new(Tricorn)),
+        "Celtic" => Some(Box::new(Celtic)),
+        "Buffalo" => Some(Box::new(Buffalo)),
+        _ => None,
+    }
+}
+
+/// `z^e + c`, the Mandelbrot set for an exponent of 2
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Multibrot;
+
+impl<F: Float + Send + Sync> EscapeTimeFormula<F> for Multibrot {
+    fn name(&self) -> &'static str {
+        NAMES[0]
+    }
+
+    fn iterate(&self, z: ComplexNumber<F>, c: ComplexNumber<F>, exponent: Exponent)
+        -> ComplexNumber<F> {
+        c + exponent.power(z)
+    }
+
+    fn is_multibrot(&self) -> bool {
+        true
+    }
+}
+
+/// `(|Re z| + i|Im z|)^e + c`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BurningShip;
+
+impl<F: Float + Send + Sync> EscapeTimeFormula<F> for BurningShip {
+    fn name(&self) -> &'static str {
+        NAMES[1]
+    }
+
+    fn iterate(&self, z: ComplexNumber<F>, c: ComplexNumber<F>, exponent: Exponent)
+        -> ComplexNumber<F> {
+        c + exponent.power(ComplexNumber::new(z.r.abs(), z.i.abs()))
+    }
+}
+
+/// `conj(z)^e + c`, also known as the Mandelbar set
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tricorn;
+
+impl<F: Float + Send + Sync> EscapeTimeFormula<F> for Tricorn {
+    fn name(&self) -> &'static str {
+        NAMES[2]
+    }
+
+    fn iterate(&self, z: ComplexNumber<F>, c: ComplexNumber<F>, exponent: Exponent)
+        -> ComplexNumber<F> {
+        c + exponent.power(ComplexNumber::new(z.r, -z.i))
+    }
+}
+
+/// `|Re z^e| + i Im z^e + c`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Celtic;
+
+impl<F: Float + Send + Sync> EscapeTimeFormula<F> for Celtic {
+    fn name(&self) -> &'static str {
+        NAMES[3]
+    }
+
+    fn iterate(&self, z: ComplexNumber<F>, c: ComplexNumber<F>, exponent: Exponent)
+        -> ComplexNumber<F> {
+        let w = exponent.power(z);
+        c + ComplexNumber::new(w.r.abs(), w.i)
+    }
+}
+
+/// `|Re z^e| + i|Im z^e| + c`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Buffalo;
+
+impl<F: Float + Send + Sync> EscapeTimeFormula<F> for Buffalo {
+    fn name(&self) -> &'static str {
+        NAMES[4]
+    }
+
+    fn iterate(&self, z: ComplexNumber<F>, c: ComplexNumber<F>, exponent: Exponent)
+        -> ComplexNumber<F> {
+        let w = exponent.power(z);
+        c + ComplexNumber::new(w.r.abs(), w.i.abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::complex_number::ComplexNumber;
+    use crate::config::antialiasing::Antialiasing;
+    use crate::mandelbrot::Mandelbrot;
+    use crate::mandelbrot::tests::{config, grid, iterations};
+
+    use super::{by_name, EscapeTimeFormula, Exponent, NAMES};
+
+    fn step(name: &str, z: (f64, f64)) -> (f64, f64) {
+        let formula: Box<dyn EscapeTimeFormula<f64>> = by_name(name).unwrap();
+        let z = formula.iterate(ComplexNumber::new(z.0, z.1), ComplexNumber::new(0.5, 0.25),
+                                   Exponent::Integer(2));
+        (z.r, z.i)
+    }
+
+    #[test]
+    fn iterate() {
+        // z = -1 - 2i, z^2 = -3 + 4i
+        assert_eq!(step("Mandelbrot", (-1., -2.)), (-2.5, 4.25));
+        // (1 + 2i)^2 = -3 + 4i
+        assert_eq!(step("Burning Ship", (-1., -2.)), (-2.5, 4.25));
+        // (-1 + 2i)^2 = -3 - 4i
+        assert_eq!(step("Tricorn", (-1., -2.)), (-2.5, -3.75));
+        assert_eq!(step("Celtic", (-1., -2.)), (3.5, 4.25));
+        assert_eq!(step("Buffalo", (1., -2.)), (3.5, 4.25));
+    }
+
+    #[test]
+    fn exponents() {
+        assert_eq!(Exponent::from(3.), Exponent::Integer(3));
+        assert_eq!(Exponent::from(2.5), Exponent::Complex(ComplexNumber::new(2.5, 0.)));
+        assert_eq!(Exponent::from(-2.).as_integer(), None);
+        let complex = Exponent::from(ComplexNumber::new(2., 0.5));
+        assert_eq!((complex.real(), complex.imaginary()), (2., 0.5));
+        assert_eq!(Exponent::from(ComplexNumber::new(2., 0.)).imaginary(), 0.);
+
+        // Close to a whole number the polar form approaches the exact power
+        let z: ComplexNumber<f64> = ComplexNumber::new(-0.6, 0.4);
+        let exact = Exponent::Integer(3);
+        let close = Exponent::Complex(ComplexNumber::new(3. + 1e-9, 0.));
+        for (a, b) in [(exact.power(z), close.power(z)),
+                       (exact.derivative(z), close.derivative(z))] {
+            assert!((a.r - b.r).abs() < 1e-8 && (a.i - b.i).abs() < 1e-8);
+        }
+
+        // z^0 is the constant 1, whose derivative is 0
+        let constant = Exponent::from(0);
+        assert_eq!(constant, Exponent::Integer(0));
+        assert_eq!(constant.power(z), ComplexNumber::new(1., 0.));
+        assert_eq!(constant.derivative(z), ComplexNumber::new(0., 0.));
+        assert_eq!(Exponent::Integer(1).power(z), z);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for name in NAMES {
+            assert_eq!(by_name::<f64>(name).unwrap().name(), name);
+        }
+        assert!(by_name::<f64>("Julia").is_none());
+    }
+
+    #[test]
+    fn real_exponents() {
+        let run = |exponent: f64, perturbation| {
+            let config = config((37, 23)).with_exponent(exponent).with_perturbation(perturbation);
+            let mut mandelbrot = Mandelbrot::new(config, 100);
+            mandelbrot.run(100);
+            iterations(&mandelbrot)
+        };
+        // Whole numbers take the exact path, perturbation included
+        assert_eq!(run(3., true), run(3., false));
+        assert_eq!(run(3., false), {
+            let mut mandelbrot = Mandelbrot::new(config((37, 23)).with_exponent(3), 100);
+            mandelbrot.run(100);
+            iterations(&mandelbrot)
+        });
+
+        // On the way from 2 to 3 the image changes a little at a time
+        let frames: Vec<_> = [2., 2.05, 2.1, 2.5, 3.].map(|e| run(e, true)).into();
+        let changed = |a: &Vec<u32>, b: &Vec<u32>| a.iter().zip(b).filter(|(a, b)| a != b).count();
+        assert!(changed(&frames[0], &frames[1]) < changed(&frames[0], &frames[2]));
+        assert!(changed(&frames[0], &frames[2]) < changed(&frames[0], &frames[4]));
+        assert!(frames[3].contains(&100) && frames[3].iter().any(|i| *i < 100));
+    }
+
+    #[test]
+    fn linear_negative_and_complex_exponents() {
+        // Everything the GUI turns on, at exponents its slider can reach
+        let run = |exponent: Exponent, accelerated| {
+            let config = config((37, 23))
+                .with_exponent(exponent)
+                .with_perturbation(accelerated)
+                .with_series_approximation(accelerated)
+                .with_cardioid_check(accelerated)
+                .with_periodicity_check(accelerated)
+                .with_interior_analysis(true)
+                .with_distance_estimation(true)
+                .with_antialiasing(Antialiasing::Adaptive { samples: 2, threshold: 1 });
+            let mut mandelbrot = Mandelbrot::new(config, 100);
+            mandelbrot.run(100);
+            mandelbrot.run(100);
+            (iterations(&mandelbrot), mandelbrot.rgba().to_vec())
+        };
+        for exponent in [1., -2.].map(Exponent::from)
+            .into_iter()
+            .chain([Exponent::from(ComplexNumber::new(2., 0.5))]) {
+            let (iterations, rgba) = run(exponent, true);
+            // Perturbation may round the slowest escapes an iteration apart
+            let direct = run(exponent, false).0;
+            assert!(iterations.iter().zip(&direct).all(|(a, b)| a.abs_diff(*b) <= 1));
+            assert!(iterations.iter().any(|i| *i < 200), "{exponent:?}");
+            assert_eq!(rgba.len(), 37 * 23 * 4);
+        }
+
+        // Cubing takes -1 to -2 and -9, adding c moves i on to 2i and 3i, and
+        // 0 to the power -2 is infinite
+        let grid = |exponent: f64| {
+            let mut mandelbrot = Mandelbrot::new(grid().with_exponent(exponent), 10);
+            mandelbrot.run(10);
+            iterations(&mandelbrot)
+        };
+        assert_eq!(grid(3.), [0, 1, 10, 1, 1, 2, 10, 2, 0, 1, 10, 1]);
+        assert_eq!(grid(1.), [0, 1, 2, 1, 1, 2, 10, 2, 0, 1, 2, 1]);
+        assert_eq!(grid(-2.)[6], 1);
+    }
+}
@@ -2,21 +2,29 @@ use std::fmt::{Debug, UpperHex};
 use std::ops::{Deref, MulAssign};
 use std::str::FromStr;
 
-use num_traits::{AsPrimitive, Bounded, Float, Unsigned, Zero};
+use num_traits::{AsPrimitive, Bounded, Float, One, Unsigned, Zero};
 
 use fractal_generator_gui::{BoxedPrimitive, Complex, Generator, GeneratorConfigOld, GeneratorSetting, GeneratorSettings, GeneratorValue, RgbaData};
 
+use crate::color_scale::{DistanceColorScale, InteriorColorScale};
 use crate::complex_number::ComplexNumber;
 use crate::config::MandelbrotConfig;
 use crate::config::viewport::Viewport;
-use crate::gui::settings::Iterations;
+use crate::formula;
+use crate::gui::settings::{
+    CardioidCheck, DistanceEstimation, Exponent, Formula, ImaginaryExponent, InteriorAnalysis,
+    Iterations, LyapunovSequence, Nova, PeriodicityCheck, Perturbation, Relaxation, Rotation,
+    SeriesApproximation,
+};
+use crate::lyapunov::{Lyapunov, LyapunovConfig};
 use crate::mandelbrot::Mandelbrot;
-use crate::pixel::Pixel;
+use crate::newton::{Newton, NewtonConfig};
+use crate::pixel::{Pixel, PixelMath};
 
 impl<P, F> Generator for Mandelbrot<P, F>
     where
         P: 'static + Unsigned + Bounded + UpperHex + Copy + Zero + Send + Sync + Sync + Debug +
-        Into<f64>,
+        Into<f64> + Into<f32> + Into<u8>,
         F: 'static + Float + From<f64> + Into<f64> + MulAssign + FromStr + From<u32> +
         From<i32> + Send + Sync + Debug,
         f64: From<P> + From<F> + AsPrimitive<P>
@@ -32,15 +40,18 @@ impl<P, F> Generator for Mandelbrot<P, F>
     }
 
     fn data(&self) -> Vec<Self::B> {
-        self.get_pixels().into_iter().flat_map(|col| col.into_iter()).collect()
+        pixels(self.rgba())
+    }
+
+    fn rgba(&self) -> &[P] {
+        Mandelbrot::rgba(self)
     }
 
     fn zoom(&mut self, center: (u32, u32), radius: u32) -> (
         Box<dyn Complex<T=F>>,
         Box<dyn Complex<T=F>>
     ) {
-        let (tl, br) = self.zoom(center, radius);
-        (Box::new(tl), Box::new(br))
+        boxed_corners(self.zoom(center, radius))
     }
 
     // fn settings(&self) -> Vec<GeneratorSetting> {
@@ -78,11 +89,22 @@ impl<P, F> GeneratorSettings for Mandelbrot<P, F>
         Into<f64>,
         F: 'static + Float + From<f64> + Into<f64> + MulAssign + FromStr + From<u32> +
         From<i32> + Send + Sync + Debug,
+        f64: From<P> + From<F> + AsPrimitive<P>
 {
     fn settings(&self) -> Vec<GeneratorSetting> {
         vec![
             Iterations::from(self.max_iterations).into(),
             self.config.viewport.into(),
+            Rotation(self.config.viewport.rotation.into().to_degrees()).into(),
+            Formula(self.config.formula.name()).into(),
+            Exponent(self.config.exponent.real()).into(),
+            ImaginaryExponent(self.config.exponent.imaginary()).into(),
+            Perturbation(self.config.perturbation).into(),
+            SeriesApproximation(self.config.series_approximation).into(),
+            CardioidCheck(self.config.cardioid_check).into(),
+            PeriodicityCheck(self.config.periodicity_check).into(),
+            DistanceEstimation(self.config.distance_estimation).into(),
+            InteriorAnalysis(self.config.interior_analysis).into(),
         ]
     }
 
@@ -91,17 +113,81 @@ impl<P, F> GeneratorSettings for Mandelbrot<P, F>
             .iter()
             .for_each(|s| {
                 match s.label.as_str() {
-                    "iterations" => {
-                        if let GeneratorValue::Range((iters, _, _, _)) = &s.value {
-                            self.max_iterations = iters.to_u32().unwrap();
+                    "rotation" => {
+                        update_view_setting(s, &mut self.max_iterations, &mut self.config.viewport);
+                        self.update(self.config.viewport);
+                    }
+                    "formula" => {
+                        if let GeneratorValue::Choice((selected, options)) = &s.value {
+                            if let Some(formula) = formula::by_name(&options[*selected]) {
+                                self.config.formula = formula;
+                                self.update(self.config.viewport);
+                            }
+                        }
+                    }
+                    "exponent" => {
+                        if let GeneratorValue::Range((exponent, _, _, _)) = &s.value {
+                            let imaginary = self.config.exponent.imaginary();
+                            self.config.exponent =
+                                ComplexNumber::new(exponent.to_f64().unwrap(), imaginary).into();
+                            self.update(self.config.viewport);
+                        }
+                    }
+                    "imaginary exponent" => {
+                        if let GeneratorValue::Range((imaginary, _, _, _)) = &s.value {
+                            let real = self.config.exponent.real();
+                            self.config.exponent =
+                                ComplexNumber::new(real, imaginary.to_f64().unwrap()).into();
+                            self.update(self.config.viewport);
                         }
                     }
-                    "viewport" => {
-                        if let GeneratorValue::Viewport(viewport) = &s.value {
-                            self.config.viewport = Viewport::from(viewport);
+                    "perturbation" => {
+                        if let GeneratorValue::Bool(perturbation) = &s.value {
+                            self.config.perturbation = *perturbation;
+                            self.update(self.config.viewport);
                         }
                     }
-                    _ => unreachable!(),
+                    "series approximation" => {
+                        if let GeneratorValue::Bool(series_approximation) = &s.value {
+                            self.config.series_approximation = *series_approximation;
+                        }
+                    }
+                    "cardioid check" => {
+                        if let GeneratorValue::Bool(cardioid_check) = &s.value {
+                            self.config.cardioid_check = *cardioid_check;
+                        }
+                    }
+                    "periodicity check" => {
+                        if let GeneratorValue::Bool(periodicity_check) = &s.value {
+                            self.config.periodicity_check = *periodicity_check;
+                        }
+                    }
+                    "distance estimation" => {
+                        if let GeneratorValue::Bool(distance_estimation) = &s.value {
+                            if *distance_estimation != self.config.distance_estimation {
+                                self.config.distance_estimation = *distance_estimation;
+                                if self.config.distance_color_fn.is_none() {
+                                    self.config.distance_color_fn = Some(Box::new(|pixel, d| {
+                                        DistanceColorScale::boundary(pixel, d, 1.)
+                                    }));
+                                }
+                                // Derivatives are only tracked from the first iteration
+                                self.update(self.config.viewport);
+                            }
+                        }
+                    }
+                    "interior analysis" => {
+                        if let GeneratorValue::Bool(interior_analysis) = &s.value {
+                            self.config.interior_analysis = *interior_analysis;
+                            if self.config.interior_color_fn.is_none() {
+                                self.config.interior_color_fn =
+                                    Some(Box::new(InteriorColorScale::bulbs));
+                            }
+                        }
+                    }
+                    _ => {
+                        update_view_setting(s, &mut self.max_iterations, &mut self.config.viewport)
+                    }
                 }
             });
     }
@@ -115,6 +201,230 @@ impl<P, F> GeneratorConfigOld for MandelbrotConfig<P, F>
     type C = MandelbrotConfig<P, F>;
 }
 
+impl<P> Generator for Newton<P>
+    where
+        P: 'static + Unsigned + Bounded + UpperHex + Copy + Zero + One + Send + Sync + Debug +
+        Into<f64> + Into<f32> + Into<u8>,
+        f64: From<P> + AsPrimitive<P>
+{
+    type B = Pixel<P>;
+    type T = f64;
+
+    type C = NewtonConfig;
+
+    fn new(config: Self::C, max_iterations: u32) -> Self {
+        Newton::new(config, max_iterations)
+    }
+
+    fn data(&self) -> Vec<Self::B> {
+        pixels(self.rgba())
+    }
+
+    fn rgba(&self) -> &[P] {
+        Newton::rgba(self)
+    }
+
+    fn zoom(&mut self, center: (u32, u32), radius: u32) -> (
+        Box<dyn Complex<T=f64>>,
+        Box<dyn Complex<T=f64>>
+    ) {
+        boxed_corners(self.zoom(center, radius))
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+
+    fn recalculate(&mut self, _refresh: bool) {
+        // Every point starts from scratch, there are no values to continue
+        self.recalculate()
+    }
+
+    fn redraw(&mut self) {
+        self.redraw()
+    }
+
+    fn viewport(&self) -> &dyn fractal_generator_gui::Viewport<T=Self::T> {
+        &self.config().viewport
+    }
+}
+
+impl<P> GeneratorSettings for Newton<P>
+    where
+        P: 'static + Unsigned + Bounded + UpperHex + Copy + Zero + One + Send + Sync + Debug +
+        Into<f64>,
+        f64: From<P> + AsPrimitive<P>
+{
+    fn settings(&self) -> Vec<GeneratorSetting> {
+        vec![
+            Iterations::from(self.max_iterations).into(),
+            self.config.viewport.into(),
+            Rotation(self.config.viewport.rotation.to_degrees()).into(),
+            Relaxation(self.config.relaxation.r).into(),
+            Nova(self.config.nova.is_some()).into(),
+        ]
+    }
+
+    fn update_settings(&mut self, settings: &[GeneratorSetting]) {
+        settings
+            .iter()
+            .for_each(|s| {
+                match s.label.as_str() {
+                    "relaxation" => {
+                        if let GeneratorValue::Range((relaxation, _, _, _)) = &s.value {
+                            self.config.relaxation =
+                                ComplexNumber::new(relaxation.to_f64().unwrap(), 0.);
+                        }
+                    }
+                    "nova" => {
+                        if let GeneratorValue::Bool(nova) = &s.value {
+                            // Start every point at 1, a root of the default z^3 - 1
+                            self.config.nova = nova.then_some(ComplexNumber::new(1., 0.));
+                        }
+                    }
+                    _ => {
+                        update_view_setting(s, &mut self.max_iterations, &mut self.config.viewport)
+                    }
+                }
+            });
+    }
+}
+
+impl GeneratorConfigOld for NewtonConfig {
+    type C = NewtonConfig;
+}
+
+impl<P> Generator for Lyapunov<P>
+    where
+        P: 'static + Unsigned + Bounded + UpperHex + Copy + Zero + One + Send + Sync + Debug +
+        Into<f64> + Into<f32> + Into<u8>,
+        f64: From<P> + AsPrimitive<P>
+{
+    type B = Pixel<P>;
+    type T = f64;
+
+    type C = LyapunovConfig<P>;
+
+    fn new(config: Self::C, max_iterations: u32) -> Self {
+        Lyapunov::new(config, max_iterations)
+    }
+
+    fn data(&self) -> Vec<Self::B> {
+        pixels(self.rgba())
+    }
+
+    fn rgba(&self) -> &[P] {
+        Lyapunov::rgba(self)
+    }
+
+    fn zoom(&mut self, center: (u32, u32), radius: u32) -> (
+        Box<dyn Complex<T=f64>>,
+        Box<dyn Complex<T=f64>>
+    ) {
+        boxed_corners(self.zoom(center, radius))
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+
+    fn recalculate(&mut self, _refresh: bool) {
+        // Every exponent is summed from scratch, there are no values to continue
+        self.recalculate()
+    }
+
+    fn redraw(&mut self) {
+        self.redraw()
+    }
+
+    fn viewport(&self) -> &dyn fractal_generator_gui::Viewport<T=Self::T> {
+        &self.config().viewport
+    }
+}
+
+impl<P> GeneratorSettings for Lyapunov<P>
+    where
+        P: 'static + Unsigned + Bounded + UpperHex + Copy + Zero + One + Send + Sync + Debug +
+        Into<f64>,
+        f64: From<P> + AsPrimitive<P>
+{
+    fn settings(&self) -> Vec<GeneratorSetting> {
+        vec![
+            Iterations::from(self.max_iterations).into(),
+            self.config.viewport.into(),
+            Rotation(self.config.viewport.rotation.to_degrees()).into(),
+            LyapunovSequence(self.config.sequence.to_string()).into(),
+        ]
+    }
+
+    fn update_settings(&mut self, settings: &[GeneratorSetting]) {
+        settings
+            .iter()
+            .for_each(|s| {
+                match s.label.as_str() {
+                    "sequence" => {
+                        if let GeneratorValue::Choice((selected, options)) = &s.value {
+                            if let Ok(sequence) = options[*selected].parse() {
+                                self.config.sequence = sequence;
+                            }
+                        }
+                    }
+                    _ => {
+                        update_view_setting(s, &mut self.max_iterations, &mut self.config.viewport)
+                    }
+                }
+            });
+    }
+}
+
+impl<P> GeneratorConfigOld for LyapunovConfig<P>
+    where
+        P: Unsigned + Bounded + UpperHex + Copy + Zero + Send + Sync + 'static,
+{
+    type C = LyapunovConfig<P>;
+}
+
+/// The pixels of flat RGBA channels
+fn pixels<P>(rgba: &[P]) -> Vec<Pixel<P>>
+    where P: 'static + Unsigned + Bounded + UpperHex + Copy + Zero + One + Send + Sync + Into<f64>,
+{
+    rgba.chunks_exact(4)
+        .map(|px| Pixel::new_rgba(px[0], px[1], px[2], px[3]))
+        .collect()
+}
+
+/// The corners a generator zoomed to, boxed for the gui
+fn boxed_corners<F>((top_left, bottom_right): (ComplexNumber<F>, ComplexNumber<F>)) -> (
+    Box<dyn Complex<T=F>>,
+    Box<dyn Complex<T=F>>
+)
+    where F: Float + Send + Sync + 'static,
+{
+    (Box::new(top_left), Box::new(bottom_right))
+}
+
+/// Applies the iterations, viewport and rotation settings every generator
+/// shares
+fn update_view_setting<F>(setting: &GeneratorSetting, max_iterations: &mut u32,
+                          viewport: &mut Viewport<F>)
+    where F: Float + Send + Sync + From<f64>,
+{
+    match (setting.label.as_str(), &setting.value) {
+        ("iterations", GeneratorValue::Range((iters, _, _, _))) => {
+            *max_iterations = iters.to_u32().unwrap();
+        }
+        ("viewport", GeneratorValue::Viewport(settings_viewport)) => {
+            // The settings viewport has no rotation of its own
+            *viewport = Viewport::from(settings_viewport).with_rotation(viewport.rotation);
+        }
+        ("rotation", GeneratorValue::Range((degrees, _, _, _))) => {
+            viewport.rotation = degrees.to_f64().unwrap().to_radians().into();
+        }
+        ("iterations" | "viewport" | "rotation", _) => {}
+        _ => unreachable!(),
+    }
+}
+
 impl<P> From<Pixel<P>> for [u8; 4]
     where P: 'static + Unsigned + Bounded + Send + Sync + Into<f64> + UpperHex + Copy + Into<f32>
     + Into<u8>,
@@ -186,6 +496,8 @@ pub mod settings {
     use fractal_generator_gui::{GeneratorSetting, GeneratorValue, GeneratorViewport};
     use crate::complex_number::ComplexNumber;
     use crate::config::viewport::Viewport;
+    use crate::formula;
+    use crate::lyapunov;
 
     #[derive(Clone)]
     pub struct Iterations {
@@ -236,6 +548,278 @@ pub mod settings {
         }
     }
 
+    /// Name of the selected `EscapeTimeFormula`
+    #[derive(Clone, Copy)]
+    pub struct Formula(pub &'static str);
+
+    impl Default for Formula {
+        fn default() -> Self {
+            Self(formula::NAMES[0])
+        }
+    }
+
+    impl From<Formula> for GeneratorValue {
+        fn from(value: Formula) -> Self {
+            let options = formula::NAMES.iter().map(|name| name.to_string()).collect();
+            let selected = formula::NAMES.iter().position(|name| *name == value.0).unwrap_or(0);
+            Self::Choice((selected, options))
+        }
+    }
+
+    impl From<Formula> for GeneratorSetting {
+        fn from(value: Formula) -> Self {
+            Self::new(
+                "formula".to_string(),
+                Formula::default().into(),
+                value.into(),
+            )
+        }
+    }
+
+    /// Whether deep zooms are rendered with perturbation
+    #[derive(Clone, Copy, Default)]
+    pub struct Perturbation(pub bool);
+
+    impl From<Perturbation> for GeneratorSetting {
+        fn from(value: Perturbation) -> Self {
+            Self::new(
+                "perturbation".to_string(),
+                GeneratorValue::Bool(Perturbation::default().0),
+                GeneratorValue::Bool(value.0),
+            )
+        }
+    }
+
+    /// Whether pixels skip ahead using a series approximation
+    #[derive(Clone, Copy, Default)]
+    pub struct SeriesApproximation(pub bool);
+
+    impl From<SeriesApproximation> for GeneratorSetting {
+        fn from(value: SeriesApproximation) -> Self {
+            Self::new(
+                "series approximation".to_string(),
+                GeneratorValue::Bool(SeriesApproximation::default().0),
+                GeneratorValue::Bool(value.0),
+            )
+        }
+    }
+
+    /// Whether points inside the main cardioid and period-2 bulb stop early
+    #[derive(Clone, Copy, Default)]
+    pub struct CardioidCheck(pub bool);
+
+    impl From<CardioidCheck> for GeneratorSetting {
+        fn from(value: CardioidCheck) -> Self {
+            Self::new(
+                "cardioid check".to_string(),
+                GeneratorValue::Bool(CardioidCheck::default().0),
+                GeneratorValue::Bool(value.0),
+            )
+        }
+    }
+
+    /// Whether orbits that cycle stop early
+    #[derive(Clone, Copy, Default)]
+    pub struct PeriodicityCheck(pub bool);
+
+    impl From<PeriodicityCheck> for GeneratorSetting {
+        fn from(value: PeriodicityCheck) -> Self {
+            Self::new(
+                "periodicity check".to_string(),
+                GeneratorValue::Bool(PeriodicityCheck::default().0),
+                GeneratorValue::Bool(value.0),
+            )
+        }
+    }
+
+    /// Whether the boundary is shaded by the estimated distance to the set
+    #[derive(Clone, Copy, Default)]
+    pub struct DistanceEstimation(pub bool);
+
+    impl From<DistanceEstimation> for GeneratorSetting {
+        fn from(value: DistanceEstimation) -> Self {
+            Self::new(
+                "distance estimation".to_string(),
+                GeneratorValue::Bool(DistanceEstimation::default().0),
+                GeneratorValue::Bool(value.0),
+            )
+        }
+    }
+
+    /// Whether the inside of the set is shaded by its attracting cycles
+    #[derive(Clone, Copy, Default)]
+    pub struct InteriorAnalysis(pub bool);
+
+    impl From<InteriorAnalysis> for GeneratorSetting {
+        fn from(value: InteriorAnalysis) -> Self {
+            Self::new(
+                "interior analysis".to_string(),
+                GeneratorValue::Bool(InteriorAnalysis::default().0),
+                GeneratorValue::Bool(value.0),
+            )
+        }
+    }
+
+    /// Real part of the exponent of the Mandelbrot formulas, whole numbers
+    /// are iterated exactly and everything in between through the polar form
+    #[derive(Clone, Copy)]
+    pub struct Exponent(pub f64);
+
+    impl Default for Exponent {
+        fn default() -> Self {
+            Self(2.)
+        }
+    }
+
+    impl From<Exponent> for GeneratorValue {
+        fn from(value: Exponent) -> Self {
+            Self::Range((
+                Box::new(value.0),
+                Box::new(-10.),
+                Box::new(10.),
+                Some(Box::new(0.05)),
+            ))
+        }
+    }
+
+    impl From<Exponent> for GeneratorSetting {
+        fn from(value: Exponent) -> Self {
+            Self::new(
+                "exponent".to_string(),
+                Exponent::default().into(),
+                value.into(),
+            )
+        }
+    }
+
+    /// Imaginary part of the exponent of the Mandelbrot formulas, which are
+    /// iterated through the polar form unless it is 0
+    #[derive(Clone, Copy, Default)]
+    pub struct ImaginaryExponent(pub f64);
+
+    impl From<ImaginaryExponent> for GeneratorValue {
+        fn from(value: ImaginaryExponent) -> Self {
+            Self::Range((
+                Box::new(value.0),
+                Box::new(-10.),
+                Box::new(10.),
+                Some(Box::new(0.05)),
+            ))
+        }
+    }
+
+    impl From<ImaginaryExponent> for GeneratorSetting {
+        fn from(value: ImaginaryExponent) -> Self {
+            Self::new(
+                "imaginary exponent".to_string(),
+                ImaginaryExponent::default().into(),
+                value.into(),
+            )
+        }
+    }
+
+    /// Rotation of the view in degrees
+    #[derive(Clone, Copy, Default)]
+    pub struct Rotation(pub f64);
+
+    impl From<Rotation> for GeneratorValue {
+        fn from(value: Rotation) -> Self {
+            Self::Range((
+                Box::new(value.0),
+                Box::new(-180.),
+                Box::new(180.),
+                Some(Box::new(1.)),
+            ))
+        }
+    }
+
+    impl From<Rotation> for GeneratorSetting {
+        fn from(value: Rotation) -> Self {
+            Self::new(
+                "rotation".to_string(),
+                Rotation::default().into(),
+                value.into(),
+            )
+        }
+    }
+
+    /// Real factor on every Newton step
+    #[derive(Clone, Copy)]
+    pub struct Relaxation(pub f64);
+
+    impl Default for Relaxation {
+        fn default() -> Self {
+            Self(1.)
+        }
+    }
+
+    impl From<Relaxation> for GeneratorValue {
+        fn from(value: Relaxation) -> Self {
+            Self::Range((
+                Box::new(value.0),
+                Box::new(0.1),
+                Box::new(2.),
+                Some(Box::new(0.05)),
+            ))
+        }
+    }
+
+    impl From<Relaxation> for GeneratorSetting {
+        fn from(value: Relaxation) -> Self {
+            Self::new(
+                "relaxation".to_string(),
+                Relaxation::default().into(),
+                value.into(),
+            )
+        }
+    }
+
+    /// Whether the pixel is added as `c` after every Newton step
+    #[derive(Clone, Copy, Default)]
+    pub struct Nova(pub bool);
+
+    impl From<Nova> for GeneratorSetting {
+        fn from(value: Nova) -> Self {
+            Self::new(
+                "nova".to_string(),
+                GeneratorValue::Bool(Nova::default().0),
+                GeneratorValue::Bool(value.0),
+            )
+        }
+    }
+
+    /// The A/B sequence of the Lyapunov fractal, offered next to
+    /// `lyapunov::SEQUENCES`
+    #[derive(Clone)]
+    pub struct LyapunovSequence(pub String);
+
+    impl Default for LyapunovSequence {
+        fn default() -> Self {
+            Self(lyapunov::SEQUENCES[0].to_string())
+        }
+    }
+
+    impl From<LyapunovSequence> for GeneratorValue {
+        fn from(value: LyapunovSequence) -> Self {
+            let mut options: Vec<_> = lyapunov::SEQUENCES.iter().map(|s| s.to_string()).collect();
+            let selected = options.iter().position(|s| *s == value.0).unwrap_or_else(|| {
+                options.push(value.0);
+                options.len() - 1
+            });
+            Self::Choice((selected, options))
+        }
+    }
+
+    impl From<LyapunovSequence> for GeneratorSetting {
+        fn from(value: LyapunovSequence) -> Self {
+            Self::new(
+                "sequence".to_string(),
+                LyapunovSequence::default().into(),
+                value.into(),
+            )
+        }
+    }
+
     impl<F> From<Viewport<F>> for GeneratorValue
         where F: Float + Send + Sync
     {
@@ -293,6 +877,7 @@ pub mod settings {
                 ),
                 width: value.width.to_f64().unwrap().into(),
                 height: value.height.to_f64().unwrap().into(),
+                rotation: F::zero(),
             }
         }
     }
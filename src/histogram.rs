@@ -0,0 +1,192 @@
+use num_traits::{Bounded, Float, Unsigned};
+
+use crate::buffer::Buffer;
+use crate::color_scale::ColorScheme;
+use crate::complex_number::ComplexNumber;
+use crate::config::bailout::Bailout;
+use crate::pixel::Pixel;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// How the iteration counts of a frame are spread over the palette
+pub enum Equalisation {
+    /// By the share of escaped pixels with a lower count, so every part of
+    /// the palette covers about as many pixels
+    #[default]
+    Histogram,
+    /// By the rank of the count among the distinct counts of the frame, rare
+    /// counts get as much of the palette as common ones
+    RankOrder,
+}
+
+/// Maps a palette position in `0..=1` to a colour
+pub type PaletteFn<P> = Box<dyn Fn(f64) -> Pixel<P> + Send + Sync>;
+
+/// Colours escaped points by where their iteration count falls in the
+/// distribution of the whole frame instead of against `max_iterations`, so
+/// deep zooms whose counts only span a narrow range still use the full
+/// palette
+pub struct HistogramColoring<P: Unsigned + Bounded + Copy + Send + Sync> {
+    pub equalisation: Equalisation,
+    pub palette: PaletteFn<P>,
+    /// Colour of points that did not escape
+    pub inside: Pixel<P>,
+    /// Palette position of every count from the last `prepare`, one past
+    /// `max_iterations` so the next count can always be looked up
+    cumulative: Vec<f64>,
+    /// Bailout and real exponent of the frame of the last `prepare`
+    bailout: Bailout,
+    exponent: f64,
+}
+
+impl<P: Unsigned + Bounded + Copy + Send + Sync> HistogramColoring<P> {
+    pub fn new(equalisation: Equalisation, palette: PaletteFn<P>, inside: Pixel<P>) -> Self {
+        Self {
+            equalisation,
+            palette,
+            inside,
+            cumulative: Vec::new(),
+            bailout: Bailout::default(),
+            exponent: 2.,
+        }
+    }
+
+    /// Palette position of a point that escaped after `iterations` with `z`,
+    /// between the positions of its own and the next count by how far past
+    /// the bailout `z` landed, so the bands blend into each other
+    pub fn density<F: Float + Send + Sync + Into<f64>>(&self, iterations: u32, z: ComplexNumber<F>) -> f64 {
+        let n = iterations as usize;
+        let (Some(low), Some(high)) = (self.cumulative.get(n), self.cumulative.get(n + 1)) else {
+            return 1.;
+        };
+        let z = ComplexNumber::new(z.r.into(), z.i.into());
+        let smooth = self.bailout.smooth(iterations, z, self.exponent);
+        let fraction = (smooth - iterations as f64).clamp(0., 1.);
+
+        low + (high - low) * fraction
+    }
+
+    fn escaped<F: Float + Send + Sync>(&self, iterations: u32, z: &ComplexNumber<F>,
+                                       max_iterations: u32) -> bool {
+        iterations < max_iterations && self.bailout.escaped(z)
+    }
+}
+
+impl<P, F> ColorScheme<P, F> for HistogramColoring<P>
+    where
+        P: Unsigned + Bounded + Copy + Send + Sync,
+        F: Float + Send + Sync + Into<f64>,
+{
+    fn prepare(&mut self, values: &Buffer<(u32, ComplexNumber<F>)>, max_iterations: u32,
+               bailout: Bailout, exponent: f64) {
+        self.bailout = bailout;
+        self.exponent = exponent;
+        let mut counts = vec![0usize; max_iterations as usize + 1];
+        for (iterations, z) in values {
+            if self.escaped(*iterations, z, max_iterations) {
+                counts[*iterations as usize] += 1;
+            }
+        }
+        if self.equalisation == Equalisation::RankOrder {
+            counts.iter_mut().for_each(|count| *count = (*count > 0) as usize);
+        }
+
+        let total = counts.iter().sum::<usize>().max(1) as f64;
+        self.cumulative = std::iter::once(0.)
+            .chain(counts.iter().scan(0, |below, count| {
+                *below += count;
+                Some(*below as f64 / total)
+            }))
+            .collect();
+    }
+
+    fn color(&self, iterations: u32, z: ComplexNumber<F>, max_iterations: u32) -> Pixel<P> {
+        if !self.escaped(iterations, &z, max_iterations) {
+            return self.inside.clone();
+        }
+        (self.palette)(self.density(iterations, z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::Buffer;
+    use crate::color_scale::ColorScheme;
+    use crate::complex_number::ComplexNumber;
+    use crate::config::bailout::Bailout;
+    use crate::config::viewport::Viewport;
+    use crate::mandelbrot::Mandelbrot;
+    use crate::mandelbrot::tests::{config, grid};
+    use crate::pixel::{Pixel, PixelMath};
+
+    use super::{Equalisation, HistogramColoring};
+
+    #[test]
+    fn equalises_counts() {
+        let escaped = ComplexNumber::new(4., 0.);
+        // Six pixels escape after 10, two after 11 and one after 40
+        let counts = [10, 10, 10, 10, 10, 10, 11, 11, 40, 100];
+        let values = Buffer::from_fn(10, 1, |x, _| {
+            let z = if counts[x] == 100 { ComplexNumber::new(0., 0.) } else { escaped };
+            (counts[x], z)
+        });
+        let palette = |t: f64| Pixel::new((t * 255.) as u8, 0, 0);
+
+        let mut histogram = HistogramColoring::new(Equalisation::Histogram, Box::new(palette),
+                                                   Pixel::new(0, 0, 255));
+        histogram.prepare(&values, 100, Bailout::default(), 2.);
+        let position = |n| histogram.density(n, escaped);
+        assert_eq!(position(10), 0.);
+        assert!((position(11) - 6. / 9.).abs() < 1e-9);
+        assert!((position(40) - 8. / 9.).abs() < 1e-9);
+        assert!(position(10) < histogram.density(10, ComplexNumber::new(1.5, 1.5)));
+        assert_eq!(histogram.color(100, ComplexNumber::new(0., 0.), 100).get_tuple(),
+                   (0, 0, 255, 255));
+
+        let mut rank = HistogramColoring::new(Equalisation::RankOrder, Box::new(palette),
+                                              Pixel::new(0, 0, 255));
+        rank.prepare(&values, 100, Bailout::default(), 2.);
+        assert!((rank.density(11, escaped) - 1. / 3.).abs() < 1e-9);
+        assert!((rank.density(40, escaped) - 2. / 3.).abs() < 1e-9);
+
+        // Only escaped past a larger radius counts, and blends by that radius
+        histogram.prepare(&values, 100, Bailout::default().with_radius(3.), 2.);
+        assert!((histogram.density(10, escaped) - 6. / 9.).abs() > 1e-3);
+        histogram.prepare(&values, 100, Bailout::default().with_radius(5.), 2.);
+        assert_eq!(histogram.color(10, escaped, 100).get_tuple(), (0, 0, 255, 255));
+    }
+
+    #[test]
+    fn histogram_coloring_uses_whole_palette() {
+        let viewport = Viewport::default()
+            .with_top_left(ComplexNumber::new(-0.76, 0.11))
+            .with_bottom_right(ComplexNumber::new(-0.74, 0.09))
+            .with_size(40., 40.);
+        let palette = |position: f64| Pixel::new((position * 255.) as u8, 0, 0);
+        let histogram = HistogramColoring::new(Equalisation::Histogram, Box::new(palette),
+                                               Pixel::new(0, 0, 255));
+        let mut mandelbrot = Mandelbrot::new(config((40, 40))
+                                                 .with_viewport(viewport)
+                                                 .with_color_scheme(Some(Box::new(histogram))),
+                                             1000);
+        mandelbrot.run(1000);
+
+        let reds: Vec<u8> = (0..40).flat_map(|y| (0..40).map(move |x| (x, y)))
+            .filter(|(x, y)| {
+                let (i, z) = mandelbrot.values()[*y][*x];
+                i < 1000 && z.norm_sqr() > 4.
+            })
+            .map(|(x, y)| mandelbrot.pixel(x, y).unwrap().r())
+            .collect();
+        assert!(reds.iter().max().unwrap() - reds.iter().min().unwrap() > 200);
+
+        // -2, -1, 0, i and -i never escape and take the interior colour
+        let histogram = HistogramColoring::new(Equalisation::Histogram, Box::new(palette),
+                                               Pixel::new(0, 0, 255));
+        let mut grid = Mandelbrot::new(grid().with_color_scheme(Some(Box::new(histogram))), 10);
+        grid.run(10);
+        for (x, y) in [(0, 1), (1, 1), (2, 1), (2, 0), (2, 2)] {
+            assert_eq!(grid.pixel(x, y).unwrap().get_tuple(), (0, 0, 255, 255));
+        }
+        assert_ne!(grid.pixel(3, 1).unwrap().get_tuple(), (0, 0, 255, 255));
+    }
+}
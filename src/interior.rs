@@ -0,0 +1,252 @@
+use num_traits::Float;
+
+use crate::complex_number::ComplexNumber;
+
+/// Longest attracting cycle searched for
+pub const MAX_PERIOD: u32 = 256;
+
+/// Newton steps refining the periodic point before giving up
+const NEWTON_STEPS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Attracting cycle an interior point of `z^e + c` converges to
+pub struct Interior {
+    /// Length of the cycle
+    pub period: u32,
+    /// Derivative of `z -> f^period(z)` along the cycle, `|multiplier| < 1`.
+    /// It is 0 at the centre of a hyperbolic component and has magnitude 1 on
+    /// its boundary.
+    pub multiplier: ComplexNumber<f64>,
+    /// Estimated distance to the boundary of the set, only known for the
+    /// Mandelbrot set where `c` varies per pixel
+    pub distance: Option<f64>,
+}
+
+impl Interior {
+    /// Finds the attracting cycle of `c` by looking for the first period the
+    /// orbit continuing from `z` returns close to `z` in, refined to an exact
+    /// periodic point with Newton's method. `z` should be the end of a long
+    /// bounded orbit so it is already close to the cycle.
+    pub fn analyse<F: Float + Send + Sync>(
+        z: ComplexNumber<F>,
+        c: ComplexNumber<F>,
+        exponent: u32,
+        with_distance: bool,
+    ) -> Option<Self>
+        where
+            f64: Into<F> + From<F>,
+    {
+        let z = ComplexNumber::new(f64::from(z.r), f64::from(z.i));
+        let c = ComplexNumber::new(f64::from(c.r), f64::from(c.i));
+        let step = |w: ComplexNumber<f64>| w.pow(exponent) + c;
+
+        // Closest return so far, later periods have to beat it to be tried
+        let mut closest = f64::INFINITY;
+        let mut w = z;
+        for period in 1..=MAX_PERIOD {
+            w = step(w);
+            let distance = (w + z * -1.).norm_sqr();
+            if distance >= closest {
+                continue;
+            }
+            closest = distance;
+
+            if let Some(interior) = Self::refine(z, c, exponent, period, with_distance) {
+                return Some(interior);
+            }
+        }
+
+        None
+    }
+
+    /// Newton's method on `f^period(z) - z`, accepted when the cycle found is
+    /// attracting
+    fn refine(
+        mut z: ComplexNumber<f64>,
+        c: ComplexNumber<f64>,
+        exponent: u32,
+        period: u32,
+        with_distance: bool,
+    ) -> Option<Self> {
+        let one = ComplexNumber::new(1., 0.);
+        for _ in 0..NEWTON_STEPS {
+            let (w, dz) = (0..period).fold((z, one), |(w, dz), _| {
+                (w.pow(exponent) + c, w.pow(exponent.saturating_sub(1)) * exponent as f64 * dz)
+            });
+            let delta = (w + z * -1.) / (dz + one * -1.);
+            z = z + delta * -1.;
+            // Written so that diverging steps (NaN) do not converge
+            let converged = delta.norm_sqr() <= 1e-24 * z.norm_sqr().max(1.);
+            if !converged {
+                continue;
+            }
+
+            let cycle = Cycle::new(z, c, exponent, period);
+            if cycle.dz.norm_sqr() >= 1. {
+                return None;
+            }
+            return Some(Self {
+                period,
+                multiplier: cycle.dz,
+                distance: with_distance.then(|| cycle.distance()),
+            });
+        }
+
+        None
+    }
+
+    /// Argument of the multiplier in `(-pi, pi]`, which together with its
+    /// magnitude gives a coordinate system within every hyperbolic component
+    pub fn angle(&self) -> f64 {
+        self.multiplier.i.atan2(self.multiplier.r)
+    }
+}
+
+/// Derivatives of `f^period` at a periodic point
+struct Cycle {
+    dz: ComplexNumber<f64>,
+    dc: ComplexNumber<f64>,
+    dzdz: ComplexNumber<f64>,
+    dcdz: ComplexNumber<f64>,
+}
+
+impl Cycle {
+    fn new(z: ComplexNumber<f64>, c: ComplexNumber<f64>, exponent: u32, period: u32) -> Self {
+        let zero = ComplexNumber::new(0., 0.);
+        let e = exponent as f64;
+        let mut cycle = Self {
+            dz: ComplexNumber::new(1., 0.),
+            dc: zero,
+            dzdz: zero,
+            dcdz: zero,
+        };
+
+        let mut z = z;
+        for _ in 0..period {
+            let first = z.pow(exponent.saturating_sub(1)) * e;
+            let second = if exponent >= 2 { z.pow(exponent - 2) * (e * (e - 1.)) } else { zero };
+            let Self { dz, dc, dzdz, dcdz } = cycle;
+            cycle = Self {
+                dz: first * dz,
+                dc: first * dc + 1.,
+                dzdz: second * dz * dz + first * dzdz,
+                dcdz: second * dc * dz + first * dcdz,
+            };
+            z = z.pow(exponent) + c;
+        }
+
+        cycle
+    }
+
+    /// Interior distance estimate
+    /// `(1 - |dz|^2) / |dcdz + dzdz dc / (1 - dz)|`
+    fn distance(&self) -> f64 {
+        let one_minus_dz = ComplexNumber::new(1. - self.dz.r, -self.dz.i);
+        let denominator = self.dcdz + self.dzdz * self.dc / one_minus_dz;
+        (1. - self.dz.norm_sqr()) / denominator.abs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::complex_number::ComplexNumber;
+    use crate::mandelbrot::Mandelbrot;
+    use crate::mandelbrot::tests::{config, grid, grid_iterations, iterations};
+    use crate::pixel::{Pixel, PixelMath};
+
+    use super::Interior;
+
+    fn analyse(r: f64, i: f64) -> Option<Interior> {
+        let c = ComplexNumber::new(r, i);
+        let mut z = c;
+        for _ in 0..1000 {
+            z = z.pow(2) + c;
+        }
+        Interior::analyse(z, c, 2, true)
+    }
+
+    #[test]
+    fn periods() {
+        assert_eq!(analyse(0., 0.).unwrap().period, 1);
+        assert_eq!(analyse(-1., 0.).unwrap().period, 2);
+        assert_eq!(analyse(-0.12, 0.75).unwrap().period, 3);
+        assert_eq!(analyse(-1.31, 0.).unwrap().period, 4);
+        assert!(analyse(0.3, 0.).is_none());
+    }
+
+    #[test]
+    fn multiplier_and_distance() {
+        // Centres of components are superattracting
+        let centre = analyse(0., 0.).unwrap();
+        assert!(centre.multiplier.abs() < 1e-12);
+        // The main cardioid reaches 0.25 on the real axis
+        let distance = centre.distance.unwrap();
+        assert!(distance > 0.25 / 4. && distance < 0.25 * 4., "{distance}");
+
+        // Fixed point multiplier of c = lambda / 2 - lambda^2 / 4
+        let lambda = ComplexNumber::new(0.3, 0.4);
+        let c = lambda / 2. + lambda.pow(2) * -0.25;
+        let interior = analyse(c.r, c.i).unwrap();
+        assert!((interior.multiplier.r - 0.3).abs() < 1e-9);
+        assert!((interior.multiplier.i - 0.4).abs() < 1e-9);
+        assert!((interior.angle() - 0.4f64.atan2(0.3)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn interior_analysis() {
+        let mut mandelbrot = Mandelbrot::new(config((40, 30))
+                                                 .with_cardioid_check(true)
+                                                 .with_interior_analysis(true), 1000);
+        mandelbrot.run(1000);
+
+        let mut periods = Vec::new();
+        let mut unsettled = 0;
+        for (y, row) in mandelbrot.interiors().rows().enumerate() {
+            for (x, interior) in row.iter().enumerate() {
+                let (i, z) = mandelbrot.values()[y][x];
+                if let Some(interior) = interior {
+                    assert!(i == 1000 && z.norm_sqr() <= 4.);
+                    assert!(interior.multiplier.norm_sqr() < 1.);
+                    assert!(interior.distance.unwrap() > 0.);
+                    periods.push(interior.period);
+                } else if z.norm_sqr() <= 4. {
+                    // Only points right at the boundary may not settle
+                    unsettled += 1;
+                }
+            }
+        }
+        assert!(periods.contains(&1) && periods.contains(&2));
+        assert!(unsettled * 10 < periods.len(), "{unsettled} of {}", periods.len());
+
+        // The pixel closest to -1 is in the period 2 bulb
+        let point = |x, y| mandelbrot.get_xy_complex(x, y).unwrap();
+        let x = (0..40).find(|x| point(*x, 0).r >= -1.).unwrap();
+        let y = (0..30).find(|y| point(0, *y).i.abs() < 0.1).unwrap();
+        assert_eq!(mandelbrot.interior(x, y).unwrap().period, 2);
+
+        mandelbrot.config.interior_color_fn = Some(Box::new(|pixel, interior| {
+            assert!(interior.distance.unwrap() > 0.);
+            let (_, _, _, a) = pixel.get_tuple();
+            Pixel::new_rgba(0, 255, 0, a)
+        }));
+        mandelbrot.redraw();
+        assert_eq!(mandelbrot.get_pixels()[y][x].g(), 255);
+    }
+
+    #[test]
+    fn analyses_mandelbrot_pixels() {
+        // 0 is a fixed point and -1 cycles through 0, both superattracting,
+        // while the orbits of -2 and i are repelling
+        let mut mandelbrot = Mandelbrot::new(grid().with_interior_analysis(true), 100);
+        mandelbrot.run(100);
+        assert_eq!(iterations(&mandelbrot), grid_iterations(100));
+
+        for (x, period) in [(2, 1), (1, 2)] {
+            let interior = mandelbrot.interior(x, 1).unwrap();
+            assert_eq!(interior.period, period);
+            assert!(interior.multiplier.abs() < 1e-12);
+        }
+        assert!(mandelbrot.interior(0, 1).is_none() && mandelbrot.interior(2, 0).is_none());
+        assert!(mandelbrot.interior(3, 1).is_none());
+    }
+}
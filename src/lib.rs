@@ -7,6 +7,20 @@ pub mod color_scale;
 pub mod pixel;
 pub mod complex_number;
 pub mod mandelbrot;
+pub mod formula;
+pub mod big_fixed;
+pub mod perturbation;
+pub mod series_approximation;
+pub mod double_double;
+pub mod interior;
+pub mod orbit_trap;
+pub mod tiles;
+pub mod buffer;
+pub mod histogram;
+pub mod simd;
+pub mod buddhabrot;
+pub mod newton;
+pub mod lyapunov;
 
 #[cfg(feature = "gui")]
 mod gui;
@@ -0,0 +1,310 @@
+use std::fmt::{Display, Formatter, UpperHex};
+use std::str::FromStr;
+
+use num_traits::{AsPrimitive, Bounded, One, Unsigned, Zero};
+use rayon::prelude::*;
+
+#[cfg(feature = "gui")]
+use bevy_ecs::prelude::Resource;
+
+use crate::buffer::Buffer;
+use crate::color_scale::LyapunovColorScale;
+use crate::complex_number::ComplexNumber;
+use crate::config::viewport::{square_zoom_scale, Viewport};
+use crate::pixel::{Pixel, PixelMath};
+use crate::tiles::Tile;
+
+/// Sequences the bevy front end offers, the first one is the default
+pub const SEQUENCES: [&str; 4] = ["AB", "AABAB", "BBBBBBAAAAAA", "ABBBAB"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Which coordinate of the parameter plane is the growth rate of a step
+pub enum Rate {
+    /// The real axis
+    A,
+    /// The imaginary axis
+    B,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The growth rates of the logistic map, repeated for as many iterations as
+/// needed
+pub struct Sequence(Vec<Rate>);
+
+impl Sequence {
+    pub fn as_slice(&self) -> &[Rate] {
+        &self.0
+    }
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        SEQUENCES[0].parse().unwrap()
+    }
+}
+
+impl FromStr for Sequence {
+    type Err = String;
+
+    /// Parses a string of `A`s and `B`s, in either case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rates = s.chars()
+            .map(|c| match c {
+                'A' | 'a' => Ok(Rate::A),
+                'B' | 'b' => Ok(Rate::B),
+                _ => Err(format!("Invalid Lyapunov sequence {s:?}: only A and B are allowed")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if rates.is_empty() {
+            return Err("Empty Lyapunov sequence".to_string());
+        }
+        Ok(Self(rates))
+    }
+}
+
+impl Display for Sequence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|rate| match rate {
+            Rate::A => write!(f, "A"),
+            Rate::B => write!(f, "B"),
+        })
+    }
+}
+
+/// Maps a Lyapunov exponent to a colour
+pub type ExponentColorFn<P> = Box<dyn Fn(f64) -> Pixel<P> + Send + Sync>;
+
+pub struct LyapunovConfig<P: Unsigned + Bounded + Copy + Send + Sync> {
+    /// The pixel dimensions of the image
+    pub dimensions: (u32, u32),
+    /// The (a, b) parameter plane, `a` along the real and `b` along the
+    /// imaginary axis
+    pub viewport: Viewport<f64>,
+    pub sequence: Sequence,
+    /// Iterations of the logistic map before the exponent is summed up, so it
+    /// describes the attractor and not the way to it
+    pub warmup: u32,
+    /// Edge length in pixels of the tiles rendered in parallel
+    pub tile_size: u32,
+    pub color_fn: ExponentColorFn<P>,
+}
+
+impl<P> LyapunovConfig<P>
+    where
+        P: 'static + Unsigned + Bounded + UpperHex + Copy + Send + Sync + Into<f64>,
+        f64: From<P> + AsPrimitive<P>,
+{
+    pub fn with_dimensions(mut self, dimensions: (u32, u32)) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+
+    pub fn with_viewport(mut self, viewport: Viewport<f64>) -> Self {
+        self.viewport = viewport;
+        self
+    }
+
+    pub fn with_sequence(mut self, sequence: Sequence) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    pub fn with_warmup(mut self, warmup: u32) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    pub fn with_tile_size(mut self, tile_size: u32) -> Self {
+        self.tile_size = tile_size;
+        self
+    }
+
+    pub fn with_color_fn(mut self, color_fn: ExponentColorFn<P>) -> Self {
+        self.color_fn = color_fn;
+        self
+    }
+}
+
+impl<P> Default for LyapunovConfig<P>
+    where
+        P: 'static + Unsigned + Bounded + UpperHex + Copy + Send + Sync + Into<f64>,
+        f64: From<P> + AsPrimitive<P>,
+{
+    /// The "AB" sequence over the square from `a = 2, b = 4` to `a = 4, b = 2`
+    fn default() -> Self {
+        Self {
+            dimensions: (1000, 1000),
+            viewport: Viewport::default()
+                .with_top_left(ComplexNumber::new(2., 4.))
+                .with_bottom_right(ComplexNumber::new(4., 2.)),
+            sequence: Sequence::default(),
+            warmup: 200,
+            tile_size: 64,
+            color_fn: Box::new(LyapunovColorScale::exponent),
+        }
+    }
+}
+
+/// Renders the Lyapunov exponent of the logistic map `x = r x (1 - x)`, with
+/// `r` switching between `a` and `b` as given by `config.sequence`
+#[cfg_attr(feature = "gui", derive(Resource))]
+pub struct Lyapunov<P: Unsigned + Bounded + UpperHex + Copy + Zero + Send + Sync> {
+    pub(crate) config: LyapunovConfig<P>,
+    pub(crate) max_iterations: u32,
+    /// Exponent of every pixel, negative where the map settles on a cycle and
+    /// positive where it is chaotic
+    exponents: Buffer<f64>,
+    rgba: Vec<P>,
+}
+
+impl<P> Lyapunov<P>
+    where
+        P: 'static + Unsigned + Bounded + UpperHex + Copy + Zero + One + Send + Sync + Into<f64>,
+        f64: From<P> + AsPrimitive<P>,
+{
+    pub fn new(config: LyapunovConfig<P>, max_iterations: u32) -> Self {
+        let (w, h) = config.dimensions;
+        Self {
+            exponents: Buffer::new(w as usize, h as usize, 0.),
+            rgba: vec![P::zero(); w as usize * h as usize * 4],
+            config,
+            max_iterations,
+        }
+    }
+
+    pub fn config(&self) -> &LyapunovConfig<P> {
+        &self.config
+    }
+
+    pub fn update_config(&mut self, config: LyapunovConfig<P>) {
+        *self = Self::new(config, self.max_iterations);
+    }
+
+    pub fn exponents(&self) -> &Buffer<f64> {
+        &self.exponents
+    }
+
+    pub fn rgba(&self) -> &[P] {
+        &self.rgba
+    }
+
+    /// Calculates and draws the image with `iterations` summed per pixel
+    pub fn run(&mut self, iterations: u32) {
+        self.max_iterations = iterations;
+        self.recalculate();
+        self.redraw();
+    }
+
+    /// The parameters of the pixel at `x`, `y`, which may lie outside the
+    /// image
+    fn point(&self, x: f64, y: f64) -> ComplexNumber<f64> {
+        self.config.viewport.pixel_to_complex((x, y), self.config.dimensions)
+    }
+
+    /// The Lyapunov exponent at `a`, `b`: the mean of `ln |r (1 - 2x)|` over
+    /// `max_iterations` steps after `config.warmup`, starting from `x = 0.5`
+    pub fn exponent(&self, a: f64, b: f64) -> f64 {
+        let sequence = self.config.sequence.as_slice();
+        let rate = |n: u32| match sequence[n as usize % sequence.len()] {
+            Rate::A => a,
+            Rate::B => b,
+        };
+        let warmup = self.config.warmup;
+
+        let mut x = 0.5;
+        for n in 0..warmup {
+            x = rate(n) * x * (1. - x);
+        }
+        let mut sum = 0.;
+        for n in warmup..warmup + self.max_iterations {
+            let r = rate(n);
+            sum += (r * (1. - 2. * x)).abs().ln();
+            x = r * x * (1. - x);
+        }
+        sum / self.max_iterations.max(1) as f64
+    }
+
+    /// Calculates every pixel one tile of `config.tile_size` pixels at a time
+    pub fn recalculate(&mut self) {
+        let dimensions = (self.exponents.width(), self.exponents.height());
+        let tiles = Tile::split(dimensions, self.config.tile_size as usize);
+        let rendered: Vec<_> = tiles.into_par_iter().map(|tile| {
+            let exponents: Vec<_> = tile.pixels()
+                .map(|(x, y)| {
+                    let point = self.point(x as f64, y as f64);
+                    self.exponent(point.r, point.i)
+                })
+                .collect();
+            (tile, exponents)
+        }).collect();
+
+        for (tile, exponents) in rendered {
+            for ((x, y), exponent) in tile.pixels().zip(exponents) {
+                self.exponents[y][x] = exponent;
+            }
+        }
+    }
+
+    pub fn redraw(&mut self) {
+        let color_fn = &self.config.color_fn;
+        self.rgba = self.exponents
+            .as_slice()
+            .par_iter()
+            .flat_map_iter(|exponent| color_fn(*exponent).get_slice())
+            .collect();
+    }
+
+    /// Zooms to the square of `radius` pixels around `center`, which fills the
+    /// shorter side of the image, and returns the new top left and bottom
+    /// right corner
+    pub fn zoom(&mut self, center: (u32, u32), radius: u32) -> (ComplexNumber<f64>,
+                                                                ComplexNumber<f64>) {
+        let dimensions = self.config.dimensions;
+        let scale = square_zoom_scale(radius, dimensions);
+        self.config.viewport = self.config.viewport
+            .zoomed((center.0 as f64, center.1 as f64), scale, dimensions);
+        self.reset();
+        (self.config.viewport.top_left, self.config.viewport.bottom_right)
+    }
+
+    pub fn reset(&mut self) {
+        self.exponents.fill(0.);
+        self.rgba.fill(P::zero());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Lyapunov, LyapunovConfig, Rate, Sequence};
+
+    #[test]
+    fn parses_sequences() {
+        let sequence: Sequence = "aBBa".parse().unwrap();
+        assert_eq!(sequence.as_slice(), [Rate::A, Rate::B, Rate::B, Rate::A]);
+        assert_eq!(sequence.to_string(), "ABBA");
+        assert!("".parse::<Sequence>().is_err());
+        assert!("ABC".parse::<Sequence>().is_err());
+    }
+
+    #[test]
+    fn separates_stable_and_chaotic() {
+        let mut lyapunov = Lyapunov::<u8>::new(
+            LyapunovConfig::default().with_dimensions((32, 32)).with_tile_size(10), 500);
+
+        // r = 3.2 settles on a cycle of two, r = 3.9 is chaotic
+        assert!(lyapunov.exponent(3.2, 3.2) < 0.);
+        assert!(lyapunov.exponent(3.9, 3.9) > 0.);
+
+        lyapunov.config.sequence = "AABAB".parse().unwrap();
+        assert!(lyapunov.exponent(3.2, 3.2) < 0.);
+        assert_eq!(lyapunov.exponent(3.5, 3.5),
+                   Lyapunov::<u8>::new(LyapunovConfig::default(), 500).exponent(3.5, 3.5));
+
+        lyapunov.run(200);
+        let exponents = lyapunov.exponents();
+        assert!(exponents.iter().any(|e| *e < 0.) && exponents.iter().any(|e| *e > 0.));
+        let stable = lyapunov.rgba()[..4].to_vec();
+        let chaotic = &lyapunov.rgba()[lyapunov.rgba().len() - 4..];
+        assert_ne!(stable, chaotic);
+    }
+}
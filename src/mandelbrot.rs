@@ -1,29 +1,72 @@
+use std::any::TypeId;
 use std::cmp;
 use std::convert::From;
 use std::fmt::{Debug, UpperHex};
-use std::ops::MulAssign;
+use std::ops::{MulAssign, Range};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use itertools_num::linspace;
 use num_traits::{AsPrimitive, Bounded, Float, Num, sign::Unsigned, Zero};
 use rayon::prelude::*;
 
 #[cfg(feature = "gui")]
-use {bevy_ecs::prelude::Resource, fractal_generator_gui::GeneratorSettingsOld};
+use {bevy_ecs::prelude::Resource, fractal_generator_gui::GeneratorSettingsOld, crate::formula};
 
+use crate::buffer::Buffer;
 use crate::color_scale::ContinuousColorScale;
 use crate::complex_number::ComplexNumber;
 use crate::config::MandelbrotConfig;
-use crate::config::viewport::Viewport;
+use crate::config::bailout::BailoutTest;
+use crate::config::render_strategy::RenderStrategy;
+use crate::config::viewport::{square_zoom_scale, Viewport};
+use crate::formula::Exponent;
+use crate::interior::Interior;
+use crate::orbit_trap::TrapHit;
+use crate::perturbation::{DeepViewport, ReferenceOrbit};
 use crate::pixel::{Pixel, PixelMath};
+use crate::series_approximation::SeriesApproximation;
+use crate::simd::{iterate_lanes, padding, LANES};
+use crate::tiles::{mariani_silver, Cancelled, CancellationToken, Progress, ProgressFn, Tile};
+
+/// Values computed for every pixel of the finished tiles
+type TileValues<T> = Vec<(Tile, Vec<T>)>;
+
+/// New value, derivative and trap of a pixel, `None` for pixels left as they
+/// are
+type PixelUpdate<F> = Option<((u32, ComplexNumber<F>), ComplexNumber<F>, TrapHit)>;
+
+/// Values of the extra samples of every pixel, row by row
+type Samples<F> = Buffer<Vec<(u32, ComplexNumber<F>)>>;
 
 #[cfg_attr(feature = "gui", derive(Resource))]
 pub struct Mandelbrot<P: Unsigned + Bounded + UpperHex + Copy + Zero + Send + Sync + Sync, F:
 Float + Send + Sync + 'static> {
     pub(crate) config: MandelbrotConfig<P, F>,
-    pixels: Vec<Vec<Pixel<P>>>,
+    /// RGBA channels of every pixel, row by row
+    rgba: Vec<P>,
     coords: (Vec<F>, Vec<F>),
-    values: Vec<Vec<(u32, ComplexNumber<F>)>>,
+    values: Buffer<(u32, ComplexNumber<F>)>,
+    /// Reference index and delta of every pixel when rendering with perturbation
+    deltas: Buffer<(usize, ComplexNumber<f64>)>,
+    /// High precision view, set while `config.perturbation` is enabled
+    deep_viewport: Option<DeepViewport>,
+    /// Derivative of every pixel's value with respect to `c` (or the starting
+    /// point for Julia sets), tracked with `config.distance_estimation`
+    derivatives: Buffer<ComplexNumber<F>>,
+    /// Attracting cycle of every bounded pixel, found with
+    /// `config.interior_analysis`
+    interiors: Buffer<Option<Interior>>,
+    /// Closest approach of every pixel's orbit to `config.orbit_trap`
+    traps: Buffer<TrapHit>,
+    /// Values of the extra samples of every pixel picked by
+    /// `config.antialiasing`, iterated from scratch on every `recalculate`
+    pub(crate) samples: Samples<F>,
+    /// Whether every pixel has escaped, these are not iterated any further
+    escaped: Buffer<bool>,
+    /// Whether every pixel was filled from the border of its rectangle by
+    /// `RenderStrategy::MarianiSilver` instead of being iterated
+    pub(crate) filled: Buffer<bool>,
     steps: (ComplexNumber<F>, ComplexNumber<F>),
     iterations: u32,
     pub(crate) max_iterations: u32,
@@ -45,55 +88,105 @@ Mandelbrot<P, F>
 
         let re_range = linspace(top_left.r, bottom_right.r, w as usize).collect();
         let im_range = linspace(top_left.i, bottom_right.i, h as usize).collect();
+        let deep_viewport = config.perturbation
+            .then(|| DeepViewport::from_viewport(&config.viewport, config.dimensions));
 
+        let (w, h) = (w as usize, h as usize);
         Mandelbrot {
             config,
-            pixels: vec![vec![Pixel::<P>::default(); w as usize]; h as usize],
-            values: vec![vec![(0, ComplexNumber::new(0.0.into(), 0.0.into())); w as usize]; h as
-                usize],
-            steps: (w_c / w, h_c / h),
+            rgba: Pixel::<P>::default().get_slice().repeat(w * h),
+            values: Buffer::new(w, h, (0, ComplexNumber::new(0.0.into(), 0.0.into()))),
+            deltas: Buffer::new(w, h, (0, ComplexNumber::new(0., 0.))),
+            deep_viewport,
+            derivatives: Buffer::new(w, h, ComplexNumber::new(0.0.into(), 0.0.into())),
+            interiors: Buffer::new(w, h, None),
+            traps: Buffer::new(w, h, TrapHit::default()),
+            samples: Buffer::new(w, h, Vec::new()),
+            escaped: Buffer::new(w, h, false),
+            filled: Buffer::new(w, h, false),
+            steps: (w_c / w as u32, h_c / h as u32),
             iterations: max_iterations,
             max_iterations: 0,
             coords: (re_range, im_range),
         }
     }
 
-    /// Returns a reference to the current state of the Pixels in the
-    /// Mandelbrot Set
+    /// Copy of the current state of the Pixels in the Mandelbrot Set, row by
+    /// row. `rgba` gives the same without copying.
     pub fn get_pixels(&self) -> Vec<Vec<Pixel<P>>> {
-        self.pixels.clone()
+        let (w, h) = self.config.dimensions;
+        (0..h as usize)
+            .map(|y| (0..w as usize).map(|x| self.pixel(x, y).unwrap()).collect())
+            .collect()
     }
 
-    pub fn julia_set(&mut self, iterations: u32) {
-        for (y, im) in self.coords.1.iter().enumerate() {
-            for (x, re) in self.coords.0.iter().enumerate() {
-                let (iters, z) = Self::julia(*re, *im, iterations);
-                self.pixels[y][x] = (self.config.color_fn)(iters, z, iterations);
-            }
+    /// RGBA channels of every pixel, row by row, ready for an image encoder
+    /// or a texture
+    pub fn rgba(&self) -> &[P] {
+        &self.rgba
+    }
+
+    /// Colour of pixel `(x, y)`
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Pixel<P>> {
+        let width = self.config.dimensions.0 as usize;
+        if x >= width {
+            return None;
         }
+        let i = (y * width + x) * 4;
+        let [r, g, b, a] = self.rgba.get(i..i + 4)? else {
+            return None;
+        };
+        Some(Pixel::new_rgba(*r, *g, *b, *a))
+    }
+
+    /// Iteration count and last value of every pixel
+    pub fn values(&self) -> &Buffer<(u32, ComplexNumber<F>)> {
+        &self.values
     }
 
-    fn julia(x: F, y: F, iterations: u32) -> (u32, ComplexNumber<F>)
-        where f64: Into<F> {
-        let mut z = ComplexNumber::new(x, y);
-        let c = ComplexNumber::new((0.38).into(), (0.28).into());
-        let mut i = 0;
-        while i < iterations && z.norm_sqr() < (32.).into() {
-            z = z * z + c;
-            i += 1;
+    /// Julia set constant for the point under pixel `(x, y)` of a Mandelbrot
+    /// render, together with a view of the whole Julia set in the same aspect
+    /// ratio. Pass both to `set_julia` to switch over.
+    pub fn julia_at(&self, x: usize, y: usize) -> Option<(ComplexNumber<F>, Viewport<F>)> {
+        if self.config.julia.is_some() {
+            return None;
         }
+        let c = self.config.viewport
+            .rotate(ComplexNumber::new(*self.coords.0.get(x)?, *self.coords.1.get(y)?));
 
-        (i, z)
+        let Viewport { top_left, bottom_right, .. } = self.config.viewport;
+        let aspect = (top_left.i - bottom_right.i).abs() / (bottom_right.r - top_left.r).abs();
+        let half_height = aspect * (2.).into();
+        let viewport = self.config.viewport
+            .with_top_left(ComplexNumber::new((-2.).into(), half_height))
+            .with_bottom_right(ComplexNumber::new((2.).into(), -half_height));
+
+        Some((c, viewport))
+    }
+
+    /// Switches to the Julia set for `julia`, or back to the Mandelbrot set
+    /// for `None`, and moves to `viewport`
+    pub fn set_julia(&mut self, julia: Option<ComplexNumber<F>>, viewport: Viewport<F>) {
+        self.config.julia = julia;
+        self.update(viewport);
     }
 
     pub fn get_xy_complex(&self, x: usize, y: usize) -> Option<ComplexNumber<f64>> {
         // self.values.get(y).and_then(|row| row.get(x).map(|v| v.1))
         match (self.coords.0.get(x), self.coords.1.get(y)) {
-            (Some(x), Some(y)) => Some(ComplexNumber::new((*x).into(), (*y).into())),
+            (Some(_), Some(_)) => {
+                let c = self.coordinate(x, y);
+                Some(ComplexNumber::new(c.r.into(), c.i.into()))
+            }
             _ => None
         }
     }
 
+    /// The point of pixel `(x, y)`, turned by the rotation of the viewport
+    fn coordinate(&self, x: usize, y: usize) -> ComplexNumber<F> {
+        self.config.viewport.rotate(ComplexNumber::new(self.coords.0[x], self.coords.1[y]))
+    }
+
     pub fn run(&mut self, iters: u32) {
         self.iterations = iters;
 
@@ -102,342 +195,171 @@ Mandelbrot<P, F>
         self.redraw();
     }
 
+    /// `run` with the progress reporting and cancellation of
+    /// `recalculate_with`, the pixels are only redrawn for a finished render
+    pub fn run_with(&mut self, iters: u32, progress: ProgressFn, cancel: &CancellationToken)
+        -> Result<(), Cancelled> {
+        self.iterations = iters;
+
+        self.recalculate_with(true, progress, cancel)?;
+
+        self.redraw();
+        Ok(())
+    }
+
     pub fn config(&self) -> &MandelbrotConfig<P, F> {
         &self.config
     }
 
+    /// Zooms to the square of `radius` pixels around `center`, which fills the
+    /// shorter side of the image, and returns the new top left and bottom
+    /// right corner. The pixels are square afterwards whatever the shape of
+    /// the image.
     pub fn zoom(&mut self, center: (u32, u32), radius: u32) -> (ComplexNumber<F>,
                                                                 ComplexNumber<F>) {
-        let width = self.config.dimensions.0;
-        let height = self.config.dimensions.1;
-
-        let x1 = center.0 as i32 - radius as i32 - 1;
-        let y1 = center.1 as i32 - radius as i32 - 1;
-        let x2 = center.0 + radius - 1;
-        let y2 = center.1 + radius - 1;
-
-        let mut viewport = self.config.viewport;
-
-        let width = width as i32;
-        let height = height as i32;
-        // match (x1, y1, x2 as i32, y2 as i32) {
-        //     (x_range, y_range, x_range, y_range) => {
-        //         viewport.top_left = top_left.unwrap();
-        //         viewport.bottom_right = bottom_right.unwrap();
-        //     }
-        //     (x_range, y_range, width.., y_range) => {
-        //         let neighbor1 = self.coords.0[width as usize - 2];
-        //         let neighbor2 = self.coords.0[width as usize - 1];
-        //         let re_diff = Self::xy_diff(neighbor1, neighbor2);
-        //         let re = re_diff * (x2 as f64 - width as f64) + self.coords.0[width as usize - 1];
-        //         let im = self.coords.1[y2 as usize];
-        //         viewport.top_left = top_left.unwrap();
-        //         viewport.bottom_right = ComplexNumber::new(re, im);
-        //     }
-        //     (x_range, y_range, x_range, height..) => {
-        //         let neighbor1 = self.coords.1[height as usize - 2];
-        //         let neighbor2 = self.coords.1[height as usize - 1];
-        //         let im_diff = Self::xy_diff(neighbor1, neighbor2);
-        //         let im = im_diff * (y2 as f64 - height as f64) + self.coords.1[height as usize - 1];
-        //         let re = self.coords.0[x2 as usize];
-        //         viewport.top_left = top_left.unwrap();
-        //         viewport.bottom_right = ComplexNumber::new(re, im);
-        //     }
-        //     (x_range, y_range, width.., height..) => {}
-        //     (i32::MIN..=-1, y_range, x_range, y_range) => {}
-        //     (x_range, i32::MIN..=-1, x_range, y_range) => {}
-        //     (i32::MIN..=-1, i32::MIN..=-1, x_range, y_range) => {}
-        //     (i32::MIN..=-1, y_range, width.., y_range) => {}
-        //     (x_range, i32::MIN..=-1, x_range, height..) => {}
-        //     (i32::MIN..=-1, i32::MIN..=-1, width.., height..) => {}
-        // }
+        let scale = square_zoom_scale(radius, self.config.dimensions);
+        let viewport = self.zoom_view((center.0 as f64, center.1 as f64), scale);
 
-        let re1 = if x1 < 0 {
-            let neighbor1 = self.coords.0[1];
-            let neighbor2 = self.coords.0[0];
-            let re_diff = Self::xy_diff(neighbor1, neighbor2);
-            re_diff * (x1.abs()).into() + self.coords.0[0]
-        } else if x1 < width {
-            self.coords.0[x1 as usize]
-        } else {
-            let neighbor1 = self.coords.0[width as usize - 2];
-            let neighbor2 = self.coords.0[width as usize - 1];
-            let re_diff = Self::xy_diff(neighbor1, neighbor2);
-            re_diff * (x1 - width).into() + self.coords.0[width as usize - 1]
-        };
-
-        let im1 = if y1 < 0 {
-            let neighbor1 = self.coords.1[0];
-            let neighbor2 = self.coords.1[1];
-            let im_diff = Self::xy_diff(neighbor1, neighbor2);
-            im_diff * y1.abs().into() + self.coords.1[0]
-        } else if y1 < height {
-            self.coords.1[y1 as usize]
-        } else {
-            let neighbor1 = self.coords.1[height as usize - 2];
-            let neighbor2 = self.coords.1[height as usize - 1];
-            let im_diff = Self::xy_diff(neighbor1, neighbor2);
-            im_diff * (y1 - height).into() + self.coords.0[height as usize - 1]
-        };
-
-        let re2 = if x2 < width as u32 {
-            self.coords.0[x2 as usize]
-        } else if x2 >= width as u32 {
-            let neighbor1 = self.coords.0[width as usize - 2];
-            let neighbor2 = self.coords.0[width as usize - 1];
-            let re_diff = Self::xy_diff(neighbor1, neighbor2);
-            re_diff * (x2 as i32 - width).into() + self.coords.0[width as usize - 1]
-        } else {
-            unreachable!()
-        };
-
-        let im2 = if y2 < height as u32 {
-            self.coords.1[y2 as usize]
-        } else if y2 >= height as u32 {
-            let neighbor1 = self.coords.1[height as usize - 2];
-            let neighbor2 = self.coords.1[height as usize - 1];
-            let im_diff = Self::xy_diff(neighbor1, neighbor2);
-            im_diff * (y2 as i32 - height).into() + self.coords.1[height as usize - 1]
-        } else {
-            unreachable!()
-        };
+        (viewport.top_left, viewport.bottom_right)
+    }
 
-        let top_left = ComplexNumber::new(re1, im1);
-        viewport.top_left = top_left;
-        let bottom_right = ComplexNumber::new(re2, im2);
-        viewport.bottom_right = bottom_right;
+    /// Zooms in `factor` times, or out for factors below 1, while the point
+    /// under pixel `anchor` stays where it is, and returns the new view.
+    /// `anchor` may lie outside the image.
+    pub fn zoom_by(&mut self, factor: f64, anchor: (i32, i32)) -> Viewport<F> {
+        let (w, h) = self.config.dimensions;
+        let middle = ((w.max(1) - 1) as f64 / 2., (h.max(1) - 1) as f64 / 2.);
+        let anchor = (anchor.0 as f64, anchor.1 as f64);
+        // The centre moves towards the anchor as the pixels shrink around it
+        let center = (anchor.0 + (middle.0 - anchor.0) / factor,
+                      anchor.1 + (middle.1 - anchor.1) / factor);
+        self.zoom_view(center, 1. / factor)
+    }
 
-        self.update(viewport);
+    /// Zooms out `factor` times around pixel `anchor`, the inverse of
+    /// `zoom_by`
+    pub fn zoom_out(&mut self, factor: f64, anchor: (i32, i32)) -> Viewport<F> {
+        self.zoom_by(1. / factor, anchor)
+    }
 
-        (top_left, bottom_right)
-        // if let (Some(tl), Some(br)) = (top_left, bottom_right) {
-        //     viewport.top_left = tl;
-        //     viewport.bottom_right = br;
-        //     self.update(viewport);
-        // } else if let Some(tl) = top_left {
-        //     let re = if x2 > max_x {
-        //         let neighbor1 = self.coords.0[max_x as usize - 2];
-        //         let neighbor2 = self.coords.0[max_x as usize - 1];
-        //         let re_diff = Self::xy_diff(neighbor1, neighbor2);
-        //         // let re_diff = Self::extend_plane(x2 as i32, o1.r, o2.r);
-        //         dbg!(&re_diff);
-        //         re_diff * (x2 as f64 - max_x as f64) + self.coords.0[max_x as usize - 1]
-        //     } else {
-        //         self.coords.0[x2 as usize]
-        //     };
-        //     let im = if y2 > height {
-        //         let neighbor1 = self.coords.1[height as usize - 2];
-        //         let neighbor2 = self.coords.1[height as usize - 1];
-        //         let im_diff = Self::xy_diff(neighbor1, neighbor2);
-        //         dbg!(&im_diff);
-        //         im_diff * (y2 as f64 - height as f64) + self.coords.1[height as usize - 1]
-        //     } else {
-        //         self.coords.1[y2 as usize]
-        //     };
-        //     viewport.top_left = tl;
-        //     viewport.bottom_right = ComplexNumber::new(re, im);
-        //     self.update(viewport);
-        //     return;
-        // } else if let Some(br) = bottom_right {
-        //     let re = if x1 < 0 {
-        //         let neighbor1 = self.coords.0[0];
-        //         let neighbor2 = self.coords.0[1];
-        //         let re_diff = Self::xy_diff(neighbor1, neighbor2);
-        //         dbg!(&re_diff);
-        //         re_diff * (x1.abs()) as f64 + self.coords.0[0]
-        //     } else {
-        //         self.coords.0[x1 as usize]
-        //     };
-        //     let im = if y1 < 0 {
-        //         let neighbor1 = self.coords.1[0];
-        //         let neighbor2 = self.coords.1[1];
-        //         let im_diff = Self::xy_diff(neighbor1, neighbor2);
-        //         dbg!(&im_diff);
-        //         im_diff * (y1.abs()) as f64 + self.coords.1[0]
-        //     } else {
-        //         self.coords.1[y1 as usize]
-        //     };
-        //     viewport.top_left = ComplexNumber::new(re, im);
-        //     viewport.bottom_right = br;
-        //     self.update(viewport);
-        //     return;
-        // } else {
-        //
-        // }
+    /// Zooms to the pixel rectangle between the corners `(x1, y1)` and
+    /// `(x2, y2)`, in any order and possibly past the edges of the image, and
+    /// returns the new view. The rectangle is centred and fills one axis, the
+    /// other one shows more so the pixels stay square.
+    pub fn zoom_to_rect(&mut self, x1: i32, y1: i32, x2: i32, y2: i32) -> Viewport<F> {
+        let (w, h) = self.config.dimensions;
+        let center = ((x1 + x2) as f64 / 2., (y1 + y2) as f64 / 2.);
+        // A rectangle without an area is taken as a single pixel
+        let scale = ((x2 - x1).abs().max(1) as f64 / (w.max(2) - 1) as f64)
+            .max((y2 - y1).abs().max(1) as f64 / (h.max(2) - 1) as f64);
+        self.zoom_view(center, scale)
+    }
 
-        // if x1 >= 0 && y1 >= 0 {
-        //     let top_left = self.get_xy_complex(x1 as usize, y1 as usize);
-        //     let bottom_right = self.get_xy_complex(x2 as usize, y2 as usize);
-        //
-        //     if let (Some(tl), Some(br)) = (top_left, bottom_right) {
-        //         viewport.top_left = tl;
-        //         viewport.bottom_right = br;
-        //         self.update(viewport);
-        //     } else if let Some(tl) = top_left {
-        //         let o1 = self.get_xy_complex(width as usize - 2, height as usize - 2).unwrap();
-        //         let o2 = self.get_xy_complex(width as usize - 1, height as usize - 1).unwrap();
-        //         let re1 = o1.r;
-        //         let im1 = o1.i;
-        //         let re2 = o2.r;
-        //         let im2 = o2.i;
-        //         let re_diff = if re1 > re2 {
-        //             re1 - re2
-        //         } else {
-        //             re2 - re1
-        //         };
-        //         let im_diff = if im1 > im2 {
-        //             im1 - im2
-        //         } else {
-        //             im2 - im1
-        //         };
-        //         if x2 > width && y2 > height {
-        //             let x_diff = x2 - width;
-        //             let y_diff = y2 - height;
-        //             let mut re = re_diff * x_diff as f64;
-        //             let mut im = im_diff * y_diff as f64;
-        //             if re1 < 0. || re2 < 0. {
-        //                 re = -re;
-        //             };
-        //             re += self.coords.0[width as usize - 1];
-        //             if im1 < 0. || im2 < 0. {
-        //                 im = -im;
-        //             };
-        //             im += self.coords.1[height as usize - 1];
-        //             viewport.top_left = tl;
-        //             viewport.bottom_right = ComplexNumber::new(re, im);
-        //             self.update(viewport);
-        //         } else if x2 > width {
-        //             let x_diff = x2 - width;
-        //             let mut re = re_diff * x_diff as f64;
-        //             if re1 < 0. || re2 < 0. {
-        //                 re = -re;
-        //             };
-        //             re += self.coords.0[width as usize - 1];
-        //             let im = self.coords.1[y2 as usize];
-        //             viewport.top_left = tl;
-        //             viewport.bottom_right = ComplexNumber::new(re, im);
-        //             self.update(viewport);
-        //         } else if y2 > height {
-        //             let y_diff = y2 - height;
-        //             let mut im = im_diff * y_diff as f64;
-        //             if im1 < 0. || im2 < 0. {
-        //                 im = -im;
-        //             };
-        //             im += self.coords.1[height as usize - 1];
-        //             let re = self.coords.0[x2 as usize];
-        //             viewport.top_left = tl;
-        //             viewport.bottom_right = ComplexNumber::new(re, im);
-        //             self.update(viewport);
-        //         }
-        //     }
-        // } else {
-        //     let o1 = self.get_xy_complex(0, 0).unwrap();
-        //     let o2 = self.get_xy_complex(1, 1).unwrap();
-        //     if x1 < 0 && y1 >= 0 {
-        //         let re_diff = Self::extend_plane(x1, o1.r, o2.r);
-        //         dbg!((&re_diff));
-        //         let re = re_diff + self.coords.0[0];
-        //         let im = self.coords.1[y1 as usize];
-        //
-        //         viewport.top_left = ComplexNumber::new(re, im);
-        //     } else if y1 < 0 && x1 >= 0 {
-        //         let im_diff = Self::extend_plane(y1, o1.i, o2.i);
-        //         dbg!((&im_diff));
-        //         let im = im_diff + self.coords.1[0];
-        //         let re = self.coords.0[x1 as usize];
-        //
-        //         viewport.top_left = ComplexNumber::new(re, im);
-        //     } else {
-        //         let re_diff = Self::extend_plane(x1, o1.r, o2.r);
-        //         let im_diff = Self::extend_plane(y1, o1.i, o2.i);
-        //         dbg!((&re_diff, &im_diff));
-        //         let re = re_diff + self.coords.0[0];
-        //         let im = im_diff + self.coords.1[0];
-        //
-        //         viewport.top_left = ComplexNumber::new(re, im);
-        //     }
-        //     dbg!(&viewport.top_left);
-        //     let bottom_right = self.get_xy_complex(x2 as usize, y2 as usize).unwrap();
-        //     viewport.bottom_right = bottom_right;
-        //     self.update(viewport);
-        // }
+    /// Moves the view centre to pixel `center` and multiplies the pixel size
+    /// by `scale`, making the pixels square if they are not yet
+    fn zoom_view(&mut self, center: (f64, f64), scale: f64) -> Viewport<F> {
+        if let Some(deep_viewport) = &self.deep_viewport {
+            let deep_viewport = deep_viewport.scaled(center, scale, self.config.dimensions);
+            self.set_deep_viewport(deep_viewport);
+            return self.config.viewport;
+        }
 
+        let viewport = self.config.viewport
+            .zoomed((center.0.into(), center.1.into()), scale.into(), self.config.dimensions);
 
-        // let re_range: Vec<_> = linspace(tl.r, br.r, width as usize).collect();
-        // let im_range: Vec<_> = linspace(tl.i, br.i, height as usize).collect();
+        self.update(viewport);
 
+        viewport
+    }
 
-        // for (y, im) in new_im_range.iter().enumerate() {
-        //     for (x, re) in new_re_range.iter().enumerate() {
-        //         if y <= y2 as usize && y >= y1 as usize && x <= x2 as usize && x >= x1 as usize {
-        //             self.coords.0[x] = ;
-        //             self.coords.1[y] = *im;
-        //             self.pixels[y][x] = Pixel::<P>::default();
-        //             self.values[y][x] = (0, ComplexNumber::new(0., 0.));
-        //         }
-        //         self.coords.0[x] = *re;
-        //         self.coords.1[y] = *im;
-        //         self.pixels[y][x] = Pixel::<P>::default();
-        //         self.values[y][x] = (0, ComplexNumber::new(0., 0.));
-        //     }
-        // }
+    pub fn update(&mut self, viewport: Viewport<F>) {
+        dbg!(&viewport);
+        self.deep_viewport = self.config.perturbation
+            .then(|| DeepViewport::from_viewport(&viewport, self.config.dimensions));
+        self.set_viewport(viewport);
+    }
 
+    /// Moves to a high precision view and enables perturbation rendering,
+    /// `config.viewport` only holds an `f64` approximation afterwards
+    pub fn set_deep_viewport(&mut self, deep_viewport: DeepViewport) {
+        let viewport = deep_viewport.to_viewport(self.config.dimensions);
+        self.config.perturbation = true;
+        self.deep_viewport = Some(deep_viewport);
+        self.set_viewport(viewport);
+    }
 
-        // let filter_fn = |axis_max| {
-        //     move |(i, v)| {
-        //         let i = i as i32;
-        //         let radius = radius as i32;
-        //         if i - radius < 0 || i + radius > axis_max {
-        //             None
-        //         } else {
-        //             Some(v)
-        //         }
-        //     }
-        // };
-
-        // self.coords.0 = self.coords.0
-        //     .iter()
-        //     .cloned()
-        //     .enumerate()
-        //     .filter_map(filter_fn(width as i32))
-        //     .collect();
-        //
-        // self.coords.1 = self.coords.1
-        //     .iter()
-        //     .cloned()
-        //     .enumerate()
-        //     .filter_map(filter_fn(height as i32))
-        //     .collect();
+    pub fn deep_viewport(&self) -> Option<&DeepViewport> {
+        self.deep_viewport.as_ref()
     }
 
-    fn xy_diff(neighbor1: F, neighbor2: F) -> F {
-        let mut re_diff = if neighbor1 > neighbor2 {
-            neighbor1 - neighbor2
+    /// Moves the view `dx` pixels to the right and `dy` pixels down. Pixels
+    /// that stay in view are moved along and only the newly exposed strips are
+    /// iterated, as far as the rest of the frame already is.
+    pub fn pan(&mut self, dx: i32, dy: i32) {
+        let (w, h) = self.config.dimensions;
+        let mut pending = Buffer::new(w as usize, h as usize, false);
+        shift(&mut pending, (dx, dy), true);
+        let mut pixels = Buffer::from_fn(w as usize, h as usize, |x, y| self.pixel(x, y).unwrap());
+        shift(&mut pixels, (dx, dy), Pixel::<P>::default());
+        self.rgba = pixels.iter().flat_map(|pixel| pixel.get_slice()).collect();
+        shift(&mut self.values, (dx, dy), (0, ComplexNumber::new((0.).into(), (0.).into())));
+        shift(&mut self.deltas, (dx, dy), (0, ComplexNumber::new(0., 0.)));
+        shift(&mut self.derivatives, (dx, dy), ComplexNumber::new((0.).into(), (0.).into()));
+        shift(&mut self.interiors, (dx, dy), None);
+        shift(&mut self.traps, (dx, dy), TrapHit::default());
+        shift(&mut self.samples, (dx, dy), Vec::new());
+        shift(&mut self.escaped, (dx, dy), false);
+        shift(&mut self.filled, (dx, dy), false);
+
+        let viewport = if let Some(deep_viewport) = &self.deep_viewport {
+            let deep_viewport = deep_viewport.zoom(
+                (dx, dy),
+                (dx + w as i32 - 1, dy + h as i32 - 1),
+                self.config.dimensions,
+            );
+            let viewport = deep_viewport.to_viewport(self.config.dimensions);
+            self.deep_viewport = Some(deep_viewport);
+            viewport
         } else {
-            neighbor2 - neighbor1
+            let Viewport { top_left, bottom_right, .. } = self.config.viewport;
+            let step = ((bottom_right.r - top_left.r) / (w.max(2) - 1).into(),
+                        (bottom_right.i - top_left.i) / (h.max(2) - 1).into());
+            let offset = ComplexNumber::new(step.0 * dx.into(), step.1 * dy.into());
+            self.config.viewport.with_corners(top_left + offset, bottom_right + offset)
         };
-        if neighbor1 < (0.).into() || neighbor2 < (0.).into() {
-            re_diff *= (-1.).into();
+        self.config.viewport = viewport;
+        self.coords = (
+            linspace(viewport.top_left.r, viewport.bottom_right.r, w as usize).collect(),
+            linspace(viewport.top_left.i, viewport.bottom_right.i, h as usize).collect(),
+        );
+
+        // Deltas are relative to the reference orbit of the old centre, so
+        // pixels that still have to be iterated start over. Without them only
+        // the pixels of tiles a cancelled render never finished do.
+        let perturbed = self.deep_viewport.is_some() && self.perturbable();
+        let max_iterations = self.max_iterations;
+        for (y, row) in pending.rows_mut().enumerate() {
+            for (x, pending) in row.iter_mut().enumerate() {
+                let unfinished = perturbed || self.values[y][x].0 < max_iterations;
+                if !*pending && !self.escaped[y][x] && unfinished {
+                    *pending = true;
+                    self.values[y][x].0 = 0;
+                }
+            }
         }
-        re_diff
-    }
 
-    fn extend_plane(extended: i32, neighbor1: f64, neighbor2: f64) -> f64 {
-        let xy_diff = extended.abs();
-        let step = if neighbor1 > neighbor2 {
-            neighbor1 - neighbor2
-        } else {
-            neighbor2 - neighbor1
-        };
-        let mut xy = step * xy_diff as f64;
-        if neighbor1 < 0. || neighbor2 < 0. {
-            xy = -xy;
+        if self.max_iterations == 0 {
+            return;
         }
-        xy
+        let iterations = self.iterations;
+        self.iterations = self.max_iterations;
+        // Nothing can cancel this render
+        let _ = self.recalculate_pixels(true, Some(&pending), &|_| {}, &CancellationToken::new());
+        self.iterations = iterations;
+        self.redraw();
     }
 
-    pub fn update(&mut self, viewport: Viewport<F>) {
-        dbg!(&viewport);
+    fn set_viewport(&mut self, viewport: Viewport<F>) {
         let (w, h) = self.config.dimensions;
 
         let top_left = viewport.top_left;
@@ -449,9 +371,16 @@ Mandelbrot<P, F>
 
         self.config.viewport = viewport;
         self.steps = (w_c / w, h_c / h);
-        self.pixels = vec![vec![Pixel::<P>::default(); w as usize]; h as usize];
-        self.values = vec![vec![(0, ComplexNumber::new((0.0).into(), (0.0).into())); w as usize]; h as
-            usize];
+        let (w, h) = (w as usize, h as usize);
+        self.rgba = Pixel::<P>::default().get_slice().repeat(w * h);
+        self.values = Buffer::new(w, h, (0, ComplexNumber::new((0.0).into(), (0.0).into())));
+        self.deltas = Buffer::new(w, h, (0, ComplexNumber::new(0., 0.)));
+        self.derivatives = Buffer::new(w, h, ComplexNumber::new(0.0.into(), 0.0.into()));
+        self.interiors = Buffer::new(w, h, None);
+        self.traps = Buffer::new(w, h, TrapHit::default());
+        self.samples = Buffer::new(w, h, Vec::new());
+        self.escaped = Buffer::new(w, h, false);
+        self.filled = Buffer::new(w, h, false);
         self.coords = (re_range, im_range);
     }
 
@@ -464,11 +393,15 @@ Mandelbrot<P, F>
     #[cfg(feature = "gui")]
     pub fn update_settings(&mut self, settings: &GeneratorSettingsOld) {
         dbg!(&settings);
+        if let Some(exponent) = settings.exponent {
+            self.config.exponent = exponent.into();
+        }
         if let Some(hue) = settings.hue {
             self.config.color_fn = ContinuousColorScale::get_color_fn_boxed(hue, 1., 1.);
         }
-        if let Some(exponent) = settings.exponent {
-            self.config.exponent = exponent;
+        if let Some(formula) = settings.formula.as_deref().and_then(formula::by_name) {
+            self.config.formula = formula;
+            self.update(self.config.viewport);
         }
         if settings.x1.is_some() || settings.y1.is_some() || settings.x2.is_some() || settings.y2.is_some() {
             let mut viewport = self.config.viewport;
@@ -493,34 +426,144 @@ Mandelbrot<P, F>
     }
 
     pub fn recalculate(&mut self, use_self: bool) {
+        // Nothing can cancel this render
+        let _ = self.recalculate_with(use_self, &|_| {}, &CancellationToken::new());
+    }
+
+    /// `recalculate` one tile of `config.tile_size` pixels at a time, calling
+    /// `progress` after every tile and stopping early once `cancel` is
+    /// cancelled. Tiles that were not finished keep their previous values.
+    pub fn recalculate_with(
+        &mut self,
+        use_self: bool,
+        progress: ProgressFn,
+        cancel: &CancellationToken,
+    ) -> Result<(), Cancelled> {
+        self.recalculate_pixels(use_self, None, progress, cancel)
+    }
+
+    /// `recalculate_with` that leaves every pixel that is not set in `only`
+    /// as it is
+    fn recalculate_pixels(
+        &mut self,
+        use_self: bool,
+        only: Option<&Buffer<bool>>,
+        progress: ProgressFn,
+        cancel: &CancellationToken,
+    ) -> Result<(), Cancelled> {
+        // Perturbation is only derived for the Mandelbrot set of `z^e + c`,
+        // everything else falls back to the `f64` approximation of the view
+        if self.deep_viewport.is_some() && self.perturbable() {
+            return self.recalculate_perturbed(use_self, only, progress, cancel);
+        }
+
         let iterations = self.iterations;
-        let updates = self.coords.1.par_iter().enumerate().map(|(y, im)| {
-            self.coords.0.par_iter().enumerate().map(|(x, re)| {
-                (
-                    (x, y),
-                    if use_self {
-                        self.iterate_coordinate(
-                            self.values[y][x],
-                            ComplexNumber::new(*re, *im),
-                            iterations,
-                        )
-                    } else {
-                        self.iterate_coordinate(
-                            (0, ComplexNumber::new((0.).into(), (0.).into())),
-                            ComplexNumber::new(*re, *im),
-                            iterations,
-                        )
-                    }
-                )
-            }).collect::<Vec<_>>()
-        }).collect::<Vec<_>>();
-        updates.into_iter().for_each(|v| {
-            v.into_iter()
-                .for_each(|((x, y), (i, z))| {
+        let dimensions = self.config.dimensions;
+        let track_derivative = self.tracks_derivative();
+        let series = self.series_skips().then(|| self.direct_series(iterations)).flatten();
+        let compute = |x: usize, y: usize| {
+            // Escaped pixels are final, only the ones that hit the limit go on
+            if use_self && self.escaped[y][x] || only.is_some_and(|only| !only[y][x]) {
+                return None;
+            }
+            // Filled pixels only have a copied orbit, so they start over and
+            // go up to the count they were filled with
+            let restart = use_self && self.filled[y][x];
+            let current = if use_self && !restart {
+                self.values[y][x]
+            } else {
+                (0, ComplexNumber::new((0.).into(), (0.).into()))
+            };
+            let limit = if restart { self.values[y][x].0 + iterations } else { iterations };
+            let mut derivative = self.derivatives[y][x];
+            let derivative_ref = track_derivative.then_some(&mut derivative);
+            let mut trap = self.traps[y][x];
+            let trap_ref = self.config.orbit_trap.is_some().then_some(&mut trap);
+            let c = self.coordinate(x, y);
+            let value = match &series {
+                Some((series, deep_viewport, (skipped, z))) if current.0 == 0 && !restart => {
+                    let dc = deep_viewport.delta(x as f64, y as f64, dimensions);
+                    let dz = series.delta(dc);
+                    let z = ComplexNumber::new(z.r + dz.r.into(), z.i + dz.i.into());
+                    let derivative_ref = derivative_ref.map(|derivative| {
+                        let d = series.derivative(dc);
+                        *derivative = ComplexNumber::new(d.r.into(), d.i.into());
+                        derivative
+                    });
+                    self.iterate_coordinate((*skipped, z), derivative_ref, trap_ref, c,
+                                            iterations - skipped)
+                }
+                _ => self.iterate_coordinate(current, derivative_ref, trap_ref, c, limit),
+            };
+            Some((value, derivative, trap))
+        };
+        let skipped = |x: usize, y: usize| {
+            use_self && self.escaped[y][x] || only.is_some_and(|only| !only[y][x])
+        };
+        // Iteration count and whether the pixel escaped after this render
+        let state = |(x, y): (usize, usize), update: &PixelUpdate<F>| match update {
+            Some(((i, z), ..)) => (*i, self.config.bailout.escaped(z)),
+            None => (self.values[y][x].0, self.escaped[y][x]),
+        };
+
+        let subdivides = self.config.render_strategy == RenderStrategy::MarianiSilver
+            && !track_derivative && self.config.orbit_trap.is_none();
+        let vectorised = series.is_none() && self.vectorises()
+            && !self.filled.iter().any(|filled| *filled);
+        let (tiles, result) = if subdivides {
+            self.render_tile_values(|tile| (!cancel.is_cancelled()).then(|| mariani_silver(
+                tile,
+                |x, y| (compute(x, y), false),
+                |border| {
+                    let first = state(border[0].0, &border[0].1.0);
+                    let update = border.iter().find_map(|(_, (update, _))| *update)?;
+                    border.iter()
+                        .all(|(xy, (update, _))| state(*xy, update) == first)
+                        .then_some(update)
+                },
+                |x, y, update| ((!skipped(x, y)).then_some(*update), true),
+            )), progress)
+        } else {
+            self.render_tile_rows(|xs, y| {
+                let updates = if vectorised {
+                    self.iterate_row(xs, y, use_self, only, iterations)
+                } else {
+                    xs.map(|x| compute(x, y)).collect()
+                };
+                updates.into_iter().map(|update| (update, false)).collect()
+            }, progress, cancel)
+        };
+        tiles.into_iter().for_each(|(tile, values)| {
+            tile.pixels()
+                .zip(values)
+                .filter_map(|(xy, (value, filled))| Some((xy, value?, filled)))
+                .for_each(|((x, y), ((i, z), derivative, trap), filled)| {
                     self.values[y][x] = (i, z);
+                    self.escaped[y][x] = self.config.bailout.escaped(&z);
+                    self.filled[y][x] = filled;
+                    self.derivatives[y][x] = derivative;
+                    self.traps[y][x] = trap;
                     self.max_iterations = cmp::max(self.max_iterations, i);
                 });
         });
+        result?;
+
+        let total = self.max_iterations;
+        let Viewport { top_left, bottom_right, .. } = self.config.viewport;
+        let (w, h) = dimensions;
+        let step = ((bottom_right.r - top_left.r) / (w.max(2) - 1).into(),
+                    (bottom_right.i - top_left.i) / (h.max(2) - 1).into());
+        self.analyse_interiors();
+        let (samples, result) = self.supersample(only, |x, y, (dx, dy)| {
+            let c = self.config.viewport.rotate(ComplexNumber::new(
+                self.coords.0[x] + step.0 * dx.into(),
+                self.coords.1[y] + step.1 * dy.into(),
+            ));
+            let start = (0, ComplexNumber::new((0.).into(), (0.).into()));
+            self.iterate_coordinate(start, None, None, c, total)
+        }, progress, cancel);
+        self.samples = samples;
+        result
         // for (y, im) in self.coords.1.iter().enumerate() {
         //     for (x, re) in self.coords.0.iter().enumerate() {
         //         let coordinate = self.iterate_coordinate(
@@ -540,34 +583,495 @@ Mandelbrot<P, F>
         // }
     }
 
+    /// Looks for the attracting cycle of every pixel that has not escaped yet,
+    /// with `config.interior_analysis`
+    fn analyse_interiors(&mut self) {
+        if !self.config.interior_analysis || !self.config.formula.is_multibrot() {
+            return;
+        }
+        let Some(exponent) = self.config.exponent.as_integer() else {
+            return;
+        };
+        let julia = self.config.julia;
+        let width = self.values.width().max(1);
+        let interiors: Vec<_> = self.values.as_slice().par_iter().enumerate().map(|(i, (n, z))| {
+            if *n == 0 || self.config.bailout.escaped(z) {
+                return None;
+            }
+            let (x, y) = (i % width, i / width);
+            let c = julia.unwrap_or_else(|| self.coordinate(x, y));
+            Interior::analyse(*z, c, exponent, julia.is_none())
+        }).collect();
+        self.interiors.as_mut_slice().copy_from_slice(&interiors);
+    }
+
+    /// Like `recalculate` but every pixel only iterates its `f64` delta to a
+    /// reference orbit of the view centre
+    fn recalculate_perturbed(
+        &mut self,
+        use_self: bool,
+        only: Option<&Buffer<bool>>,
+        progress: ProgressFn,
+        cancel: &CancellationToken,
+    ) -> Result<(), Cancelled> {
+        let (Some(deep_viewport), Some(exponent)) =
+            (&self.deep_viewport, self.config.exponent.as_integer()) else {
+            return Ok(());
+        };
+        let iterations = self.iterations;
+        let dimensions = self.config.dimensions;
+        let track_derivative = self.tracks_derivative();
+        let power = self.config.exponent;
+        let orbit_trap = self.config.orbit_trap;
+        // Resumed pixels continue from reference indices of earlier runs
+        let orbit_length = if use_self { self.max_iterations + iterations } else { iterations };
+        let orbit = ReferenceOrbit::with_bailout(deep_viewport, exponent, orbit_length,
+                                                 self.config.bailout)
+            .with_periodicity_check(self.config.periodicity_check);
+        let center = ComplexNumber::new(deep_viewport.re.to_f64(), deep_viewport.im.to_f64());
+        // Rounding `c` to `f64` only matters right at the edge of the cardioid
+        let cardioid_check = self.checks_cardioid() && orbit_trap.is_none();
+        // Traps have to see the skipped part of the orbit as well
+        let series = self.series_skips().then(|| SeriesApproximation::new(
+            &orbit,
+            &Self::series_probes(deep_viewport, dimensions),
+            exponent,
+            iterations,
+        ));
+
+        let (tiles, result) = self.render_tiles(|x, y| {
+            if use_self && self.escaped[y][x] || only.is_some_and(|only| !only[y][x]) {
+                return None;
+            }
+            let state = if use_self {
+                (self.values[y][x].0, self.deltas[y][x])
+            } else {
+                (0, (0, ComplexNumber::new(0., 0.)))
+            };
+            let dc = deep_viewport.delta(x as f64, y as f64, dimensions);
+            let d = self.derivatives[y][x];
+            let mut derivative = ComplexNumber::new(d.r.into(), d.i.into());
+            let mut trap = self.traps[y][x];
+            if state.0 == 0 {
+                derivative = ComplexNumber::new(1., 0.);
+                trap = TrapHit::default();
+            }
+            let c = center + dc;
+            if cardioid_check && Self::in_cardioid_or_bulb(ComplexNumber::new(c.r.into(),
+                                                                              c.i.into())) {
+                let delta = if state.0 == 0 { (1, dc) } else { state.1 };
+                return Some(((state.0 + iterations, delta), derivative, trap));
+            }
+            let (state, limit) = match &series {
+                Some(series) if state.0 == 0 && series.skip() > 1 => {
+                    let skipped = series.skip() as u32 - 1;
+                    derivative = series.derivative(dc);
+                    ((skipped, (series.skip(), series.delta(dc))), iterations - skipped)
+                }
+                _ => (state, iterations),
+            };
+            let value = orbit.iterate_visiting(state, dc, exponent, limit, |z, iteration| {
+                if track_derivative {
+                    derivative = power.derivative(z) * derivative + 1.;
+                }
+                if let Some(orbit_trap) = &orbit_trap {
+                    trap.visit(orbit_trap, z, iteration);
+                }
+            });
+            Some((value, derivative, trap))
+        }, progress, cancel);
+        tiles.into_iter().for_each(|(tile, values)| {
+            tile.pixels()
+                .zip(values)
+                .filter_map(|(xy, value)| Some((xy, value?)))
+                .for_each(|((x, y), ((i, delta), derivative, trap))| {
+                    let z = orbit.value(delta);
+                    self.values[y][x] = (i, ComplexNumber::new(z.r.into(), z.i.into()));
+                    self.escaped[y][x] = self.config.bailout.escaped(&z);
+                    self.filled[y][x] = false;
+                    self.derivatives[y][x] = ComplexNumber::new(derivative.r.into(),
+                                                                derivative.i.into());
+                    self.traps[y][x] = trap;
+                    self.deltas[y][x] = delta;
+                    self.max_iterations = cmp::max(self.max_iterations, i);
+                });
+        });
+
+        result?;
+        self.analyse_interiors();
+
+        let total = self.max_iterations;
+        let Some(deep_viewport) = &self.deep_viewport else {
+            return Ok(());
+        };
+        let (samples, result) = self.supersample(only, |x, y, (dx, dy)| {
+            let dc = deep_viewport.delta(x as f64 + dx, y as f64 + dy, dimensions);
+            let (i, delta) = orbit.iterate((0, (0, dc)), dc, exponent, total);
+            let z = orbit.value(delta);
+            (i, ComplexNumber::new(z.r.into(), z.i.into()))
+        }, progress, cancel);
+        self.samples = samples;
+        result
+    }
+
+    /// Computes the value of every pixel with `compute` one tile at a time,
+    /// reporting every finished tile to `progress`. Once `cancel` is cancelled
+    /// the remaining tiles stop at their next row and only the finished tiles
+    /// are returned.
+    fn render_tiles<T: Send>(
+        &self,
+        compute: impl Fn(usize, usize) -> T + Sync,
+        progress: ProgressFn,
+        cancel: &CancellationToken,
+    ) -> (TileValues<T>, Result<(), Cancelled>) {
+        self.render_tile_rows(|xs, y| xs.map(|x| compute(x, y)).collect(), progress, cancel)
+    }
+
+    /// `render_tiles` that computes the pixels `xs` of row `y` of a tile at
+    /// once
+    fn render_tile_rows<T: Send>(
+        &self,
+        compute: impl Fn(Range<usize>, usize) -> Vec<T> + Sync,
+        progress: ProgressFn,
+        cancel: &CancellationToken,
+    ) -> (TileValues<T>, Result<(), Cancelled>) {
+        self.render_tile_values(|tile| {
+            let mut values = Vec::with_capacity(tile.width * tile.height);
+            for y in tile.y..tile.y + tile.height {
+                if cancel.is_cancelled() {
+                    return None;
+                }
+                values.extend(compute(tile.x..tile.x + tile.width, y));
+            }
+            Some(values)
+        }, progress)
+    }
+
+    /// `render_tiles` that computes a whole tile at once, `compute` returns
+    /// `None` once the render is cancelled
+    fn render_tile_values<T: Send>(
+        &self,
+        compute: impl Fn(Tile) -> Option<Vec<T>> + Sync,
+        progress: ProgressFn,
+    ) -> (TileValues<T>, Result<(), Cancelled>) {
+        let dimensions = (self.values.width(), self.values.height());
+        let tiles = Tile::split(dimensions, self.config.tile_size as usize);
+        let total = tiles.len();
+        let finished = AtomicUsize::new(0);
+
+        let rendered: Vec<_> = tiles.into_par_iter().filter_map(|tile| {
+            let values = compute(tile)?;
+            let finished = finished.fetch_add(1, Ordering::Relaxed) + 1;
+            progress(Progress { tile, finished, total });
+            Some((tile, values))
+        }).collect();
+
+        let result = if rendered.len() < total { Err(Cancelled) } else { Ok(()) };
+        (rendered, result)
+    }
+
+    /// Iterates the extra samples `config.antialiasing` picks for every pixel
+    /// set in `only` with `sample`, which gets the pixel and the offset of the
+    /// sample from its centre in pixels. The samples are taken one tile at a
+    /// time like `render_tiles`, pixels of tiles that were cancelled go
+    /// without samples.
+    fn supersample(
+        &self,
+        only: Option<&Buffer<bool>>,
+        sample: impl Fn(usize, usize, (f64, f64)) -> (u32, ComplexNumber<F>) + Sync,
+        progress: ProgressFn,
+        cancel: &CancellationToken,
+    ) -> (Samples<F>, Result<(), Cancelled>) {
+        let antialiasing = self.config.antialiasing;
+        let offsets = antialiasing.offsets();
+        let (width, height) = (self.values.width(), self.values.height());
+        let kept = |x: usize, y: usize| only.is_some_and(|only| !only[y][x]);
+        let mut samples = Buffer::from_fn(width, height, |x, y| {
+            if kept(x, y) { self.samples[y][x].clone() } else { Vec::new() }
+        });
+        if offsets.is_empty() {
+            return (samples, Ok(()));
+        }
+        let (tiles, result) = self.render_tiles(|x, y| {
+            if kept(x, y) {
+                return None;
+            }
+            let neighbours = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)),
+                (x, y + 1)]
+                .into_iter()
+                .filter_map(|(x, y)| self.values.get(y)?.get(x).map(|v| v.0));
+            if !antialiasing.resamples(self.values[y][x].0, neighbours) {
+                return None;
+            }
+            Some(offsets.iter().map(|offset| sample(x, y, *offset)).collect::<Vec<_>>())
+        }, progress, cancel);
+        tiles.into_iter().for_each(|(tile, values)| {
+            tile.pixels()
+                .zip(values)
+                .filter_map(|(xy, value)| Some((xy, value?)))
+                .for_each(|((x, y), value)| samples[y][x] = value);
+        });
+        (samples, result)
+    }
+
+    /// Series approximation around the view centre for `recalculate`, with the
+    /// high precision view it was built from and the centre's own state after
+    /// the skipped iterations, which every pixel's delta is added to
+    fn direct_series(&self, iterations: u32)
+        -> Option<(SeriesApproximation, DeepViewport, (u32, ComplexNumber<F>))> {
+        let dimensions = self.config.dimensions;
+        let exponent = self.config.exponent.as_integer()?;
+        let deep_viewport = DeepViewport::from_viewport(&self.config.viewport, dimensions);
+        let orbit = ReferenceOrbit::with_bailout(&deep_viewport, exponent, iterations,
+                                                 self.config.bailout);
+        let series = SeriesApproximation::new(
+            &orbit,
+            &Self::series_probes(&deep_viewport, dimensions),
+            exponent,
+            iterations,
+        );
+
+        let skipped = series.skip() as u32 - 1;
+        let Viewport { top_left, bottom_right, .. } = self.config.viewport;
+        let center = ComplexNumber::new(
+            (top_left.r + bottom_right.r) / 2.0.into(),
+            (top_left.i + bottom_right.i) / 2.0.into(),
+        );
+        let reference = self.iterate_coordinate(
+            (0, ComplexNumber::new((0.).into(), (0.).into())),
+            None,
+            None,
+            center,
+            skipped,
+        );
+
+        // The centre may still escape early in `F` if it sits right on the edge
+        (skipped > 0 && reference.0 == skipped).then_some((series, deep_viewport, reference))
+    }
+
+    /// Corners and edge midpoints of the view, where the series error is largest
+    fn series_probes(deep_viewport: &DeepViewport, dimensions: (u32, u32))
+        -> Vec<ComplexNumber<f64>> {
+        let w = dimensions.0.max(1) as f64 - 1.;
+        let h = dimensions.1.max(1) as f64 - 1.;
+        [(0., 0.), (w, 0.), (0., h), (w, h), (w / 2., 0.), (w / 2., h), (0., h / 2.), (w, h / 2.)]
+            .iter()
+            .map(|(x, y)| deep_viewport.delta(*x, *y, dimensions))
+            .collect()
+    }
+
     pub fn redraw(&mut self) {
-        for (r, row) in self.values.iter().enumerate() {
+        let (bailout, exponent) = (self.config.bailout, self.config.exponent.real());
+        if let Some(color_scheme) = &mut self.config.color_scheme {
+            color_scheme.prepare(&self.values, self.max_iterations, bailout, exponent);
+        }
+        let color = |iterations, z| match &self.config.color_scheme {
+            Some(color_scheme) => color_scheme.color(iterations, z, self.max_iterations),
+            None => (self.config.color_fn)(iterations, z, self.max_iterations, bailout, exponent),
+        };
+        let pixel_size = self.pixel_size();
+        let mut rgba = Vec::with_capacity(self.rgba.len());
+        for (r, row) in self.values.rows().enumerate() {
             for (c, (iters, zn)) in row.iter().enumerate() {
-                self.pixels[r][c] = (self.config.color_fn)(*iters, *zn, self.max_iterations);
+                let samples = &self.samples[r][c];
+                let mut pixel = if samples.is_empty() {
+                    color(*iters, *zn)
+                } else {
+                    let colors: Vec<_> = samples.iter()
+                        .map(|(i, z)| color(*i, *z))
+                        .collect();
+                    Pixel::average_linear(&colors).unwrap()
+                };
+                if let (Some(distance_color_fn), Some(distance)) =
+                    (&self.config.distance_color_fn, self.distance_estimate(c, r)) {
+                    pixel = distance_color_fn(pixel, f64::from(distance) / pixel_size);
+                }
+                if let (Some(interior_color_fn), Some(interior)) =
+                    (&self.config.interior_color_fn, self.interior(c, r)) {
+                    let distance = interior.distance.map(|distance| distance / pixel_size);
+                    pixel = interior_color_fn(pixel, Interior { distance, ..interior });
+                }
+                if let (Some(trap_color_fn), Some(trap)) =
+                    (&self.config.trap_color_fn, self.trap(c, r)) {
+                    pixel = trap_color_fn(pixel, trap);
+                }
+                rgba.extend(pixel.get_slice());
             }
         }
+        self.rgba = rgba;
+    }
+
+    /// Estimated distance from pixel `(x, y)` to the set in the complex plane,
+    /// only available with `config.distance_estimation` for escaped points.
+    /// The true distance lies between a quarter and twice this value.
+    pub fn distance_estimate(&self, x: usize, y: usize) -> Option<F> {
+        if !self.tracks_derivative() {
+            return None;
+        }
+        let (_, z) = self.values.get(y)?.get(x)?;
+        let derivative = self.derivatives[y][x];
+        if self.config.bailout.bounded(z) || derivative.norm_sqr() == (0.).into() {
+            return None;
+        }
+
+        let z_abs = z.abs();
+        Some(z_abs * z_abs.ln() / derivative.abs() * (0.5).into())
+    }
+
+    /// `distance_estimate` for every pixel, row by row
+    pub fn distance_estimates(&self) -> Buffer<Option<F>> {
+        Buffer::from_fn(self.values.width(), self.values.height(), |x, y| {
+            self.distance_estimate(x, y)
+        })
+    }
+
+    /// Number of pixels that have not escaped yet, these are the only ones
+    /// raising the iteration limit continues. Includes points inside the set.
+    pub fn undecided(&self) -> usize {
+        self.escaped.iter().filter(|escaped| !**escaped).count()
+    }
+
+    /// Attracting cycle pixel `(x, y)` converges to, only available with
+    /// `config.interior_analysis` for points that have not escaped
+    pub fn interior(&self, x: usize, y: usize) -> Option<Interior> {
+        *self.interiors.get(y)?.get(x)?
+    }
+
+    /// `interior` for every pixel, row by row
+    pub fn interiors(&self) -> &Buffer<Option<Interior>> {
+        &self.interiors
+    }
+
+    /// Closest approach of the orbit of pixel `(x, y)` to `config.orbit_trap`,
+    /// only available while a trap is set
+    pub fn trap(&self, x: usize, y: usize) -> Option<TrapHit> {
+        self.config.orbit_trap?;
+        self.traps.get(y)?.get(x).copied()
+    }
+
+    /// Distance between neighbouring pixels along the real axis
+    fn pixel_size(&self) -> f64 {
+        let Viewport { top_left, bottom_right, .. } = self.config.viewport;
+        let steps = self.config.dimensions.0.max(2) - 1;
+        (f64::from(bottom_right.r) - f64::from(top_left.r)).abs() / steps as f64
     }
 
     pub fn reset(&mut self) {
-        self.pixels
-            .iter_mut()
-            .map(|col| col.iter_mut().map(|px| *px = Pixel::<P>::default()))
-            .count();
-        self.values
-            .iter_mut()
-            .map(|row| {
-                row.iter_mut()
-                    .map(|coor| *coor = (0, ComplexNumber::new((0.0).into(), (0.0).into())))
-            })
-            .count();
+        let default = Pixel::<P>::default().get_slice();
+        self.rgba.chunks_exact_mut(4).for_each(|px| px.copy_from_slice(&default));
+        self.values.fill((0, ComplexNumber::new((0.0).into(), (0.0).into())));
+        self.deltas.fill((0, ComplexNumber::new(0., 0.)));
+        self.interiors.fill(None);
+        self.samples.iter_mut().for_each(Vec::clear);
+        self.escaped.fill(false);
+        self.filled.fill(false);
         self.iterations = 0;
         self.max_iterations = 0;
     }
 
+    /// Whether the series approximation may skip iterations, orbit traps have
+    /// to see the skipped part of the orbit as well
+    fn series_skips(&self) -> bool {
+        self.config.series_approximation && self.perturbable() && self.config.orbit_trap.is_none()
+    }
+
+    /// Whether pixels in the main cardioid or the period 2 bulb are skipped,
+    /// which are only known for the Mandelbrot set of `z^2 + c`
+    fn checks_cardioid(&self) -> bool {
+        self.config.cardioid_check && self.config.exponent == Exponent::Integer(2)
+            && self.perturbable()
+    }
+
+    /// Whether derivatives are tracked for distance estimation, which needs an
+    /// analytic formula
+    fn tracks_derivative(&self) -> bool {
+        self.config.distance_estimation && self.config.formula.is_multibrot()
+    }
+
+    /// Whether `recalculate` may use `iterate_row`, which only does the
+    /// cardioid check of the optional work `iterate_coordinate` does and only
+    /// knows the Euclidean bailout test and integer exponents
+    pub(crate) fn vectorises(&self) -> bool {
+        let primitive = TypeId::of::<F>() == TypeId::of::<f64>()
+            || TypeId::of::<F>() == TypeId::of::<f32>();
+        self.config.simd && primitive && self.config.formula.is_multibrot()
+            && !self.tracks_derivative() && self.config.orbit_trap.is_none()
+            && !self.config.periodicity_check
+            && self.config.bailout.test == BailoutTest::Euclidean
+            && self.config.exponent.as_integer().is_some()
+    }
+
+    /// Does what `recalculate` does for every pixel `xs` of row `y`, but
+    /// iterates `LANES` of them at a time with `iterate_lanes`
+    fn iterate_row(
+        &self,
+        xs: Range<usize>,
+        y: usize,
+        use_self: bool,
+        only: Option<&Buffer<bool>>,
+        limit: u32,
+    ) -> Vec<PixelUpdate<F>> {
+        let mut row = Vec::with_capacity(xs.len());
+        let xs: Vec<_> = xs.collect();
+        for chunk in xs.chunks(LANES) {
+            let mut z = [padding(); LANES];
+            let mut c = [padding(); LANES];
+            // Position in `chunk` and finished iterations of every used lane
+            let mut lanes = Vec::with_capacity(LANES);
+            let mut updates: Vec<PixelUpdate<F>> = Vec::with_capacity(LANES);
+            for (i, &x) in chunk.iter().enumerate() {
+                if use_self && self.escaped[y][x] || only.is_some_and(|only| !only[y][x]) {
+                    updates.push(None);
+                    continue;
+                }
+                let (finished, current) = if use_self {
+                    self.values[y][x]
+                } else {
+                    (0, ComplexNumber::new(F::zero(), F::zero()))
+                };
+                let point = self.coordinate(x, y);
+                let start = if finished == 0 { point } else { current };
+                let constant = self.config.julia.unwrap_or(point);
+                let value = if self.checks_cardioid() && Self::in_cardioid_or_bulb(constant) {
+                    (finished + limit, start)
+                } else {
+                    z[lanes.len()] = start;
+                    c[lanes.len()] = constant;
+                    lanes.push((i, finished));
+                    (finished, start)
+                };
+                updates.push(Some((value, self.derivatives[y][x], self.traps[y][x])));
+            }
+
+            let radius = self.config.bailout.radius().into();
+            let exponent = self.config.exponent.as_integer().unwrap();
+            let counts = iterate_lanes(&mut z, &c, exponent, radius, limit);
+            for (lane, (i, finished)) in lanes.into_iter().enumerate() {
+                if let Some((value, ..)) = &mut updates[i] {
+                    *value = (finished + counts[lane], z[lane]);
+                }
+            }
+            row.extend(updates);
+        }
+        row
+    }
+
+    /// Whether the view is the Mandelbrot set of `z^e + c` with an integer
+    /// `e`, which perturbation and series approximation are derived for
+    fn perturbable(&self) -> bool {
+        self.config.formula.is_multibrot() && self.config.julia.is_none()
+            && self.config.exponent.as_integer().is_some()
+    }
+
+    /// Continues the orbit of the pixel at `point` from `current_coord` for at
+    /// most `limit` iterations. The orbit starts at `point` and adds `point`
+    /// on every iteration, or the Julia constant if one is set.
     fn iterate_coordinate(
         &self,
         current_coord: (u32, ComplexNumber<F>),
-        c: ComplexNumber<F>,
+        mut derivative: Option<&mut ComplexNumber<F>>,
+        mut trap: Option<&mut TrapHit>,
+        point: ComplexNumber<F>,
         limit: u32,
     ) -> (u32, ComplexNumber<F>)
         where
@@ -575,18 +1079,596 @@ Mandelbrot<P, F>
     {
         let mut count = 0;
         let (finished_iters, z) = current_coord;
+        let c = self.config.julia.unwrap_or(point);
+        let exponent = self.config.exponent;
+        let bailout = self.config.bailout;
+        // Julia sets are differentiated with respect to the starting point,
+        // which does not add the `+ 1` of `c`
+        let derivative_offset: F = if self.config.julia.is_some() { 0. } else { 1. }.into();
 
         let mut z = if finished_iters == 0 {
-            c
+            if let Some(derivative) = derivative.as_deref_mut() {
+                *derivative = ComplexNumber::new((1.).into(), (0.).into());
+            }
+            if let Some(trap) = trap.as_deref_mut() {
+                *trap = TrapHit::default();
+            }
+            point
         } else {
             z
         };
 
-        while z.norm_sqr() <= (4.).into() && count < limit {
-            z = c + z.pow(self.config.exponent);
+        // Interior points never escape, the colour functions only need the
+        // iteration count for them so `z` is left as is
+        if self.checks_cardioid() && trap.is_none() && Self::in_cardioid_or_bulb(c) {
+            return (finished_iters + limit, z);
+        }
+
+        // Brent's cycle detection, `saved` is moved up to the current value
+        // after `interval` iterations and the interval is doubled each time
+        let mut saved = z;
+        let mut since_saved = 0;
+        let mut interval: u32 = 1;
+        while bailout.bounded(&z) && count < limit {
+            if let Some(derivative) = derivative.as_deref_mut() {
+                *derivative = exponent.derivative(z) * *derivative + derivative_offset;
+            }
+            if let (Some(trap), Some(orbit_trap)) = (trap.as_deref_mut(), &self.config.orbit_trap) {
+                trap.visit(orbit_trap, ComplexNumber::new(z.r.into(), z.i.into()),
+                           finished_iters + count);
+            }
+            z = self.config.formula.iterate(z, c, exponent);
             count += 1;
+
+            if self.config.periodicity_check {
+                since_saved += 1;
+                if z.r == saved.r && z.i == saved.i {
+                    // The orbit repeats exactly with a period of `since_saved`
+                    // from here on, so only the position in that cycle after
+                    // the remaining iterations is left to work out
+                    for _ in 0..(limit - count) % since_saved {
+                        z = self.config.formula.iterate(z, c, self.config.exponent);
+                    }
+                    return (finished_iters + limit, z);
+                }
+                if since_saved == interval {
+                    saved = z;
+                    since_saved = 0;
+                    interval = interval.saturating_mul(2);
+                }
+            }
         }
 
         (count + finished_iters, z)
     }
+
+    /// Analytic test for the main cardioid and the period-2 bulb of `z^2 + c`
+    fn in_cardioid_or_bulb(c: ComplexNumber<F>) -> bool {
+        let quarter: F = (0.25).into();
+        let x = c.r - quarter;
+        let y2 = c.i * c.i;
+        let q = x * x + y2;
+        let bulb = c.r + (1.).into();
+
+        q * (q + x) < quarter * y2 || bulb * bulb + y2 < (0.0625).into()
+    }
+}
+
+/// Moves the contents of `grid` by `(dx, dy)` cells towards its top left,
+/// filling the cells that come into view with `fill`
+fn shift<T: Clone>(grid: &mut Buffer<T>, (dx, dy): (i32, i32), fill: T) {
+    let (w, h) = (grid.width() as i64, grid.height() as i64);
+    let shifted = Buffer::from_fn(w as usize, h as usize, |x, y| {
+        let (x, y) = (x as i64 + dx as i64, y as i64 + dy as i64);
+        if (0..w).contains(&x) && (0..h).contains(&y) {
+            grid[y as usize][x as usize].clone()
+        } else {
+            fill.clone()
+        }
+    });
+    *grid = shifted;
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::complex_number::ComplexNumber;
+    use crate::config::MandelbrotConfig;
+    use crate::config::viewport::{CenteredViewport, Viewport};
+    use crate::double_double::DoubleDouble;
+    use crate::flatten_array;
+    use crate::perturbation::DeepViewport;
+    use crate::tiles::{Cancelled, CancellationToken};
+
+    use crate::pixel::{Pixel, PixelMath};
+
+    use super::Mandelbrot;
+
+    pub(crate) fn config(dimensions: (u32, u32)) -> MandelbrotConfig<u8, f64> {
+        MandelbrotConfig::default()
+            .with_dimensions(dimensions)
+            .with_viewport(Viewport::default().with_size(dimensions.0 as f64, dimensions.1 as f64))
+    }
+
+    pub(crate) fn iterations(mandelbrot: &Mandelbrot<u8, f64>) -> Vec<u32> {
+        mandelbrot.values.iter().map(|v| v.0).collect()
+    }
+
+    /// 4×3 pixels on the whole numbers from -2 + i to 1 - i, whose orbits are
+    /// short enough to follow by hand
+    pub(crate) fn grid() -> MandelbrotConfig<u8, f64> {
+        config((4, 3)).with_viewport(Viewport::default()
+            .with_top_left(ComplexNumber::new(-2., 1.))
+            .with_bottom_right(ComplexNumber::new(1., -1.))
+            .with_size(4., 3.))
+    }
+
+    /// Escape counts of `grid` after `limit` iterations of `z^2 + c` from
+    /// `z = c`: 1 goes to 2 and 5, -1 + i to -1 - i and -1 + 3i, and -2, -1, 0
+    /// and i never escape
+    pub(crate) fn grid_iterations(limit: u32) -> Vec<u32> {
+        vec![0, 2, limit, 1, limit, limit, limit, 2, 0, 2, limit, 1]
+    }
+
+    #[test]
+    fn perturbation_matches_direct() {
+        let mut direct = Mandelbrot::new(config((40, 30)), 300);
+        let mut perturbed = Mandelbrot::new(config((40, 30)).with_perturbation(true), 300);
+        direct.run(300);
+        perturbed.run(300);
+
+        let direct = iterations(&direct);
+        let perturbed = iterations(&perturbed);
+        let matching = direct.iter().zip(&perturbed).filter(|(a, b)| a == b).count();
+
+        // Pixels right on the boundary may land on either side due to rounding
+        assert!(matching as f64 > direct.len() as f64 * 0.98);
+    }
+
+    #[test]
+    fn perturbation_zooms_past_f64() {
+        let mut mandelbrot = Mandelbrot::new(config((16, 16)), 40000);
+        mandelbrot.set_deep_viewport(DeepViewport::parse(
+            "-0.743643887037158704752191506114774",
+            "0.131825904205311970493132056385139",
+            1e-30,
+            (16, 16),
+        ).unwrap());
+        mandelbrot.run(40000);
+
+        let mut counts = iterations(&mandelbrot);
+        counts.sort();
+        counts.dedup();
+        assert!(counts.len() > 10);
+    }
+
+    #[test]
+    fn double_double_zooms_past_f64() {
+        let parse = |s: &str| s.parse::<DoubleDouble>().unwrap();
+        let (re, im) = ("-0.743643887037158704752191506114774", "0.131825904205311970493132056385139");
+        let half = parse("0.5e-20");
+        let viewport = Viewport::default()
+            .with_top_left(ComplexNumber::new(parse(re) - half, parse(im) + half))
+            .with_bottom_right(ComplexNumber::new(parse(re) + half, parse(im) - half))
+            .with_size(8.into(), 8.into());
+        let mut mandelbrot = Mandelbrot::new(MandelbrotConfig::<u8, DoubleDouble>::default()
+            .with_dimensions((8, 8))
+            .with_viewport(viewport), 20000);
+        mandelbrot.run(20000);
+        let counts: Vec<u32> = mandelbrot.values.iter().map(|v| v.0).collect();
+
+        let mut perturbed = Mandelbrot::new(config((8, 8)), 20000);
+        perturbed.set_deep_viewport(DeepViewport::parse(re, im, 1e-20, (8, 8)).unwrap());
+        perturbed.run(20000);
+        let expected = iterations(&perturbed);
+
+        let matching = counts.iter().zip(&expected).filter(|(a, b)| a == b).count();
+        assert!(matching >= 60, "{counts:?} != {expected:?}");
+        assert_eq!(mandelbrot.get_pixels().len(), 8);
+    }
+
+    #[test]
+    fn series_approximation_matches_full_iteration() {
+        // Shallow enough for plain `f64` to be exact without perturbation
+        for (width, perturbation) in [(1e-6, false), (1e-10, true)] {
+            let viewport = DeepViewport::parse("-0.7436438870371587", "0.1318259042053119", width,
+                                               (24, 24)).unwrap();
+            let mut full = Mandelbrot::new(config((24, 24)), 3000);
+            let mut series = Mandelbrot::new(config((24, 24)).with_series_approximation(true),
+                                             3000);
+            if perturbation {
+                full.set_deep_viewport(viewport.clone());
+                series.set_deep_viewport(viewport.clone());
+            } else {
+                full.update(viewport.to_viewport((24, 24)));
+                series.update(viewport.to_viewport((24, 24)));
+            }
+            full.run(3000);
+            series.run(3000);
+
+            let full = iterations(&full);
+            let series = iterations(&series);
+            let matching = full.iter().zip(&series).filter(|(a, b)| a == b).count();
+            // Chaotic pixels may still drift apart over the remaining iterations
+            assert!(matching as f64 > full.len() as f64 * 0.98, "{full:?} != {series:?}");
+        }
+    }
+
+    #[test]
+    fn cardioid_and_bulb() {
+        let inside = |r, i| Mandelbrot::<u8, f64>::in_cardioid_or_bulb(ComplexNumber::new(r, i));
+
+        assert!(inside(0., 0.));
+        assert!(inside(-0.5, 0.3));
+        assert!(inside(0.24, 0.));
+        assert!(inside(-1., 0.));
+        assert!(inside(-1.2, 0.1));
+        assert!(!inside(0.26, 0.));
+        assert!(!inside(-1.26, 0.));
+        assert!(!inside(-0.75, 0.1));
+        assert!(!inside(-0.1, 0.8));
+    }
+
+    #[test]
+    fn interior_checks_match_brute_force() {
+        for exponent in [2, 3] {
+            let mut brute_force = Mandelbrot::new(config((48, 40)).with_exponent(exponent), 2000);
+            let mut checked = Mandelbrot::new(config((48, 40))
+                                                  .with_exponent(exponent)
+                                                  .with_cardioid_check(true)
+                                                  .with_periodicity_check(true), 2000);
+            // Run twice to also cover resuming from a stored state
+            for _ in 0..2 {
+                brute_force.run(1000);
+                checked.run(1000);
+            }
+
+            assert_eq!(iterations(&brute_force), iterations(&checked));
+            assert_eq!(
+                flatten_array(brute_force.get_pixels()),
+                flatten_array(checked.get_pixels()),
+            );
+        }
+    }
+
+    #[test]
+    fn perturbed_interior_checks_match_brute_force() {
+        let config = |checks| config((48, 40))
+            .with_perturbation(true)
+            .with_cardioid_check(checks)
+            .with_periodicity_check(checks);
+        let mut brute_force = Mandelbrot::new(config(false), 2000);
+        let mut checked = Mandelbrot::new(config(true), 2000);
+        for _ in 0..2 {
+            brute_force.run(1000);
+            checked.run(1000);
+        }
+
+        assert_eq!(iterations(&brute_force), iterations(&checked));
+    }
+
+    #[test]
+    fn periodicity_keeps_ending_point() {
+        let brute_force = Mandelbrot::new(config((4, 4)), 1000);
+        let checked = Mandelbrot::new(config((4, 4)).with_periodicity_check(true), 1000);
+        let start = (0, ComplexNumber::new(0., 0.));
+
+        for c in [(-0.1, 0.2), (-1.1, 0.05), (-0.12, 0.75), (0.3, 0.5), (-1.76, 0.)] {
+            let c = ComplexNumber::new(c.0, c.1);
+            let expected = brute_force.iterate_coordinate(start, None, None, c, 997);
+            let actual = checked.iterate_coordinate(start, None, None, c, 997);
+
+            assert_eq!(expected.0, actual.0);
+            assert_eq!((expected.1.r, expected.1.i), (actual.1.r, actual.1.i));
+        }
+    }
+
+    #[test]
+    fn julia_from_mandelbrot_pixel() {
+        let mut mandelbrot = Mandelbrot::new(config((30, 20)).with_exponent(3), 200);
+        mandelbrot.run(200);
+
+        let (c, viewport) = mandelbrot.julia_at(12, 7).unwrap();
+        assert_eq!((c.r, c.i), (mandelbrot.coords.0[12], mandelbrot.coords.1[7]));
+        assert_eq!((viewport.top_left.r, viewport.bottom_right.r), (-2., 2.));
+        // The default view is square, so is the Julia view
+        assert!((viewport.top_left.i - 2.).abs() < 1e-12);
+
+        mandelbrot.set_julia(Some(c), viewport);
+        assert!(mandelbrot.julia_at(0, 0).is_none());
+        // Resuming has to give the same result as running in one go
+        mandelbrot.run(100);
+        mandelbrot.run(100);
+
+        for (x, y) in [(0, 0), (15, 10), (20, 3), (29, 19)] {
+            let mut z = ComplexNumber::new(mandelbrot.coords.0[x], mandelbrot.coords.1[y]);
+            let mut count = 0;
+            while z.norm_sqr() <= 4. && count < 200 {
+                z = z.pow(3) + c;
+                count += 1;
+            }
+            assert_eq!(mandelbrot.values[y][x].0, count);
+            assert_eq!((mandelbrot.values[y][x].1.r, mandelbrot.values[y][x].1.i), (z.r, z.i));
+        }
+
+        mandelbrot.set_julia(None, Viewport::default().with_size(30., 20.));
+        assert!(mandelbrot.julia_at(0, 0).is_some());
+    }
+
+    #[test]
+    fn distance_estimation() {
+        let mut plain = Mandelbrot::new(config((40, 30)), 500);
+        let mut estimated = Mandelbrot::new(config((40, 30)).with_distance_estimation(true), 500);
+        plain.run(500);
+        estimated.run(250);
+        estimated.run(250);
+        assert_eq!(iterations(&plain), iterations(&estimated));
+        assert!(plain.distance_estimate(0, 0).is_none());
+
+        // 1 escapes to 5 with a derivative of 13, after 3 at 2
+        let mut grid = Mandelbrot::new(grid().with_distance_estimation(true), 10);
+        grid.run(10);
+        assert_eq!(iterations(&grid), grid_iterations(10));
+        let distance = grid.distance_estimate(3, 1).unwrap();
+        assert!((distance - 5. * 5f64.ln() / 13. / 2.).abs() < 1e-15, "{distance}");
+        assert!(grid.distance_estimate(2, 1).is_none());
+
+        // 0 is in the set, so no point can be further from it than from 0
+        let mut count = 0;
+        for (y, row) in estimated.distance_estimates().rows().enumerate() {
+            for (x, distance) in row.iter().enumerate() {
+                let (i, z) = estimated.values[y][x];
+                assert_eq!(distance.is_some(), z.norm_sqr() > 4., "{x} {y} {i}");
+                if let Some(distance) = distance {
+                    let c = ComplexNumber::new(estimated.coords.0[x], estimated.coords.1[y]);
+                    assert!(*distance > 0. && *distance <= 2. * c.abs(), "{distance}");
+                    count += 1;
+                }
+            }
+        }
+        assert!(count > 0);
+
+        // Perturbation and series approximation track the same derivative
+        let mut perturbed = Mandelbrot::new(config((40, 30))
+                                                .with_perturbation(true)
+                                                .with_series_approximation(true)
+                                                .with_distance_estimation(true), 500);
+        perturbed.run(500);
+        let matching = estimated.distance_estimates().into_vec().iter()
+            .zip(perturbed.distance_estimates().into_vec())
+            .filter(|(a, b)| match (a, b) {
+                (Some(a), Some(b)) => (*a - b).abs() <= 1e-6 * a,
+                (a, b) => a.is_none() && b.is_none(),
+            })
+            .count();
+        assert!(matching as f64 > 1200. * 0.98);
+
+        // `z + c` has a derivative of `n` after `n` iterations on both paths
+        let linear = |perturbation| config((40, 30))
+            .with_exponent(1)
+            .with_perturbation(perturbation)
+            .with_distance_estimation(true);
+        let mut direct = Mandelbrot::new(linear(false), 50);
+        let mut perturbed = Mandelbrot::new(linear(true), 50);
+        direct.run(50);
+        perturbed.run(50);
+        for (a, b) in direct.derivatives.iter().zip(perturbed.derivatives.iter()) {
+            assert!((*a + *b * -1.).abs() <= 1e-9 * a.abs(), "{a:?} {b:?}");
+        }
+    }
+
+    #[test]
+    fn distance_recolours_without_recomputing() {
+        let mut mandelbrot = Mandelbrot::new(config((20, 20))
+                                                 .with_distance_estimation(true)
+                                                 .with_distance_color_fn(Some(Box::new(
+                                                     |pixel, _| {
+                                                         let (_, _, _, a) = pixel.get_tuple();
+                                                         Pixel::new_rgba(0, 0, 0, a)
+                                                     }))), 100);
+        mandelbrot.run(100);
+        let before = flatten_array(mandelbrot.get_pixels());
+
+        mandelbrot.config.distance_color_fn = None;
+        mandelbrot.redraw();
+        assert_ne!(flatten_array(mandelbrot.get_pixels()), before);
+        assert_eq!(mandelbrot.distance_estimates().height(), 20);
+    }
+
+    #[test]
+    fn zoom_keeps_pixels_square() {
+        // The default view over 40×30 pixels has pixels taller than wide
+        let mut mandelbrot = Mandelbrot::new(config((40, 30)), 100);
+        let target = mandelbrot.get_xy_complex(30, 10).unwrap();
+        let (top_left, bottom_right) = mandelbrot.zoom((30, 10), 6);
+
+        let pixel = ((bottom_right.r - top_left.r) / 39., (top_left.i - bottom_right.i) / 29.);
+        assert!((pixel.0 - pixel.1).abs() < 1e-15);
+        // The square of 12 pixels fills the 30 rows
+        let old = CenteredViewport::fit(&config((40, 30)).viewport, (40, 30));
+        assert!((pixel.1 - old.scale * 12. / 29.).abs() < 1e-15);
+        let center = mandelbrot.config().viewport.center();
+        assert!((center.r - target.r).abs() < 1e-12 && (center.i - target.i).abs() < 1e-12);
+    }
+
+    #[test]
+    fn zoom_by_keeps_the_anchor() {
+        for perturbation in [false, true] {
+            let viewport = CenteredViewport::new(ComplexNumber::new(-0.75, 0.1), 0.05, (40, 30));
+            let mut mandelbrot = Mandelbrot::new(
+                config((40, 30)).with_viewport(viewport.into()).with_perturbation(perturbation),
+                100,
+            );
+            let close = |a: ComplexNumber<f64>, b: ComplexNumber<f64>| {
+                (a.r - b.r).abs() < 1e-9 && (a.i - b.i).abs() < 1e-9
+            };
+            let point = |mandelbrot: &Mandelbrot<u8, f64>, (x, y): (i32, i32)| mandelbrot
+                .config().viewport
+                .pixel_to_complex((x as f64, y as f64), (40, 30));
+
+            // Corners, the last pixel and a point past the left edge
+            for anchor in [(0, 0), (39, 29), (39, 0), (-15, 12)] {
+                let before = point(&mandelbrot, anchor);
+                let zoomed = mandelbrot.zoom_by(4., anchor);
+                assert!(close(before, point(&mandelbrot, anchor)), "{anchor:?}");
+                assert!(((zoomed.bottom_right.r - zoomed.top_left.r) / 39. - 0.0125).abs()
+                    < 1e-12);
+
+                let unzoomed = mandelbrot.zoom_out(4., anchor);
+                assert!(close(before, point(&mandelbrot, anchor)), "{anchor:?}");
+                assert!(close(unzoomed.center(), ComplexNumber::new(-0.75, 0.1)));
+            }
+        }
+    }
+
+    #[test]
+    fn zoom_to_rect_past_the_edges() {
+        let viewport = CenteredViewport::new(ComplexNumber::new(-0.75, 0.), 0.05, (40, 30));
+        let config = || config((40, 30)).with_viewport(viewport.into());
+        let mut mandelbrot = Mandelbrot::new(config(), 100);
+        let old: Viewport<f64> = viewport.into();
+        let corner = |x: f64, y: f64| old.pixel_to_complex((x, y), (40, 30));
+
+        // Reaches 10 pixels past the left edge and 60 rows below the bottom
+        let zoomed = mandelbrot.zoom_to_rect(-10, 10, 30, 90);
+        let center = zoomed.center();
+        assert!((center.r - corner(10., 50.).r).abs() < 1e-12);
+        assert!((center.i - corner(10., 50.).i).abs() < 1e-12);
+        // The 80 rows fill the height, the pixels stay square
+        let pixel = ((zoomed.bottom_right.r - zoomed.top_left.r) / 39.,
+                     (zoomed.top_left.i - zoomed.bottom_right.i) / 29.);
+        assert!((pixel.0 - 0.05 * 80. / 29.).abs() < 1e-12);
+        assert!((pixel.0 - pixel.1).abs() < 1e-12);
+        assert!(zoomed.top_left.r < corner(-10., 10.).r);
+        assert!(zoomed.bottom_right.r > corner(30., 90.).r);
+
+        // Corners in any order and rectangles without an area
+        let mut swapped = Mandelbrot::new(config(), 100);
+        let swapped = swapped.zoom_to_rect(30, 90, -10, 10);
+        assert_eq!((swapped.top_left, swapped.bottom_right),
+                   (zoomed.top_left, zoomed.bottom_right));
+        let mut point = Mandelbrot::new(config(), 100);
+        let point = point.zoom_to_rect(39, 29, 39, 29);
+        assert!(((point.bottom_right.r - point.top_left.r) / 39. - 0.05 / 29.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn half_turn_mirrors_the_frame() {
+        for perturbation in [false, true] {
+            let config = || config((40, 30)).with_perturbation(perturbation);
+            let mut upright = Mandelbrot::new(config(), 300);
+            let viewport = config().viewport.with_rotation(std::f64::consts::PI);
+            let mut turned = Mandelbrot::new(config().with_viewport(viewport), 300);
+            upright.run(300);
+            turned.run(300);
+
+            let c = turned.get_xy_complex(3, 5).unwrap();
+            let expected = upright.get_xy_complex(36, 24).unwrap();
+            assert!((c.r - expected.r).abs() < 1e-12 && (c.i - expected.i).abs() < 1e-12);
+            let mirrored = (0..1200)
+                .filter(|i| {
+                    let (x, y) = (i % 40, i / 40);
+                    turned.values[y][x].0 == upright.values[29 - y][39 - x].0
+                })
+                .count();
+            assert!(mirrored > 1150, "{mirrored}");
+        }
+
+        // The top left pixel shows 1 - i, which goes to 1 - 3i, and the bottom
+        // right one -2 + i
+        let viewport = grid().viewport.with_rotation(std::f64::consts::PI);
+        let mut grid = Mandelbrot::new(grid().with_viewport(viewport), 10);
+        grid.run(10);
+        let counts = iterations(&grid);
+        assert_eq!([counts[0], counts[3], counts[10], counts[11]], [1, 0, 2, 0]);
+    }
+
+    #[test]
+    fn deepening_only_continues_undecided_pixels() {
+        for perturbation in [false, true] {
+            let config = || config((40, 30)).with_perturbation(perturbation);
+            let mut once = Mandelbrot::new(config(), 500);
+            let mut deepened = Mandelbrot::new(config(), 500);
+            once.run(500);
+            assert_eq!(deepened.undecided(), 1200);
+            deepened.run(300);
+            let escaped: Vec<_> = deepened.values.iter()
+                .map(|(i, z)| (z.norm_sqr() > 4.).then_some(*i))
+                .collect();
+            let undecided = deepened.undecided();
+            assert_eq!(undecided, escaped.iter().filter(|i| i.is_none()).count());
+            deepened.run(200);
+
+            assert_eq!(iterations(&once), iterations(&deepened));
+            assert!(deepened.undecided() <= undecided);
+            for (before, (after, _)) in escaped.iter().zip(deepened.values.as_slice().to_vec()) {
+                if let Some(before) = before {
+                    assert_eq!(*before, after);
+                }
+            }
+        }
+
+        // -2, -1, 0, i and -i never escape, the rest do within two iterations
+        let mut grid = Mandelbrot::new(grid(), 10);
+        grid.run(4);
+        assert_eq!(grid.undecided(), 5);
+        grid.run(6);
+        assert_eq!(grid.undecided(), 5);
+        assert_eq!(iterations(&grid), grid_iterations(10));
+    }
+
+    #[test]
+    fn pan_reuses_pixels() {
+        for perturbation in [false, true] {
+            let mut panned = Mandelbrot::new(config((40, 30)).with_perturbation(perturbation),
+                                             300);
+            panned.run(300);
+            let before = panned.values.clone();
+            panned.pan(7, -4);
+
+            // Kept pixels are moved as they are
+            for y in 4..30 {
+                for x in 0..33 {
+                    let (i, z) = panned.values[y][x];
+                    if !perturbation || z.norm_sqr() > 4. {
+                        assert_eq!(i, before[y - 4][x + 7].0);
+                    }
+                }
+            }
+
+            let mut fresh = Mandelbrot::new(config((40, 30)), 300);
+            match panned.deep_viewport() {
+                Some(deep_viewport) => fresh.set_deep_viewport(deep_viewport.clone()),
+                None => fresh.update(panned.config.viewport),
+            }
+            fresh.run(300);
+            let fresh = iterations(&fresh);
+            let panned = iterations(&panned);
+            let matching = fresh.iter().zip(&panned).filter(|(a, b)| a == b).count();
+            assert!(matching as f64 > fresh.len() as f64 * 0.99, "{fresh:?} != {panned:?}");
+        }
+
+        // The column coming into view escapes right away at 2 + i and after
+        // going to 6 at 2
+        let mut grid = Mandelbrot::new(grid(), 10);
+        grid.run(10);
+        grid.pan(1, 0);
+        assert_eq!(iterations(&grid), [2, 10, 1, 0, 10, 10, 2, 1, 2, 10, 1, 0]);
+        assert_eq!(grid.get_xy_complex(3, 1).unwrap(), ComplexNumber::new(2., 0.));
+    }
+
+    #[test]
+    fn pan_after_cancelled_render() {
+        let mut panned = Mandelbrot::new(config((40, 30)).with_tile_size(16), 300);
+        panned.run(300);
+        // Only the first tile of the new view finishes
+        panned.update(panned.config.viewport);
+        let cancel = CancellationToken::new();
+        assert_eq!(panned.run_with(300, &|_| cancel.cancel(), &cancel), Err(Cancelled));
+        panned.pan(7, -4);
+
+        let mut fresh = Mandelbrot::new(config((40, 30)), 300);
+        fresh.update(panned.config.viewport);
+        fresh.run(300);
+        assert_eq!(iterations(&panned), iterations(&fresh));
+    }
 }
@@ -0,0 +1,387 @@
+use std::fmt::UpperHex;
+
+use num_traits::{AsPrimitive, Bounded, One, Unsigned, Zero};
+use rayon::prelude::*;
+
+#[cfg(feature = "gui")]
+use bevy_ecs::prelude::Resource;
+
+use crate::buffer::Buffer;
+use crate::complex_number::ComplexNumber;
+use crate::config::viewport::{square_zoom_scale, Viewport};
+use crate::pixel::{Pixel, PixelMath};
+
+#[derive(Debug, Clone, PartialEq)]
+/// A polynomial with complex coefficients
+pub struct Polynomial {
+    /// Coefficients from the constant term up, the last one is never zero
+    coefficients: Vec<ComplexNumber<f64>>,
+}
+
+impl Polynomial {
+    /// The polynomial with `coefficients`, from the constant term up. No
+    /// coefficients at all make the zero polynomial
+    pub fn from_coefficients(mut coefficients: Vec<ComplexNumber<f64>>) -> Self {
+        if coefficients.is_empty() {
+            coefficients.push(ComplexNumber::new(0., 0.));
+        }
+        while coefficients.len() > 1 && coefficients.last().is_some_and(|c| c.norm_sqr() == 0.) {
+            coefficients.pop();
+        }
+        Self { coefficients }
+    }
+
+    /// The monic polynomial with `roots`
+    pub fn from_roots(roots: &[ComplexNumber<f64>]) -> Self {
+        let mut coefficients = vec![ComplexNumber::new(1., 0.)];
+        for root in roots {
+            // Multiply by `z - root`
+            let mut next = vec![ComplexNumber::new(0., 0.); coefficients.len() + 1];
+            for (i, c) in coefficients.iter().enumerate() {
+                next[i + 1] = next[i + 1] + *c;
+                next[i] = next[i] + *c * *root * -1.;
+            }
+            coefficients = next;
+        }
+        Self { coefficients }
+    }
+
+    pub fn coefficients(&self) -> &[ComplexNumber<f64>] {
+        &self.coefficients
+    }
+
+    pub fn degree(&self) -> usize {
+        self.coefficients.len() - 1
+    }
+
+    /// The polynomial and its derivative at `z`, by Horner's method
+    pub fn evaluate(&self, z: ComplexNumber<f64>) -> (ComplexNumber<f64>, ComplexNumber<f64>) {
+        let zero = ComplexNumber::new(0., 0.);
+        self.coefficients.iter().rev().fold((zero, zero), |(p, dp), c| {
+            (p * z + *c, dp * z + p)
+        })
+    }
+
+    /// All roots, counted with their multiplicity, found with the
+    /// Durand-Kerner method
+    pub fn roots(&self) -> Vec<ComplexNumber<f64>> {
+        let n = self.degree();
+        if n == 0 {
+            return Vec::new();
+        }
+        let leading = self.coefficients[n];
+        let monic = |z: ComplexNumber<f64>| self.evaluate(z).0 / leading;
+
+        let seed = ComplexNumber::new(0.4, 0.9);
+        let mut roots: Vec<_> = std::iter::successors(Some(ComplexNumber::new(1., 0.)),
+                                                      |z| Some(*z * seed))
+            .take(n)
+            .collect();
+        for _ in 0..1000 {
+            let mut change: f64 = 0.;
+            for i in 0..n {
+                let denominator = roots.iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .fold(ComplexNumber::new(1., 0.), |d, (_, r)| d * (roots[i] + *r * -1.));
+                let step = monic(roots[i]) / denominator;
+                roots[i] = roots[i] + step * -1.;
+                change = change.max(step.norm_sqr());
+            }
+            if change < 1e-28 {
+                break;
+            }
+        }
+        roots
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewtonConfig {
+    /// The pixel dimensions of the image
+    pub dimensions: (u32, u32),
+    /// The `Viewport` the image covers
+    pub viewport: Viewport<f64>,
+    pub polynomial: Polynomial,
+    /// Factor on every Newton step, values other than 1 trade convergence
+    /// speed for more intricate basin boundaries
+    pub relaxation: ComplexNumber<f64>,
+    /// Nova fractal: every point starts at this value and the pixel is added
+    /// as `c` after every step, instead of the pixel being the starting point
+    pub nova: Option<ComplexNumber<f64>>,
+    /// A point has converged once a step moves it less than this, and it
+    /// converged to a root if that lies within the square root of it
+    pub tolerance: f64,
+    /// Exponent of the darkening with the iterations a point needs to converge
+    pub shading: f64,
+}
+
+impl NewtonConfig {
+    pub fn with_dimensions(mut self, dimensions: (u32, u32)) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+
+    pub fn with_viewport(mut self, viewport: Viewport<f64>) -> Self {
+        self.viewport = viewport;
+        self
+    }
+
+    pub fn with_polynomial(mut self, polynomial: Polynomial) -> Self {
+        self.polynomial = polynomial;
+        self
+    }
+
+    pub fn with_relaxation(mut self, relaxation: ComplexNumber<f64>) -> Self {
+        self.relaxation = relaxation;
+        self
+    }
+
+    pub fn with_nova(mut self, nova: Option<ComplexNumber<f64>>) -> Self {
+        self.nova = nova;
+        self
+    }
+
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn with_shading(mut self, shading: f64) -> Self {
+        self.shading = shading;
+        self
+    }
+}
+
+impl Default for NewtonConfig {
+    /// `z^3 - 1` over the square from `-2 + 2i` to `2 - 2i`
+    fn default() -> Self {
+        let one = ComplexNumber::new(1., 0.);
+        let zero = ComplexNumber::new(0., 0.);
+        Self {
+            dimensions: (1000, 1000),
+            viewport: Viewport::default()
+                .with_top_left(ComplexNumber::new(-2., 2.))
+                .with_bottom_right(ComplexNumber::new(2., -2.)),
+            polynomial: Polynomial::from_coefficients(vec![one * -1., zero, zero, one]),
+            relaxation: one,
+            nova: None,
+            tolerance: 1e-6,
+            shading: 4.,
+        }
+    }
+}
+
+/// Colours the plane by the root of `config.polynomial` Newton's method
+/// converges to from every point
+#[cfg_attr(feature = "gui", derive(Resource))]
+pub struct Newton<P: Unsigned + Bounded + UpperHex + Copy + Zero + Send + Sync> {
+    pub(crate) config: NewtonConfig,
+    pub(crate) max_iterations: u32,
+    roots: Vec<ComplexNumber<f64>>,
+    /// Iterations every point needed and the index of the root it converged
+    /// to, `None` if it did not converge to a root
+    values: Buffer<(u32, Option<usize>)>,
+    rgba: Vec<P>,
+}
+
+impl<P> Newton<P>
+    where
+        P: 'static + Unsigned + Bounded + UpperHex + Copy + Zero + One + Send + Sync + Into<f64>,
+        f64: From<P> + AsPrimitive<P>,
+{
+    pub fn new(config: NewtonConfig, max_iterations: u32) -> Self {
+        let (w, h) = config.dimensions;
+        Self {
+            roots: config.polynomial.roots(),
+            values: Buffer::new(w as usize, h as usize, (0, None)),
+            rgba: vec![P::zero(); w as usize * h as usize * 4],
+            config,
+            max_iterations,
+        }
+    }
+
+    pub fn config(&self) -> &NewtonConfig {
+        &self.config
+    }
+
+    pub fn update_config(&mut self, config: NewtonConfig) {
+        *self = Self::new(config, self.max_iterations);
+    }
+
+    pub fn roots(&self) -> &[ComplexNumber<f64>] {
+        &self.roots
+    }
+
+    pub fn values(&self) -> &Buffer<(u32, Option<usize>)> {
+        &self.values
+    }
+
+    pub fn rgba(&self) -> &[P] {
+        &self.rgba
+    }
+
+    /// Calculates and draws the image with `iterations` as the limit
+    pub fn run(&mut self, iterations: u32) {
+        self.max_iterations = iterations;
+        self.recalculate();
+        self.redraw();
+    }
+
+    /// The point of the pixel at `x`, `y`, which may lie outside the image
+    fn point(&self, x: f64, y: f64) -> ComplexNumber<f64> {
+        self.config.viewport.pixel_to_complex((x, y), self.config.dimensions)
+    }
+
+    /// Runs Newton's method from `point`, or from `config.nova` with `point`
+    /// as `c`, until a step is smaller than `config.tolerance`
+    pub fn iterate(&self, point: ComplexNumber<f64>) -> (u32, Option<usize>) {
+        let config = &self.config;
+        let (mut z, c) = match config.nova {
+            Some(start) => (start, point),
+            None => (point, ComplexNumber::new(0., 0.)),
+        };
+        let tolerance = config.tolerance * config.tolerance;
+
+        for n in 0..self.max_iterations {
+            let (p, dp) = config.polynomial.evaluate(z);
+            if dp.norm_sqr() == 0. {
+                return (self.max_iterations, None);
+            }
+            let next = z + config.relaxation * (p / dp) * -1. + c;
+            let step = (next + z * -1.).norm_sqr();
+            z = next;
+            if step < tolerance {
+                let root = self.roots.iter()
+                    .map(|root| (z + *root * -1.).norm_sqr())
+                    .enumerate()
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .filter(|(_, distance)| *distance < config.tolerance)
+                    .map(|(i, _)| i);
+                return (n + 1, root);
+            }
+        }
+        (self.max_iterations, None)
+    }
+
+    pub fn recalculate(&mut self) {
+        let width = self.values.width();
+        let values: Vec<_> = (0..self.values.as_slice().len())
+            .into_par_iter()
+            .map(|i| self.iterate(self.point((i % width) as f64, (i / width) as f64)))
+            .collect();
+        self.values.as_mut_slice().copy_from_slice(&values);
+    }
+
+    /// Colours every pixel by the hue of its root, darker the more
+    /// iterations it needed. Points that converged to something other than a
+    /// root are grey, ones that did not converge are black.
+    pub fn redraw(&mut self) {
+        let max = self.max_iterations.max(1) as f64;
+        let roots = self.roots.len().max(1) as f64;
+        let shading = self.config.shading;
+        self.rgba = self.values
+            .as_slice()
+            .par_iter()
+            .flat_map_iter(|(iterations, root)| {
+                let brightness = (1. - *iterations as f64 / max).max(0.).powf(shading);
+                let pixel = match root {
+                    Some(k) => Pixel::<P>::from_hsb(360. * *k as f64 / roots, 0.8, brightness),
+                    None => Pixel::<P>::from_hsb(0., 0., brightness),
+                }.unwrap();
+                pixel.get_slice()
+            })
+            .collect();
+    }
+
+    /// Zooms to the square of `radius` pixels around `center`, which fills the
+    /// shorter side of the image, and returns the new top left and bottom
+    /// right corner
+    pub fn zoom(&mut self, center: (u32, u32), radius: u32) -> (ComplexNumber<f64>,
+                                                                ComplexNumber<f64>) {
+        let dimensions = self.config.dimensions;
+        let scale = square_zoom_scale(radius, dimensions);
+        self.config.viewport = self.config.viewport
+            .zoomed((center.0 as f64, center.1 as f64), scale, dimensions);
+        self.reset();
+        (self.config.viewport.top_left, self.config.viewport.bottom_right)
+    }
+
+    pub fn reset(&mut self) {
+        self.values.fill((0, None));
+        self.rgba.fill(P::zero());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::complex_number::ComplexNumber;
+    use super::{Newton, NewtonConfig, Polynomial};
+
+    fn close(a: ComplexNumber<f64>, b: ComplexNumber<f64>) -> bool {
+        (a + b * -1.).norm_sqr() < 1e-18
+    }
+
+    #[test]
+    fn polynomial_roots() {
+        let roots = [(1., 0.), (-2., 0.), (0., 3.)].map(|(r, i)| ComplexNumber::new(r, i));
+        let polynomial = Polynomial::from_roots(&roots);
+        assert_eq!(polynomial.degree(), 3);
+        for root in roots {
+            assert!(polynomial.evaluate(root).0.norm_sqr() < 1e-18);
+            assert!(polynomial.roots().iter().any(|found| close(*found, root)));
+        }
+        // z^2 + 1 has the derivative 2z
+        let polynomial = Polynomial::from_coefficients(vec![
+            ComplexNumber::new(1., 0.), ComplexNumber::new(0., 0.), ComplexNumber::new(1., 0.),
+            ComplexNumber::new(0., 0.),
+        ]);
+        assert_eq!(polynomial.degree(), 2);
+        let (p, dp) = polynomial.evaluate(ComplexNumber::new(2., 1.));
+        assert!(close(p, ComplexNumber::new(4., 4.)));
+        assert!(close(dp, ComplexNumber::new(4., 2.)));
+
+        let zero = Polynomial::from_coefficients(vec![]);
+        assert_eq!(zero.degree(), 0);
+        assert_eq!(zero.coefficients(), [ComplexNumber::new(0., 0.)]);
+        assert!(zero.roots().is_empty());
+    }
+
+    #[test]
+    fn colours_basins_by_root() {
+        let config = NewtonConfig::default().with_dimensions((41, 41));
+        let mut newton = Newton::<u8>::new(config, 50);
+        newton.run(50);
+
+        // Points next to the roots of z^3 - 1 converge to them within a few steps
+        for (i, root) in newton.roots().to_vec().into_iter().enumerate() {
+            let (iterations, found) = newton.iterate(root * 1.05);
+            assert_eq!(found, Some(i));
+            assert!(iterations < 10);
+        }
+        let mut basins: Vec<_> = newton.values().iter().filter_map(|(_, root)| *root).collect();
+        basins.sort();
+        basins.dedup();
+        assert_eq!(basins, vec![0, 1, 2]);
+        assert!(newton.rgba().chunks_exact(4).any(|px| px[..3] != [0, 0, 0]));
+
+        // Shorter steps still converge to the same root but need more iterations
+        let relaxed = Newton::<u8>::new(
+            NewtonConfig::default().with_relaxation(ComplexNumber::new(0.5, 0.)), 50);
+        let point = ComplexNumber::new(0.8, 0.3);
+        assert!(relaxed.iterate(point).0 > newton.iterate(point).0);
+        assert_eq!(relaxed.iterate(point).1, newton.iterate(point).1);
+    }
+
+    #[test]
+    fn nova_adds_the_pixel() {
+        let nova = Newton::<u8>::new(
+            NewtonConfig::default().with_nova(Some(ComplexNumber::new(1., 0.))), 100);
+        // With c = 0 the start is a root already
+        assert_eq!(nova.iterate(ComplexNumber::new(0., 0.)).1, Some(0));
+        // Small c moves the fixed point off the root
+        let (iterations, root) = nova.iterate(ComplexNumber::new(0.05, 0.));
+        assert!(iterations < 100);
+        assert_eq!(root, None);
+    }
+}
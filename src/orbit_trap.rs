@@ -0,0 +1,173 @@
+use crate::complex_number::ComplexNumber;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Shape the orbit of every pixel is measured against, colouring by how close
+/// the orbit gets to it rather than by how fast it escapes
+pub enum OrbitTrap {
+    /// A single point
+    Point(ComplexNumber<f64>),
+    /// Infinite line through `point` at `angle` radians to the real axis
+    Line { point: ComplexNumber<f64>, angle: f64 },
+    /// Plus sign centred on `centre` with arms `size` long in every direction
+    Cross { centre: ComplexNumber<f64>, size: f64 },
+    /// Outline of a circle
+    Circle { centre: ComplexNumber<f64>, radius: f64 },
+    /// Pickover stalks, the horizontal and vertical lines through `centre`
+    /// (usually the origin)
+    PickoverStalk(ComplexNumber<f64>),
+}
+
+impl OrbitTrap {
+    /// Distance from `z` to the trap
+    pub fn distance(&self, z: ComplexNumber<f64>) -> f64 {
+        match *self {
+            OrbitTrap::Point(point) => (z + point * -1.).abs(),
+            OrbitTrap::Line { point, angle } => {
+                let d = z + point * -1.;
+                (d.r * angle.sin() - d.i * angle.cos()).abs()
+            }
+            OrbitTrap::Cross { centre, size } => {
+                let d = z + centre * -1.;
+                let (x, y) = (d.r.abs(), d.i.abs());
+                // Distance to the horizontal and the vertical arm
+                let horizontal = (x - size).max(0.).hypot(y);
+                let vertical = (y - size).max(0.).hypot(x);
+                horizontal.min(vertical)
+            }
+            OrbitTrap::Circle { centre, radius } => ((z + centre * -1.).abs() - radius).abs(),
+            OrbitTrap::PickoverStalk(centre) => {
+                (z.r - centre.r).abs().min((z.i - centre.i).abs())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Closest approach of an orbit to the `OrbitTrap`
+pub struct TrapHit {
+    /// Smallest distance of any value of the orbit to the trap
+    pub distance: f64,
+    /// Iteration that value was reached at, starting at 0 for the pixel itself
+    pub iteration: u32,
+}
+
+impl Default for TrapHit {
+    fn default() -> Self {
+        Self {
+            distance: f64::INFINITY,
+            iteration: 0,
+        }
+    }
+}
+
+impl TrapHit {
+    /// Records the value of iteration `iteration` if it is closer to `trap`
+    pub fn visit(&mut self, trap: &OrbitTrap, z: ComplexNumber<f64>, iteration: u32) {
+        let distance = trap.distance(z);
+        if distance < self.distance {
+            *self = Self { distance, iteration };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_4;
+
+    use crate::complex_number::ComplexNumber;
+    use crate::mandelbrot::Mandelbrot;
+    use crate::mandelbrot::tests::{config, grid};
+    use crate::pixel::{Pixel, PixelMath};
+
+    use super::{OrbitTrap, TrapHit};
+
+    fn z(r: f64, i: f64) -> ComplexNumber<f64> {
+        ComplexNumber::new(r, i)
+    }
+
+    #[test]
+    fn distances() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-12;
+
+        assert!(close(OrbitTrap::Point(z(1., 1.)).distance(z(4., 5.)), 5.));
+        let diagonal = OrbitTrap::Line { point: z(0., 0.), angle: FRAC_PI_4 };
+        assert!(close(diagonal.distance(z(1., 1.)), 0.));
+        assert!(close(diagonal.distance(z(1., -1.)), 2f64.sqrt()));
+        let cross = OrbitTrap::Cross { centre: z(0., 0.), size: 1. };
+        assert!(close(cross.distance(z(0.5, 0.2)), 0.2));
+        assert!(close(cross.distance(z(4., 4.)), 5.));
+        let circle = OrbitTrap::Circle { centre: z(0., 0.), radius: 2. };
+        assert!(close(circle.distance(z(0., 0.5)), 1.5));
+        assert!(close(circle.distance(z(3., 0.)), 1.));
+        assert!(close(OrbitTrap::PickoverStalk(z(0., 0.)).distance(z(4., -0.1)), 0.1));
+    }
+
+    #[test]
+    fn keeps_closest_visit() {
+        let trap = OrbitTrap::Point(z(0., 0.));
+        let mut hit = TrapHit::default();
+        hit.visit(&trap, z(1., 0.), 0);
+        hit.visit(&trap, z(0.5, 0.), 1);
+        hit.visit(&trap, z(0.7, 0.), 2);
+
+        assert_eq!(hit, TrapHit { distance: 0.5, iteration: 1 });
+    }
+
+    #[test]
+    fn orbit_trap_matches_brute_force() {
+        let orbit_trap = OrbitTrap::Circle { centre: ComplexNumber::new(0.1, 0.), radius: 0.5 };
+        let configs = [
+            config((30, 20)),
+            config((30, 20)).with_perturbation(true).with_series_approximation(true),
+        ];
+        for config in configs {
+            let mut mandelbrot = Mandelbrot::new(config
+                                                     .with_cardioid_check(true)
+                                                     .with_orbit_trap(Some(orbit_trap)), 100);
+            mandelbrot.run(40);
+            mandelbrot.run(60);
+
+            for (x, y) in [(0, 0), (10, 10), (15, 10), (22, 4), (29, 19)] {
+                let c = mandelbrot.get_xy_complex(x, y).unwrap();
+                let mut z = c;
+                let mut expected = TrapHit::default();
+                for iteration in 0..100 {
+                    if z.norm_sqr() > 4. {
+                        break;
+                    }
+                    expected.visit(&orbit_trap, z, iteration);
+                    z = z.pow(2) + c;
+                }
+
+                let trap = mandelbrot.trap(x, y).unwrap();
+                assert_eq!(trap.iteration, expected.iteration);
+                // Pixels escaping right away never get close
+                assert!(trap.distance == expected.distance
+                    || (trap.distance - expected.distance).abs() < 1e-9);
+            }
+        }
+
+        // 1 is closest to the circle where it starts, -1 at the 0 it keeps
+        // returning to
+        let mut mandelbrot = Mandelbrot::new(grid().with_orbit_trap(Some(orbit_trap)), 10);
+        mandelbrot.run(10);
+        let close = |hit: TrapHit, distance: f64| (hit.distance - distance).abs() < 1e-15;
+        let (one, minus_one) = (mandelbrot.trap(3, 1).unwrap(), mandelbrot.trap(1, 1).unwrap());
+        assert!(close(one, 0.4) && one.iteration == 0);
+        assert!(close(minus_one, 0.4) && minus_one.iteration == 1);
+        assert_eq!(mandelbrot.trap(0, 0).unwrap(), TrapHit::default());
+
+        let mut mandelbrot = Mandelbrot::new(config((10, 10)), 10);
+        mandelbrot.run(10);
+        assert!(mandelbrot.trap(0, 0).is_none());
+        mandelbrot.config.orbit_trap = Some(orbit_trap);
+        mandelbrot.config.trap_color_fn = Some(Box::new(|pixel, trap| {
+            let (_, _, _, a) = pixel.get_tuple();
+            Pixel::new_rgba(0, 0, trap.iteration as u8, a)
+        }));
+        mandelbrot.reset();
+        mandelbrot.run(10);
+        let pixels = mandelbrot.get_pixels();
+        assert_eq!(pixels[5][5].b() as u32, mandelbrot.trap(5, 5).unwrap().iteration);
+    }
+}
@@ -0,0 +1,407 @@
+use num_traits::Float;
+
+use crate::big_fixed::{BigFixed, ParseBigFixedError};
+use crate::complex_number::ComplexNumber;
+use crate::config::bailout::Bailout;
+use crate::config::viewport::Viewport;
+
+#[derive(Debug, Clone)]
+/// High precision location used for perturbation rendering.
+///
+/// The corners of a `Viewport<f64>` collapse onto each other once the zoom
+/// passes ~1e-14, so deep zooms are described by a `BigFixed` centre and the
+/// (plain `f64`) distance between neighbouring pixels instead.
+pub struct DeepViewport {
+    /// Real part of the view centre
+    pub re: BigFixed,
+    /// Imaginary part of the view centre
+    pub im: BigFixed,
+    /// Complex plane step between neighbouring pixels along each axis,
+    /// the imaginary step is negative as rows go down
+    pub pixel_size: (f64, f64),
+    /// Counter-clockwise rotation around the centre in radians, like
+    /// `Viewport::rotation`
+    pub rotation: f64,
+}
+
+impl DeepViewport {
+    pub fn new(re: BigFixed, im: BigFixed, pixel_size: (f64, f64)) -> Self {
+        let precision = Self::precision(pixel_size);
+        Self {
+            re: re.with_precision(precision),
+            im: im.with_precision(precision),
+            pixel_size,
+            rotation: 0.,
+        }
+    }
+
+    pub fn with_rotation(mut self, rotation: f64) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Centres the view on the decimal strings `re`/`im` with a real axis
+    /// span of `width` over `dimensions` pixels
+    pub fn parse(re: &str, im: &str, width: f64, dimensions: (u32, u32)) -> Result<Self,
+        ParseBigFixedError> {
+        let step = width / (dimensions.0.max(2) - 1) as f64;
+        let precision = Self::precision((step, step));
+        Ok(Self::new(
+            BigFixed::parse(re, precision)?,
+            BigFixed::parse(im, precision)?,
+            (step, -step),
+        ))
+    }
+
+    pub fn from_viewport<F>(viewport: &Viewport<F>, dimensions: (u32, u32)) -> Self
+        where F: Float + Send + Sync,
+              f64: From<F>,
+    {
+        let (w, h) = dimensions;
+        let tl = viewport.top_left;
+        let br = viewport.bottom_right;
+        let pixel_size = (
+            (f64::from(br.r) - f64::from(tl.r)) / (w.max(2) - 1) as f64,
+            (f64::from(br.i) - f64::from(tl.i)) / (h.max(2) - 1) as f64,
+        );
+        let precision = Self::precision(pixel_size);
+        // Wider `F`s like `DoubleDouble` keep a remainder past the leading
+        // `f64`, which is exact in `F` and added separately
+        let exact = |v: F| {
+            let hi = f64::from(v);
+            BigFixed::from_f64(hi, precision + 1)
+                + BigFixed::from_f64(f64::from(v - F::from(hi).unwrap()), precision + 1)
+        };
+        let half = |a: F, b: F| exact(a) + exact(b);
+
+        Self::new(
+            half(tl.r, br.r).div_u32(2),
+            half(tl.i, br.i).div_u32(2),
+            pixel_size,
+        ).with_rotation(f64::from(viewport.rotation))
+    }
+
+    fn precision(pixel_size: (f64, f64)) -> usize {
+        BigFixed::precision_for(pixel_size.0.abs().min(pixel_size.1.abs()))
+    }
+
+    /// Offset of pixel `(x, y)` from the view centre
+    pub fn delta(&self, x: f64, y: f64, dimensions: (u32, u32)) -> ComplexNumber<f64> {
+        let offset = self.offset(x, y, dimensions);
+        if self.rotation == 0. {
+            return offset;
+        }
+        let (sin, cos) = self.rotation.sin_cos();
+        ComplexNumber::new(offset.r * cos - offset.i * sin, offset.r * sin + offset.i * cos)
+    }
+
+    /// `delta` before the rotation
+    fn offset(&self, x: f64, y: f64, dimensions: (u32, u32)) -> ComplexNumber<f64> {
+        let (w, h) = dimensions;
+        ComplexNumber::new(
+            (x - (w as f64 - 1.) / 2.) * self.pixel_size.0,
+            (y - (h as f64 - 1.) / 2.) * self.pixel_size.1,
+        )
+    }
+
+    /// `f64` approximation of the view, which is only exact to ~1e-16
+    pub fn to_viewport<F>(&self, dimensions: (u32, u32)) -> Viewport<F>
+        where F: Float + Send + Sync,
+              f64: Into<F>,
+    {
+        let (w, h) = dimensions;
+        let center = ComplexNumber::new(self.re.to_f64(), self.im.to_f64());
+        let tl = center + self.offset(0., 0., dimensions);
+        let br = center + self.offset((w - 1) as f64, (h - 1) as f64, dimensions);
+
+        Viewport::default()
+            .with_top_left(ComplexNumber::new(tl.r.into(), tl.i.into()))
+            .with_bottom_right(ComplexNumber::new(br.r.into(), br.i.into()))
+            .with_size((w as f64).into(), (h as f64).into())
+            .with_rotation(self.rotation.into())
+    }
+
+    /// The view centred on pixel `center`, which may lie outside the current
+    /// view, with pixels `factor` times as large
+    pub fn scaled(&self, center: (f64, f64), factor: f64, dimensions: (u32, u32)) -> Self {
+        let offset = self.delta(center.0, center.1, dimensions);
+        let pixel_size = (self.pixel_size.0 * factor, self.pixel_size.1 * factor);
+        let precision = Self::precision(pixel_size);
+
+        Self::new(
+            self.re.clone().with_precision(precision) + BigFixed::from_f64(offset.r, precision),
+            self.im.clone().with_precision(precision) + BigFixed::from_f64(offset.i, precision),
+            pixel_size,
+        ).with_rotation(self.rotation)
+    }
+
+    /// The view covering the pixel rectangle `top_left`..`bottom_right`, which
+    /// may extend past the edges of the current view
+    pub fn zoom(&self, top_left: (i32, i32), bottom_right: (i32, i32), dimensions: (u32, u32))
+        -> Self {
+        let (w, h) = dimensions;
+        let center = self.delta(
+            (top_left.0 + bottom_right.0) as f64 / 2.,
+            (top_left.1 + bottom_right.1) as f64 / 2.,
+            dimensions,
+        );
+        let pixel_size = (
+            self.pixel_size.0 * (bottom_right.0 - top_left.0) as f64 / (w.max(2) - 1) as f64,
+            self.pixel_size.1 * (bottom_right.1 - top_left.1) as f64 / (h.max(2) - 1) as f64,
+        );
+        let precision = Self::precision(pixel_size);
+
+        Self::new(
+            self.re.clone().with_precision(precision)
+                + BigFixed::from_f64(center.r, precision),
+            self.im.clone().with_precision(precision)
+                + BigFixed::from_f64(center.i, precision),
+            pixel_size,
+        ).with_rotation(self.rotation)
+    }
+}
+
+/// Orbit of the view centre, computed in `BigFixed` and stored rounded to
+/// `f64` so that every pixel can iterate only its (small) difference to it
+pub struct ReferenceOrbit {
+    orbit: Vec<ComplexNumber<f64>>,
+    bailout: Bailout,
+    periodicity_check: bool,
+}
+
+impl ReferenceOrbit {
+    /// Iterates `z = z^exponent + c` from `z = 0` for the centre of `viewport`
+    /// until it escapes or `limit` iterations have been stored
+    pub fn new(viewport: &DeepViewport, exponent: u32, limit: u32) -> Self {
+        Self::with_bailout(viewport, exponent, limit, Bailout::default())
+    }
+
+    /// `new` for an orbit, and pixels iterated against it, that escape past
+    /// `bailout`
+    pub fn with_bailout(viewport: &DeepViewport, exponent: u32, limit: u32, bailout: Bailout)
+        -> Self {
+        let c = (viewport.re.clone(), viewport.im.clone());
+        let mut orbit = Vec::with_capacity(limit as usize + 2);
+        orbit.push(ComplexNumber::new(0., 0.));
+        orbit.push(ComplexNumber::new(c.0.to_f64(), c.1.to_f64()));
+
+        let mut z = c.clone();
+        while orbit.len() < limit as usize + 2 {
+            let mut power = z.clone();
+            for _ in 1..exponent {
+                power = (
+                    &power.0 * &z.0 - &power.1 * &z.1,
+                    &power.0 * &z.1 + &power.1 * &z.0,
+                );
+            }
+            z = (power.0 + c.0.clone(), power.1 + c.1.clone());
+
+            let rounded = ComplexNumber::new(z.0.to_f64(), z.1.to_f64());
+            if bailout.escaped(&rounded) {
+                break;
+            }
+            orbit.push(rounded);
+        }
+
+        Self { orbit, bailout, periodicity_check: false }
+    }
+
+    /// Stop pixels once their full value is found to repeat, like
+    /// `MandelbrotConfig::periodicity_check` does for direct iteration
+    pub fn with_periodicity_check(mut self, periodicity_check: bool) -> Self {
+        self.periodicity_check = periodicity_check;
+        self
+    }
+
+    pub fn bailout(&self) -> Bailout {
+        self.bailout
+    }
+
+    pub fn len(&self) -> usize {
+        self.orbit.len()
+    }
+
+    /// Reference value `Z_n`
+    pub fn get(&self, n: usize) -> ComplexNumber<f64> {
+        self.orbit[n]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orbit.is_empty()
+    }
+
+    /// Continues a pixel from its stored state `(iterations, (reference index,
+    /// delta))` for at most `limit` more iterations. Works like
+    /// `Mandelbrot::iterate_coordinate` but only the delta to the reference is
+    /// iterated, which stays representable in `f64` at any zoom depth.
+    pub fn iterate(
+        &self,
+        state: (u32, (usize, ComplexNumber<f64>)),
+        dc: ComplexNumber<f64>,
+        exponent: u32,
+        limit: u32,
+    ) -> (u32, (usize, ComplexNumber<f64>)) {
+        self.iterate_visiting(state, dc, exponent, limit, |_, _| {})
+    }
+
+    /// `iterate` that also calls `visit` with the full value of the pixel and
+    /// its iteration for every value of the orbit that has not escaped, before
+    /// it is iterated
+    pub fn iterate_visiting(
+        &self,
+        state: (u32, (usize, ComplexNumber<f64>)),
+        dc: ComplexNumber<f64>,
+        exponent: u32,
+        limit: u32,
+        mut visit: impl FnMut(ComplexNumber<f64>, u32),
+    ) -> (u32, (usize, ComplexNumber<f64>)) {
+        let (finished_iters, (mut n, mut dz)) = state;
+        if finished_iters == 0 {
+            n = 1;
+            dz = dc;
+        }
+
+        let step = |(mut n, mut dz): (usize, ComplexNumber<f64>)| {
+            let z = self.orbit[n] + dz;
+            // Rebase onto the start of the orbit when the pixel gets closer to
+            // zero than to the reference or runs past its end, this replaces
+            // glitch detection with a second reference
+            if z.norm_sqr() < dz.norm_sqr() || n + 1 >= self.orbit.len() {
+                dz = z;
+                n = 0;
+            }
+            (n + 1, Self::perturb(self.orbit[n], dz, exponent) + dc)
+        };
+
+        // Brent's cycle detection on the full value, as in
+        // `Mandelbrot::iterate_coordinate`
+        let mut saved = self.value((n, dz));
+        let mut since_saved = 0;
+        let mut interval: u32 = 1;
+        let mut count = 0;
+        while count < limit {
+            let z = self.orbit[n] + dz;
+            if self.bailout.escaped(&z) {
+                break;
+            }
+            visit(z, finished_iters + count);
+            (n, dz) = step((n, dz));
+            count += 1;
+
+            if self.periodicity_check {
+                since_saved += 1;
+                let z = self.value((n, dz));
+                if z.r == saved.r && z.i == saved.i {
+                    for _ in 0..(limit - count) % since_saved {
+                        (n, dz) = step((n, dz));
+                    }
+                    return (finished_iters + limit, (n, dz));
+                }
+                if since_saved == interval {
+                    saved = z;
+                    since_saved = 0;
+                    interval = interval.saturating_mul(2);
+                }
+            }
+        }
+
+        (count + finished_iters, (n, dz))
+    }
+
+    /// Full value of a pixel from its reference index and delta
+    pub fn value(&self, (n, dz): (usize, ComplexNumber<f64>)) -> ComplexNumber<f64> {
+        self.orbit[n.min(self.orbit.len() - 1)] + dz
+    }
+
+    /// `(z + dz)^e - z^e` expanded binomially so that no large terms cancel
+    pub(crate) fn perturb(z: ComplexNumber<f64>, dz: ComplexNumber<f64>, exponent: u32) -> ComplexNumber<f64> {
+        if exponent == 2 {
+            return dz * (z * 2. + dz);
+        }
+
+        let mut result = ComplexNumber::new(0., 0.);
+        let mut dz_power = dz;
+        let mut binomial = exponent as f64;
+        for k in 1..=exponent {
+            let term = if k == exponent {
+                dz_power
+            } else {
+                z.pow(exponent - k) * dz_power
+            };
+            result = result + term * binomial;
+            dz_power *= dz;
+            binomial = binomial * (exponent - k) as f64 / (k + 1) as f64;
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::big_fixed::BigFixed;
+    use crate::complex_number::ComplexNumber;
+
+    use super::{DeepViewport, ReferenceOrbit};
+
+    fn direct(c: ComplexNumber<f64>, exponent: u32, limit: u32) -> (u32, ComplexNumber<f64>) {
+        let mut z = c;
+        let mut count = 0;
+        while z.norm_sqr() <= 4. && count < limit {
+            z = c + z.pow(exponent);
+            count += 1;
+        }
+        (count, z)
+    }
+
+    #[test]
+    fn matches_direct_iteration() {
+        for exponent in [2, 3] {
+            let viewport = DeepViewport::new(
+                BigFixed::from_f64(-0.75, 4),
+                BigFixed::from_f64(0.1, 4),
+                (0.01, -0.01),
+            );
+            let orbit = ReferenceOrbit::new(&viewport, exponent, 500);
+            for (x, y) in [(0., 0.), (3., 7.), (9., 9.), (5., 2.)] {
+                let dc = viewport.delta(x, y, (10, 10));
+                let c = ComplexNumber::new(-0.75 + dc.r, 0.1 + dc.i);
+                let (count, state) = orbit.iterate((0, (0, dc)), dc, exponent, 500);
+                let (expected, z) = direct(c, exponent, 500);
+
+                assert_eq!(count, expected);
+                assert!((orbit.value(state).r - z.r).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn resumes_from_state() {
+        let viewport = DeepViewport::new(
+            BigFixed::from_f64(-0.1, 4),
+            BigFixed::from_f64(0.8, 4),
+            (0.001, -0.001),
+        );
+        let orbit = ReferenceOrbit::new(&viewport, 2, 200);
+        let dc = viewport.delta(1., 2., (4, 4));
+
+        let whole = orbit.iterate((0, (0, dc)), dc, 2, 200);
+        let half = orbit.iterate((0, (0, dc)), dc, 2, 100);
+        let resumed = orbit.iterate(half, dc, 2, 100);
+
+        assert_eq!(whole.0, resumed.0);
+        assert_eq!(orbit.value(whole.1), orbit.value(resumed.1));
+    }
+
+    #[test]
+    fn zoom_keeps_precision() {
+        let mut viewport = DeepViewport::parse("-1.25", "0", 1., (100, 100)).unwrap();
+        for _ in 0..110 {
+            viewport = viewport.zoom((44, 44), (55, 55), (100, 100));
+        }
+
+        assert!(viewport.pixel_size.0 < 1e-100);
+        assert!(viewport.re.precision() > 11);
+        assert!((viewport.re.to_f64() + 1.25).abs() < 1e-10);
+    }
+}
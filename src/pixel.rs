@@ -176,6 +176,41 @@ impl<T: 'static + Unsigned + Bounded + UpperHex + Zero + One + Copy + Send + Syn
     }
 }
 
+impl<T: 'static + Unsigned + Bounded + Send + Sync + Copy + Into<f64>> Pixel<T>
+    where
+        f64: AsPrimitive<T>,
+{
+    /// Mean of `pixels` taken in linear light, decoding the sRGB channels
+    /// before averaging so that edges do not come out too dark
+    pub fn average_linear(pixels: &[Self]) -> Option<Self> {
+        if pixels.is_empty() {
+            return None;
+        }
+
+        let max: f64 = T::max_value().into();
+        let decode = |v: T| {
+            let v = v.into() / max;
+            if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+        };
+        let encode = |v: f64| {
+            let v = if v <= 0.0031308 { v * 12.92 } else { 1.055 * v.powf(1. / 2.4) - 0.055 };
+            (v.clamp(0., 1.) * max).round().as_()
+        };
+
+        let n = pixels.len() as f64;
+        let sum = pixels.iter().fold([0.; 4], |[r, g, b, a], pixel| {
+            [r + decode(pixel.r), g + decode(pixel.g), b + decode(pixel.b), a + pixel.a.into()]
+        });
+        Some(Self {
+            r: encode(sum[0] / n),
+            g: encode(sum[1] / n),
+            b: encode(sum[2] / n),
+            // Alpha is already linear
+            a: (sum[3] / n).round().as_(),
+        })
+    }
+}
+
 pub struct PixelIter<'a, T: 'a + Unsigned + Bounded + Send + Sync + Copy> {
     px: &'a Pixel<T>,
     remaining: u8,
@@ -223,6 +258,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn average_linear() {
+        let black = Pixel::new(0u8, 0, 0);
+        let white = Pixel::new(255u8, 255, 255);
+        let red = Pixel::new(255u8, 0, 0);
+
+        assert!(Pixel::<u8>::average_linear(&[]).is_none());
+        assert_eq!(Pixel::average_linear(&[red.clone(), red]).unwrap().get_tuple(),
+                   (255, 0, 0, 255));
+        // Half the light of white is lighter than half the sRGB value
+        assert_eq!(Pixel::average_linear(&[black, white]).unwrap().get_tuple(),
+                   (188, 188, 188, 255));
+    }
+
     fn test_hsb_to_rgb(h: f64, s: f64, v: f64, r: u8, g: u8, b: u8) {
         assert_eq!(
             (r, g, b, 255u8),
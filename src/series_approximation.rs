@@ -0,0 +1,175 @@
+use crate::complex_number::ComplexNumber;
+use crate::perturbation::ReferenceOrbit;
+
+/// Largest error relative to the exactly iterated delta a probe may have for
+/// the series to still be used at that iteration
+const TOLERANCE: f64 = 1e-12;
+
+#[derive(Debug, Clone, Copy)]
+/// Third order series `dz_n = A_n dc + B_n dc^2 + C_n dc^3` approximating the
+/// delta of every pixel to a `ReferenceOrbit` after `n` iterations.
+///
+/// The coefficients only depend on the reference orbit, so they are computed
+/// once per viewport and let every pixel jump straight to iteration `n`.
+pub struct SeriesApproximation {
+    /// Orbit index the coefficients are valid for
+    skip: usize,
+    coefficients: [ComplexNumber<f64>; 3],
+}
+
+impl SeriesApproximation {
+    /// Advances the coefficients along `orbit` for as long as the series stays
+    /// within the error bound for every delta in `probes` (usually the corners
+    /// of the view, where the error is largest), skipping at most `limit`
+    /// iterations
+    pub fn new(orbit: &ReferenceOrbit, probes: &[ComplexNumber<f64>], exponent: u32, limit: u32)
+        -> Self {
+        let zero = ComplexNumber::new(0., 0.);
+        let one = ComplexNumber::new(1., 0.);
+        let mut accepted = Self {
+            skip: 1,
+            coefficients: [one, zero, zero],
+        };
+        let mut exact = probes.to_vec();
+
+        let e = exponent as f64;
+        let binomial = (e * (e - 1.) / 2., e * (e - 1.) * (e - 2.) / 6.);
+
+        while accepted.skip < (limit as usize + 1).min(orbit.len().saturating_sub(1)) {
+            let n = accepted.skip;
+            let z = orbit.get(n);
+            let [a, b, c] = accepted.coefficients;
+
+            let first = z.pow(exponent - 1) * e;
+            let second = if exponent >= 2 { z.pow(exponent - 2) * binomial.0 } else { zero };
+            let third = if exponent >= 3 { z.pow(exponent - 3) * binomial.1 } else { zero };
+            let next = Self {
+                skip: n + 1,
+                coefficients: [
+                    first * a + one,
+                    first * b + second * a * a,
+                    first * c + second * a * b * 2. + third * a * a * a,
+                ],
+            };
+
+            let within_bound = probes.iter().zip(exact.iter_mut()).all(|(dc, dz)| {
+                *dz = ReferenceOrbit::perturb(z, *dz, exponent) + *dc;
+                let error = next.delta(*dc) + *dz * -1.;
+                let escaped = orbit.bailout().escaped(&(orbit.get(n + 1) + *dz));
+                // Written so that overflowing coefficients (NaN) also fail
+                !escaped && error.norm_sqr() <= TOLERANCE * TOLERANCE * dz.norm_sqr()
+            });
+            if !within_bound {
+                break;
+            }
+            accepted = next;
+        }
+
+        accepted
+    }
+
+    /// Orbit index every pixel starts at, `skip() - 1` iterations are skipped
+    pub fn skip(&self) -> usize {
+        self.skip
+    }
+
+    /// Delta to the reference orbit at `skip()` for a pixel `dc` off its centre
+    pub fn delta(&self, dc: ComplexNumber<f64>) -> ComplexNumber<f64> {
+        let [a, b, c] = self.coefficients;
+        ((c * dc + b) * dc + a) * dc
+    }
+
+    /// Derivative of `delta` with respect to `dc`, which is also the derivative
+    /// of the full value since the reference does not depend on `dc`
+    pub fn derivative(&self, dc: ComplexNumber<f64>) -> ComplexNumber<f64> {
+        let [a, b, c] = self.coefficients;
+        (c * dc * 3. + b * 2.) * dc + a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::big_fixed::BigFixed;
+    use crate::complex_number::ComplexNumber;
+    use crate::perturbation::{DeepViewport, ReferenceOrbit};
+
+    use super::SeriesApproximation;
+
+    fn viewport() -> DeepViewport {
+        DeepViewport::parse(
+            "-0.743643887037158704752191506114774",
+            "0.131825904205311970493132056385139",
+            1e-12,
+            (20, 20),
+        ).unwrap()
+    }
+
+    fn corners(viewport: &DeepViewport) -> Vec<ComplexNumber<f64>> {
+        [(0., 0.), (19., 0.), (0., 19.), (19., 19.)]
+            .iter()
+            .map(|(x, y)| viewport.delta(*x, *y, (20, 20)))
+            .collect()
+    }
+
+    #[test]
+    fn skips_iterations_on_deep_zooms() {
+        let viewport = viewport();
+        let orbit = ReferenceOrbit::new(&viewport, 2, 5000);
+        let series = SeriesApproximation::new(&orbit, &corners(&viewport), 2, 5000);
+
+        assert!(series.skip() > 100, "only skipped to {}", series.skip());
+    }
+
+    #[test]
+    fn matches_perturbed_iteration() {
+        for exponent in [2, 3] {
+            let viewport = DeepViewport::new(
+                BigFixed::from_f64(-0.1, 4),
+                BigFixed::from_f64(0.8, 4),
+                (1e-9, -1e-9),
+            );
+            let orbit = ReferenceOrbit::new(&viewport, exponent, 1000);
+            let probes = [(0., 0.), (9., 9.)].map(|(x, y)| viewport.delta(x, y, (10, 10)));
+            let series = SeriesApproximation::new(&orbit, &probes, exponent, 1000);
+            let skipped = series.skip() as u32 - 1;
+            assert!(skipped > 0);
+
+            let dc = viewport.delta(3., 7., (10, 10));
+            let (_, state) = orbit.iterate((0, (0, dc)), dc, exponent, skipped);
+            let dz = series.delta(dc);
+
+            assert_eq!(state.0, series.skip());
+            assert!((state.1.r - dz.r).abs() <= 1e-5 * state.1.abs());
+            assert!((state.1.i - dz.i).abs() <= 1e-5 * state.1.abs());
+        }
+    }
+
+    #[test]
+    fn linear_exponent() {
+        // `z + c` has no second order terms, the series is exact
+        let viewport = DeepViewport::new(
+            BigFixed::from_f64(0.01, 4),
+            BigFixed::from_f64(0.01, 4),
+            (1e-6, -1e-6),
+        );
+        let orbit = ReferenceOrbit::new(&viewport, 1, 1000);
+        let probes = [(0., 0.), (9., 9.)].map(|(x, y)| viewport.delta(x, y, (10, 10)));
+        let series = SeriesApproximation::new(&orbit, &probes, 1, 1000);
+        assert!(series.skip() > 100, "only skipped to {}", series.skip());
+
+        let dc = viewport.delta(3., 7., (10, 10));
+        let (_, state) = orbit.iterate((0, (0, dc)), dc, 1, series.skip() as u32 - 1);
+        let dz = series.delta(dc);
+        assert!((state.1.r - dz.r).abs() <= 1e-9 * state.1.abs());
+        assert!((state.1.i - dz.i).abs() <= 1e-9 * state.1.abs());
+    }
+
+    #[test]
+    fn respects_limit() {
+        let viewport = viewport();
+        let orbit = ReferenceOrbit::new(&viewport, 2, 5000);
+        let series = SeriesApproximation::new(&orbit, &corners(&viewport), 2, 10);
+
+        assert_eq!(series.skip(), 11);
+    }
+}
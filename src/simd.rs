@@ -0,0 +1,142 @@
+use num_traits::Float;
+
+use crate::complex_number::ComplexNumber;
+
+/// Points iterated together by `iterate_lanes`, 8 `f64`s fill an AVX-512
+/// register and two AVX2 ones
+pub const LANES: usize = 8;
+
+/// Starting value for lanes without a point, it has escaped already for any
+/// radius so the lane never holds the others up
+pub fn padding<T: Float + Send + Sync>() -> ComplexNumber<T> {
+    ComplexNumber::new(T::infinity(), T::zero())
+}
+
+/// Iterates `z = z^exponent + c` for `N` points at once, split into one
+/// array per component so the loops below compile to vector instructions for
+/// `f32` and `f64`. Works like `Mandelbrot::iterate_coordinate` without the
+/// optional checks: every lane stops when its distance to zero exceeds
+/// `radius` or after `limit` iterations, escaped lanes are masked and keep their value while the rest
+/// go on. Returns the iterations done by every lane.
+///
+/// The operations are the ones of `ComplexNumber` in the same order, so the
+/// results match the scalar path bit for bit.
+pub fn iterate_lanes<T: Float + Send + Sync, const N: usize>(
+    z: &mut [ComplexNumber<T>; N],
+    c: &[ComplexNumber<T>; N],
+    exponent: u32,
+    radius: T,
+    limit: u32,
+) -> [u32; N] {
+    let radius_sqr = radius * radius;
+    let mut re = z.map(|z| z.r);
+    let mut im = z.map(|z| z.i);
+    let c_re = c.map(|c| c.r);
+    let c_im = c.map(|c| c.i);
+    let mut counts = [0; N];
+    let mut active = [true; N];
+
+    for _ in 0..limit {
+        let mut any = false;
+        for lane in 0..N {
+            active[lane] &= (re[lane] * re[lane]) + (im[lane] * im[lane]) <= radius_sqr;
+            any |= active[lane];
+        }
+        if !any {
+            break;
+        }
+
+        // `ComplexNumber::pow`, computed for every lane and only kept for
+        // the active ones so that there is no branch per lane
+        let (mut pow_re, mut pow_im) = (re, im);
+        for _ in 1..exponent {
+            for lane in 0..N {
+                let (r, i) = (pow_re[lane], pow_im[lane]);
+                pow_re[lane] = (r * re[lane]) - (i * im[lane]);
+                pow_im[lane] = (r * im[lane]) + (i * re[lane]);
+            }
+        }
+        for lane in 0..N {
+            if active[lane] {
+                re[lane] = c_re[lane] + pow_re[lane];
+                im[lane] = c_im[lane] + pow_im[lane];
+                counts[lane] += 1;
+            }
+        }
+    }
+
+    for lane in 0..N {
+        z[lane] = ComplexNumber::new(re[lane], im[lane]);
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::complex_number::ComplexNumber;
+    use crate::mandelbrot::Mandelbrot;
+    use crate::mandelbrot::tests::{config, grid, grid_iterations, iterations};
+
+    use super::{iterate_lanes, padding};
+
+    fn scalar(mut z: ComplexNumber<f32>, c: ComplexNumber<f32>, exponent: u32, limit: u32)
+        -> (u32, ComplexNumber<f32>) {
+        let mut count = 0;
+        while z.norm_sqr() <= 4. && count < limit {
+            z = c + z.pow(exponent);
+            count += 1;
+        }
+        (count, z)
+    }
+
+    #[test]
+    fn matches_scalar() {
+        for exponent in [2, 3] {
+            let c: [ComplexNumber<f32>; 4] = [(-0.75, 0.1), (0.3, 0.5), (-1.2, 0.), (0.26, 0.)]
+                .map(|(r, i)| ComplexNumber::new(r, i));
+            let mut z = c;
+            z[3] = padding();
+            let counts = iterate_lanes(&mut z, &c, exponent, 2., 300);
+
+            for lane in 0..3 {
+                let (count, expected) = scalar(c[lane], c[lane], exponent, 300);
+                assert_eq!(counts[lane], count);
+                assert_eq!((z[lane].r, z[lane].i), (expected.r, expected.i));
+            }
+            assert_eq!(counts[3], 0);
+        }
+    }
+
+    #[test]
+    fn simd_matches_generic() {
+        for (exponent, julia) in [(2, None), (3, None), (2, Some(ComplexNumber::new(-0.8, 0.156)))] {
+            let config = |simd| config((37, 23))
+                .with_exponent(exponent)
+                .with_julia(julia)
+                .with_cardioid_check(true)
+                .with_tile_size(16)
+                .with_simd(simd);
+            let mut generic = Mandelbrot::new(config(false), 300);
+            let mut vectorised = Mandelbrot::new(config(true), 300);
+            assert!(vectorised.vectorises() && !generic.vectorises());
+            for iterations in [200, 100] {
+                generic.run(iterations);
+                vectorised.run(iterations);
+
+                let bits = |mandelbrot: &Mandelbrot<u8, f64>| mandelbrot.values().iter()
+                    .map(|(i, z)| (*i, z.r.to_bits(), z.i.to_bits()))
+                    .collect::<Vec<_>>();
+                assert_eq!(bits(&generic), bits(&vectorised));
+            }
+        }
+
+        // Cubing takes -1 to -2 and -9, and i to 0 and back
+        for (exponent, expected) in [(2, grid_iterations(10)),
+                                     (3, vec![0, 1, 10, 1, 1, 2, 10, 2, 0, 1, 10, 1])] {
+            let mut grid = Mandelbrot::new(grid().with_exponent(exponent).with_simd(true), 10);
+            assert!(grid.vectorises());
+            grid.run(10);
+            assert_eq!(iterations(&grid), expected);
+        }
+    }
+}
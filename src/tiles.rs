@@ -0,0 +1,280 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Rectangle of pixels rendered as one unit of work
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    /// Splits a frame of `dimensions` pixels into tiles of at most
+    /// `size`×`size` pixels, row by row
+    pub fn split((width, height): (usize, usize), size: usize) -> Vec<Tile> {
+        let size = size.max(1);
+        (0..height).step_by(size)
+            .flat_map(|y| (0..width).step_by(size).map(move |x| Tile {
+                x,
+                y,
+                width: size.min(width - x),
+                height: size.min(height - y),
+            }))
+            .collect()
+    }
+
+    /// Coordinates of every pixel in the tile, row by row
+    pub fn pixels(&self) -> impl Iterator<Item=(usize, usize)> {
+        let Tile { x, y, width, height } = *self;
+        (y..y + height).flat_map(move |y| (x..x + width).map(move |x| (x, y)))
+    }
+}
+
+/// Renders `tile` by the rectangle subdivision of Mariani and Silver: only
+/// the border of a rectangle is computed with `compute`, and when `uniform`
+/// accepts the border its inside is filled with `fill` instead, otherwise
+/// the rectangle is split in four and each quarter is checked the same way.
+/// Returns the values of the tile row by row.
+pub fn mariani_silver<T: Clone, U>(
+    tile: Tile,
+    compute: impl Fn(usize, usize) -> T,
+    uniform: impl Fn(&[((usize, usize), T)]) -> Option<U>,
+    fill: impl Fn(usize, usize, &U) -> T,
+) -> Vec<T> {
+    let mut values: Vec<Option<T>> = vec![None; tile.width * tile.height];
+    let index = |x: usize, y: usize| (y - tile.y) * tile.width + x - tile.x;
+    let get = |values: &mut Vec<Option<T>>, (x, y): (usize, usize)| {
+        values[index(x, y)].get_or_insert_with(|| compute(x, y)).clone()
+    };
+
+    let mut rectangles = vec![tile];
+    while let Some(rectangle) = rectangles.pop() {
+        let Tile { x, y, width, height } = rectangle;
+        // Without an inside there is nothing to fill
+        if width < 3 || height < 3 {
+            rectangle.pixels().for_each(|xy| { get(&mut values, xy); });
+            continue;
+        }
+
+        let border: Vec<_> = rectangle.pixels()
+            .filter(|(px, py)| *px == x || *py == y || *px == x + width - 1
+                || *py == y + height - 1)
+            .map(|xy| (xy, get(&mut values, xy)))
+            .collect();
+        if let Some(value) = uniform(&border) {
+            let inside = Tile { x: x + 1, y: y + 1, width: width - 2, height: height - 2 };
+            for (px, py) in inside.pixels() {
+                values[index(px, py)] = Some(fill(px, py, &value));
+            }
+            continue;
+        }
+
+        let (left, top) = (width / 2, height / 2);
+        rectangles.extend([
+            Tile { x, y, width: left, height: top },
+            Tile { x: x + left, y, width: width - left, height: top },
+            Tile { x, y: y + top, width: left, height: height - top },
+            Tile { x: x + left, y: y + top, width: width - left, height: height - top },
+        ]);
+    }
+
+    values.into_iter().map(Option::unwrap).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Reported after every finished tile, from the thread that rendered it. With
+/// antialiasing every tile is reported a second time once its extra samples
+/// are taken, counting `finished` from the start again.
+pub struct Progress {
+    pub tile: Tile,
+    /// Tiles finished so far, including `tile`
+    pub finished: usize,
+    pub total: usize,
+}
+
+/// Called with the `Progress` of a render
+pub type ProgressFn<'a> = &'a (dyn Fn(Progress) + Sync);
+
+#[derive(Debug, Clone, Default)]
+/// Shared flag to stop a render early, cloned tokens cancel the same render
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A render was stopped by its `CancellationToken`, only the tiles finished
+/// before that were updated
+pub struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "render cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::complex_number::ComplexNumber;
+    use crate::config::antialiasing::Antialiasing;
+    use crate::config::render_strategy::RenderStrategy;
+    use crate::config::viewport::Viewport;
+    use crate::mandelbrot::Mandelbrot;
+    use crate::mandelbrot::tests::{config, grid, grid_iterations, iterations};
+
+    use super::{mariani_silver, Cancelled, CancellationToken, Tile};
+
+    #[test]
+    fn split() {
+        let tiles = Tile::split((5, 3), 2);
+
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(tiles[2], Tile { x: 4, y: 0, width: 1, height: 2 });
+        assert_eq!(tiles[5], Tile { x: 4, y: 2, width: 1, height: 1 });
+        let mut pixels: Vec<_> = tiles.iter().flat_map(Tile::pixels).collect();
+        pixels.sort_by_key(|(x, y)| (*y, *x));
+        assert_eq!(pixels.len(), 15);
+        pixels.dedup();
+        assert_eq!(pixels.len(), 15);
+    }
+
+    #[test]
+    fn cancel() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+
+        clone.cancel();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn mariani_silver_fills_uniform_borders() {
+        // A disc of 1s in a field of 0s, connected to the edge of the tile like
+        // the Mandelbrot set is to the edge of every view showing its boundary
+        let value = |x: usize, y: usize| {
+            ((x as f64 - 64.).powi(2) + (y as f64 - 64.).powi(2) < 6400.) as u32
+        };
+        let computed = Cell::new(0);
+        let tile = Tile { x: 3, y: 2, width: 125, height: 126 };
+        let values = mariani_silver(
+            tile,
+            |x, y| {
+                computed.set(computed.get() + 1);
+                value(x, y)
+            },
+            |border| border.iter().all(|(_, v)| *v == border[0].1).then_some(border[0].1),
+            |_, _, v| *v,
+        );
+
+        let expected: Vec<_> = tile.pixels().map(|(x, y)| value(x, y)).collect();
+        assert_eq!(values, expected);
+        assert!(computed.get() < expected.len() / 2, "{}", computed.get());
+    }
+
+    #[test]
+    fn mariani_silver_matches_brute_force() {
+        let config = |strategy| config((96, 64))
+            .with_tile_size(32)
+            .with_render_strategy(strategy);
+        let mut brute_force = Mandelbrot::new(config(RenderStrategy::BruteForce), 300);
+        let mut subdivided = Mandelbrot::new(config(RenderStrategy::MarianiSilver), 300);
+        for iters in [100, 200] {
+            brute_force.run(iters);
+            subdivided.run(iters);
+
+            let matching = iterations(&brute_force).into_iter().zip(iterations(&subdivided))
+                .filter(|(a, b)| a == b)
+                .count();
+            assert!(matching as f64 > 96. * 64. * 0.99, "{matching}");
+        }
+        let filled = subdivided.filled.iter().filter(|filled| **filled).count();
+        assert!(filled > 96 * 64 / 8, "{filled}");
+
+        // Inside the main cardioid only the borders of the four tiles are
+        // iterated
+        let viewport = Viewport::default()
+            .with_top_left(ComplexNumber::new(-0.3, 0.1))
+            .with_bottom_right(ComplexNumber::new(-0.1, -0.1))
+            .with_size(32., 32.);
+        let mut inside = Mandelbrot::new(config(RenderStrategy::MarianiSilver)
+                                             .with_dimensions((32, 32))
+                                             .with_tile_size(16)
+                                             .with_viewport(viewport), 50);
+        inside.run(50);
+        assert!(iterations(&inside).iter().all(|i| *i == 50));
+        assert_eq!(inside.filled.iter().filter(|filled| **filled).count(), 4 * 14 * 14);
+    }
+
+    #[test]
+    fn tiles_report_progress_and_cancel() {
+        let mut whole = Mandelbrot::new(config((50, 30)).with_tile_size(1000), 200);
+        let mut tiled = Mandelbrot::new(config((50, 30)).with_tile_size(16), 200);
+        whole.run(200);
+        let reported = Mutex::new(Vec::new());
+        tiled.run_with(200, &|progress| reported.lock().unwrap().push(progress),
+                       &CancellationToken::new()).unwrap();
+
+        assert_eq!(iterations(&whole), iterations(&tiled));
+        let mut reported = reported.into_inner().unwrap();
+        reported.sort_by_key(|progress| progress.finished);
+        assert_eq!(reported.len(), 8);
+        assert!(reported.iter().enumerate().all(|(i, p)| p.finished == i + 1 && p.total == 8));
+
+        // Two by two tiles over the 4×3 grid, the last row on its own
+        let mut grid = Mandelbrot::new(grid().with_tile_size(2), 10);
+        let reported = AtomicUsize::new(0);
+        grid.run_with(10, &|progress| {
+            assert_eq!(progress.total, 4);
+            reported.fetch_add(1, Ordering::Relaxed);
+        }, &CancellationToken::new()).unwrap();
+        assert_eq!(reported.into_inner(), 4);
+        assert_eq!(iterations(&grid), grid_iterations(10));
+
+        // Cancelling from the first finished tile leaves the rest untouched
+        let mut cancelled = Mandelbrot::new(config((50, 30)).with_tile_size(16), 200);
+        let cancel = CancellationToken::new();
+        let result = cancelled.run_with(200, &|_| cancel.cancel(), &cancel);
+        assert_eq!(result, Err(Cancelled));
+        let untouched = cancelled.values().iter().filter(|(i, _)| *i == 0).count();
+        assert!(untouched > 0, "nothing was cancelled");
+        assert!(untouched < 1500, "no tile finished");
+
+        // Antialiasing reports every tile again and cancels between its samples
+        let mut sampled = Mandelbrot::new(config((50, 30))
+                                              .with_tile_size(16)
+                                              .with_antialiasing(Antialiasing::Uniform(2)), 200);
+        let cancel = CancellationToken::new();
+        let reports = AtomicUsize::new(0);
+        let result = sampled.run_with(200, &|_| {
+            if reports.fetch_add(1, Ordering::Relaxed) + 1 == 9 {
+                cancel.cancel();
+            }
+        }, &cancel);
+        assert_eq!(result, Err(Cancelled));
+        assert_eq!(iterations(&sampled), iterations(&whole));
+        let unsampled = sampled.samples.iter().filter(|samples| samples.is_empty()).count();
+        assert!(unsampled > 0 && unsampled < 1500, "{unsampled}");
+    }
+}
@@ -1,15 +1,16 @@
 use std::cell::RefCell;
-use std::cmp;
 use std::fs::File;
 use std::io::BufWriter;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
 use fltk::{app, input};
 use fltk::app::{App, Scheme, Sender};
 use fltk::button::Button;
 use fltk::draw::*;
 use fltk::draw::Offscreen;
-use fltk::enums::{Align, Color, Event, FrameType};
+use fltk::enums::{Align, Color, Event, FrameType, Key};
 use fltk::frame::Frame;
 use fltk::group::Flex;
 use fltk::prelude::*;
@@ -18,11 +19,12 @@ use png::HasParameters;
 
 use mandelbrot::color_scale::ContinuousColorScale;
 use mandelbrot::complex_number::ComplexNumber;
+use mandelbrot::config::antialiasing::Antialiasing;
 use mandelbrot::config::MandelbrotConfig;
-use mandelbrot::config::viewport::Viewport;
-use mandelbrot::flatten_array;
+use mandelbrot::config::viewport::{CenteredViewport, Viewport};
 use mandelbrot::mandelbrot::Mandelbrot;
 use mandelbrot::pixel::{Pixel, PixelMath};
+use mandelbrot::tiles::CancellationToken;
 
 const WIDTH: i32 = 1000;
 const HEIGHT: i32 = 1000;
@@ -48,6 +50,7 @@ struct Settings {
 enum Message {
     Redraw,
     Zoom((i32, i32)),
+    Pan((i32, i32)),
     Mode,
     Save,
     Reset,
@@ -139,6 +142,9 @@ fn main() {
 
     let julia_set = Rc::new(RefCell::new(false));
 
+    // Julia constant and view for the last point clicked in the Mandelbrot set
+    let picked_julia = Rc::new(RefCell::new(None));
+
     let loading = Rc::new(RefCell::new(false));
 
     let zoom = Rc::new(RefCell::new(1));
@@ -151,11 +157,31 @@ fn main() {
         let sender = sender.clone();
         let selecting_center = selecting_center.clone();
         let loading = loading.clone();
+        // Last position of the pointer while the button is held, and whether
+        // it was dragged since it was pressed
+        let mut pressed = None;
+        let mut dragged = false;
         move |_, event| {
             if !*loading.borrow() {
                 match event {
                     Event::Push => {
+                        pressed = Some(app::event_coords());
+                        dragged = false;
+                        true
+                    }
+                    Event::Drag => {
+                        let (x, y) = app::event_coords();
+                        if let Some((last_x, last_y)) = pressed.replace((x, y)) {
+                            dragged = true;
+                            sender.send(Message::Pan((last_x - x, last_y - y)));
+                        }
+                        true
+                    }
+                    Event::Released => {
                         let coords = app::event_coords();
+                        if pressed.take().is_none() || dragged {
+                            return true;
+                        }
                         if *selecting_center.borrow() {
                             sender.send(Message::Center(coords));
                             sender.send(Message::Animation(AnimationState::Running));
@@ -195,35 +221,16 @@ fn main() {
                     sender.send(Message::Loading(false));
                 }
                 Message::Zoom((x, y)) => {
-                    let x1 = x - 200;
-                    let y1 = y - 200;
-                    let x2 = x + 200;
-                    let y2 = y + 200;
-
-                    let tl = mandelbrot.borrow().get_xy_complex(
-                        cmp::max(0, x1) as usize,
-                        cmp::max(0, y1) as usize,
-                    );
-                    let br = mandelbrot.borrow().get_xy_complex(
-                        cmp::min(WIDTH - 1, x2) as usize,
-                        cmp::min(HEIGHT - 1, y2) as usize,
-                    );
-
-                    if let (Some(tl), Some(br)) = (tl, br) {
+                    if let Some(julia) = mandelbrot.borrow().julia_at(x as usize, y as usize) {
+                        picked_julia.replace(Some(julia));
+                    }
+
+                    if (0..WIDTH).contains(&x) && (0..HEIGHT).contains(&y) {
                         offs.borrow_mut().begin();
                         draw_rect_fill(0, 0, WIDTH, HEIGHT, Color::White);
-                        let viewport = Viewport::<f64> {
-                            top_left: tl,
-                            bottom_right: br,
-                            width: WIDTH as f64,
-                            height: HEIGHT as f64,
-                        };
-                        mandelbrot.borrow_mut().update(viewport);
-                        if *julia_set.borrow() {
-                            mandelbrot.borrow_mut().julia_set(ITERATIONS * *zoom.borrow());
-                        } else {
-                            mandelbrot.borrow_mut().run(ITERATIONS * *zoom.borrow());
-                        }
+                        mandelbrot.borrow_mut().zoom((x as u32, y as u32), 200);
+                        run_cancellable(&mut mandelbrot.borrow_mut(), ITERATIONS * *zoom.borrow(),
+                                        &mut window);
                         let data = mandelbrot.borrow().get_pixels().clone();
                         draw_mandelbrot(&data);
                         offs.borrow_mut().end();
@@ -231,15 +238,30 @@ fn main() {
                         sender.send(Message::Redraw);
                     }
                 }
+                Message::Pan((dx, dy)) => {
+                    mandelbrot.borrow_mut().pan(dx, dy);
+                    offs.borrow_mut().begin();
+                    let data = mandelbrot.borrow().get_pixels().clone();
+                    draw_mandelbrot(&data);
+                    offs.borrow_mut().end();
+                    mandelbrot_frame.redraw();
+                }
                 Message::Mode => {
                     sender.send(Message::Loading(true));
                     if julia_set.replace_with(|&mut val| !val) {
-                        mandelbrot.borrow_mut().run(ITERATIONS * *zoom.borrow());
+                        mandelbrot.borrow_mut().set_julia(None, default_viewport());
                         julia_btn.set_label("Switch to julia set");
                     } else {
-                        mandelbrot.borrow_mut().julia_set(ITERATIONS * *zoom.borrow());
+                        // Without a click use the centre of the current view
+                        let picked = picked_julia.take().or_else(|| mandelbrot.borrow()
+                            .julia_at((WIDTH / 2) as usize, (HEIGHT / 2) as usize));
+                        if let Some((c, viewport)) = picked {
+                            mandelbrot.borrow_mut().set_julia(Some(c), viewport);
+                        }
                         julia_btn.set_label("Switch to mandelbrot set");
                     }
+                    zoom.replace(1);
+                    run_cancellable(&mut mandelbrot.borrow_mut(), ITERATIONS, &mut window);
                     offs.borrow_mut().begin();
                     draw_rect_fill(0, 0, WIDTH, HEIGHT, Color::White);
                     let data = mandelbrot.borrow().get_pixels().clone();
@@ -258,9 +280,8 @@ fn main() {
                     let mut encoder = png::Encoder::new(buf, WIDTH as u32, HEIGHT as u32);
                     encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
                     let mut writer = encoder.write_header().unwrap();
-                    let data = mandelbrot.borrow().get_pixels().clone();
                     writer
-                        .write_image_data(flatten_array(&data).as_slice())
+                        .write_image_data(mandelbrot.borrow().rgba())
                         .unwrap();
                     sender.send(Message::Loading(false));
                 }
@@ -269,12 +290,10 @@ fn main() {
                     if animation_running {
                         sender.send(Message::Animation(AnimationState::Stopped))
                     }
-                    mandelbrot.borrow_mut().update(default_viewport());
-                    if *julia_set.borrow() {
-                        mandelbrot.borrow_mut().julia_set(ITERATIONS);
-                    } else {
-                        mandelbrot.borrow_mut().run(ITERATIONS);
-                    }
+                    mandelbrot.borrow_mut().set_julia(None, default_viewport());
+                    julia_set.replace(false);
+                    julia_btn.set_label("Switch to julia set");
+                    run_cancellable(&mut mandelbrot.borrow_mut(), ITERATIONS, &mut window);
                     let data = mandelbrot.borrow().get_pixels().clone();
                     let offs = offs.borrow_mut();
                     offs.begin();
@@ -326,11 +345,7 @@ fn main() {
                         viewport.top_left = a;
                         viewport.bottom_right = b;
                         mandelbrot.borrow_mut().update(viewport);
-                        if *julia_set.borrow() {
-                            mandelbrot.borrow_mut().julia_set(ITERATIONS);
-                        } else {
-                            mandelbrot.borrow_mut().run(ITERATIONS);
-                        }
+                        mandelbrot.borrow_mut().run(ITERATIONS);
                         if animation_running {
                             let data = mandelbrot.borrow().get_pixels().clone();
                             let offs = offs.borrow_mut();
@@ -452,6 +467,37 @@ fn settings_window(sender: Sender<Message>) -> DoubleWindow {
     settings_window
 }
 
+/// Runs `iterations` on another thread while `window` keeps handling events
+/// and shows the finished tiles in its label, Escape cancels the render and
+/// keeps the tiles finished so far
+fn run_cancellable(mandelbrot: &mut Mandelbrot<u8, f64>, iterations: u32, window: &mut Window) {
+    let cancel = CancellationToken::new();
+    let finished = AtomicUsize::new(0);
+    let total = AtomicUsize::new(0);
+    let result = thread::scope(|scope| {
+        let render = scope.spawn(|| mandelbrot.run_with(iterations, &|progress| {
+            finished.store(progress.finished, Ordering::Relaxed);
+            total.store(progress.total, Ordering::Relaxed);
+        }, &cancel));
+        while !render.is_finished() {
+            app::wait_for(0.05).unwrap();
+            if app::event_key_down(Key::Escape) {
+                cancel.cancel();
+            }
+            window.set_label(&format!(
+                "Rendering {}/{} tiles, press Escape to cancel",
+                finished.load(Ordering::Relaxed),
+                total.load(Ordering::Relaxed),
+            ));
+        }
+        render.join().unwrap()
+    });
+    window.set_label("");
+    if result.is_err() {
+        mandelbrot.redraw();
+    }
+}
+
 fn draw_mandelbrot(data: &[Vec<Pixel<u8>>]) {
     for y in 0..HEIGHT as usize {
         for x in 0..WIDTH as usize {
@@ -462,20 +508,18 @@ fn draw_mandelbrot(data: &[Vec<Pixel<u8>>]) {
     }
 }
 
-fn setup_mandelbrot() -> Mandelbrot<u8> {
-    let viewport = default_viewport();
-
-    let config = MandelbrotConfig::<u8> {
-        dimensions: (WIDTH as u32, HEIGHT as u32),
-        viewport,
-        color_fn: ContinuousColorScale::get_color_fn_boxed(200.0, 1.0, 1.0),
-    };
+fn setup_mandelbrot() -> Mandelbrot<u8, f64> {
+    let config = MandelbrotConfig::<u8, f64>::default()
+        .with_dimensions((WIDTH as u32, HEIGHT as u32))
+        .with_viewport(default_viewport())
+        .with_color_fn(ContinuousColorScale::get_color_fn_boxed(200.0, 1.0, 1.0))
+        .with_antialiasing(Antialiasing::Adaptive { samples: 3, threshold: 1 });
 
-    Mandelbrot::new(config)
+    Mandelbrot::new(config, ITERATIONS)
 }
 
 fn default_viewport() -> Viewport<f64> {
-    Viewport::default().with_size(WIDTH as f64, HEIGHT as f64)
+    CenteredViewport::fit(&Viewport::default(), (WIDTH as u32, HEIGHT as u32)).into()
 }
 
 fn get_rect_coords(x1: i32, y1: i32, x2: i32, y2: i32) -> (Coord<i32>, Coord<i32>, Coord<i32>,